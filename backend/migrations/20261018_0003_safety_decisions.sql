@@ -0,0 +1,12 @@
+-- Safety pre-screening decisions, one row per screened query
+CREATE TABLE IF NOT EXISTS safety_decisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id TEXT NOT NULL,
+    category TEXT NOT NULL, -- 'crisis' | 'medical' | 'legal' | 'financial' | 'none'
+    action TEXT NOT NULL CHECK(action IN ('allow', 'guardrail', 'respond')),
+    source TEXT NOT NULL CHECK(source IN ('rules', 'model')),
+    matched_terms JSON, -- Rule terms that triggered the decision
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_safety_decisions_session ON safety_decisions(session_id);
@@ -0,0 +1,3 @@
+-- Prompt-injection heuristics recorded per reading
+-- {"signals": ["override_instructions"], "truncated": false}
+ALTER TABLE readings ADD COLUMN injection_signals JSON;
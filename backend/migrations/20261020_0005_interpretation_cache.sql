@@ -0,0 +1,8 @@
+-- Persistent layer of the interpretation cache
+CREATE TABLE IF NOT EXISTS interpretation_cache (
+    cache_key TEXT PRIMARY KEY NOT NULL, -- SHA-256 of deck version, persona, spread, cards, query
+    interpretation TEXT NOT NULL,
+    stored_at INTEGER NOT NULL -- Unix seconds, compared against the TTL
+);
+
+CREATE INDEX IF NOT EXISTS idx_interpretation_cache_stored ON interpretation_cache(stored_at);
@@ -0,0 +1,19 @@
+-- Token usage and estimated cost of every LLM call
+CREATE TABLE IF NOT EXISTS llm_usage (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id TEXT NOT NULL,
+    reading_id INTEGER, -- NULL for calls made before a reading exists (e.g. safety checks)
+    message_id INTEGER, -- The assistant message the call produced, if any
+    purpose TEXT NOT NULL, -- 'interpretation' | 'safety_classification'
+    model TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    cost_usd REAL NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(reading_id) REFERENCES readings(id) ON DELETE SET NULL,
+    FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_llm_usage_reading ON llm_usage(reading_id);
+CREATE INDEX IF NOT EXISTS idx_llm_usage_created ON llm_usage(created_at);
@@ -0,0 +1,61 @@
+-- Seeker's journal: one entry of reflections per reading
+CREATE TABLE IF NOT EXISTS journal_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    reading_id INTEGER NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL,
+    notes TEXT,
+    mood TEXT,
+    tags TEXT NOT NULL DEFAULT '[]', -- JSON array of lowercase tags
+    outcome TEXT, -- "What actually happened", written later
+    outcome_recorded_at DATETIME,
+    starred INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(reading_id) REFERENCES readings(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_journal_entries_user ON journal_entries(user_id, updated_at);
+
+-- Previous versions of an entry, captured on every edit
+CREATE TABLE IF NOT EXISTS journal_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_id INTEGER NOT NULL,
+    notes TEXT,
+    mood TEXT,
+    tags TEXT NOT NULL,
+    outcome TEXT,
+    starred INTEGER NOT NULL,
+    edited_at DATETIME, -- When this version was written (the entry's old updated_at)
+    replaced_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(entry_id) REFERENCES journal_entries(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_journal_revisions_entry ON journal_revisions(entry_id);
+
+CREATE TRIGGER journal_entries_revision AFTER UPDATE ON journal_entries BEGIN
+  INSERT INTO journal_revisions(entry_id, notes, mood, tags, outcome, starred, edited_at)
+  VALUES (old.id, old.notes, old.mood, old.tags, old.outcome, old.starred, old.updated_at);
+END;
+
+-- Full Text Search over journal text, searched alongside readings_fts
+CREATE VIRTUAL TABLE IF NOT EXISTS journal_fts USING fts5(
+    notes,
+    outcome,
+    tags,
+    content='journal_entries',
+    content_rowid='id'
+);
+
+CREATE TRIGGER journal_entries_ai AFTER INSERT ON journal_entries BEGIN
+  INSERT INTO journal_fts(rowid, notes, outcome, tags) VALUES (new.id, new.notes, new.outcome, new.tags);
+END;
+
+CREATE TRIGGER journal_entries_ad AFTER DELETE ON journal_entries BEGIN
+  INSERT INTO journal_fts(journal_fts, rowid, notes, outcome, tags) VALUES ('delete', old.id, old.notes, old.outcome, old.tags);
+END;
+
+CREATE TRIGGER journal_entries_au AFTER UPDATE ON journal_entries BEGIN
+  INSERT INTO journal_fts(journal_fts, rowid, notes, outcome, tags) VALUES ('delete', old.id, old.notes, old.outcome, old.tags);
+  INSERT INTO journal_fts(rowid, notes, outcome, tags) VALUES (new.id, new.notes, new.outcome, new.tags);
+END;
@@ -0,0 +1,22 @@
+-- Drawn cards, one row per card, so statistics don't have to parse readings.drawn_cards
+CREATE TABLE IF NOT EXISTS reading_cards (
+    reading_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    card_id TEXT NOT NULL,
+    reversed INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (reading_id, position),
+    FOREIGN KEY(reading_id) REFERENCES readings(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_reading_cards_card ON reading_cards(card_id);
+
+-- Backfill from the JSON column ([{card: {id, ...}, is_reversed, position_index}, ...])
+INSERT OR IGNORE INTO reading_cards (reading_id, position, card_id, reversed)
+SELECT r.id,
+       COALESCE(json_extract(c.value, '$.position_index'), c.key),
+       json_extract(c.value, '$.card.id'),
+       COALESCE(json_extract(c.value, '$.is_reversed'), 0)
+FROM readings r,
+     json_each(CASE WHEN json_valid(r.drawn_cards) THEN r.drawn_cards ELSE '[]' END) c
+WHERE c.type = 'object'
+  AND json_extract(c.value, '$.card.id') IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Public, read-only links to a reading, created explicitly by its owner
+CREATE TABLE IF NOT EXISTS reading_shares (
+    token TEXT PRIMARY KEY NOT NULL, -- Unguessable, random 128 bits in hex
+    reading_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    include_query INTEGER NOT NULL DEFAULT 0, -- The question stays private unless opted in
+    view_count INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    revoked_at DATETIME, -- Set when the owner revokes the link
+    FOREIGN KEY(reading_id) REFERENCES readings(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_reading_shares_reading ON reading_shares(reading_id);
@@ -0,0 +1,17 @@
+-- One card of the day per user or anonymous session, stored so it can't be re-rolled
+CREATE TABLE IF NOT EXISTS daily_cards (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,
+    session_id TEXT, -- Anonymous owners, identified by the X-Session-Id header
+    day TEXT NOT NULL, -- Calendar day in the owner's timezone (YYYY-MM-DD)
+    timezone TEXT NOT NULL, -- IANA name the day was computed in
+    card_id TEXT NOT NULL,
+    reversed INTEGER NOT NULL DEFAULT 0,
+    interpretation TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((user_id IS NULL) <> (session_id IS NULL)),
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_daily_cards_user ON daily_cards(user_id, day) WHERE user_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_daily_cards_session ON daily_cards(session_id, day) WHERE session_id IS NOT NULL;
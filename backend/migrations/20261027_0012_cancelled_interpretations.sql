@@ -0,0 +1,3 @@
+-- Set when the seeker stopped the interpretation; ai_interpretation then
+-- holds only the part that was delivered
+ALTER TABLE readings ADD COLUMN interpretation_cancelled INTEGER NOT NULL DEFAULT 0;
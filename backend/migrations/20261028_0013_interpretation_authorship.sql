@@ -0,0 +1,15 @@
+-- Who wrote each reading's interpretation: 'ai' for the model alone,
+-- 'human' or 'both' when a reader wrote it, with or without the model's draft
+ALTER TABLE readings ADD COLUMN authorship TEXT NOT NULL DEFAULT 'ai';
+-- The draft the model offered the reader, kept alongside what was sent
+ALTER TABLE readings ADD COLUMN ai_draft TEXT;
+
+-- A reader-written interpretation as it was sent, one row per part
+CREATE TABLE IF NOT EXISTS interpretation_parts (
+    reading_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    authorship TEXT NOT NULL,
+    PRIMARY KEY (reading_id, position),
+    FOREIGN KEY(reading_id) REFERENCES readings(id) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- Readings drawn for streaming are saved before they are interpreted, so
+-- the interpretation arrives by UPDATE and the index must follow it
+CREATE TRIGGER IF NOT EXISTS readings_au AFTER UPDATE OF user_query, ai_interpretation ON readings BEGIN
+  INSERT INTO readings_fts(readings_fts, rowid, user_query, ai_interpretation) VALUES ('delete', old.id, old.user_query, old.ai_interpretation);
+  INSERT INTO readings_fts(rowid, user_query, ai_interpretation) VALUES (new.id, new.user_query, new.ai_interpretation);
+END;
@@ -0,0 +1,4 @@
+-- SHA-256 of the claim token handed to whoever started an anonymous session;
+-- a login has to present the token to move the session into an account.
+-- Cleared once claimed. Sessions from before this column can't be claimed.
+ALTER TABLE sessions ADD COLUMN claim_token_hash TEXT;
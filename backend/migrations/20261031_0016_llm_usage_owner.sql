@@ -0,0 +1,37 @@
+-- Daily-card calls belong to an account or an anonymous visitor, not to a
+-- reading session. They used to be stored as 'user:<id>' / 'session:<id>'
+-- in session_id; move them into their own column so session_id only ever
+-- holds real session IDs. SQLite can't drop NOT NULL in place, so rebuild.
+CREATE TABLE llm_usage_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id TEXT, -- NULL for calls made outside a session (daily cards)
+    owner TEXT, -- 'user:<id>' | 'session:<id>' for daily-card calls
+    reading_id INTEGER,
+    message_id INTEGER,
+    purpose TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    cost_usd REAL NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(reading_id) REFERENCES readings(id) ON DELETE SET NULL,
+    FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE SET NULL
+);
+
+INSERT INTO llm_usage_new (
+    id, session_id, owner, reading_id, message_id, purpose, model,
+    prompt_tokens, completion_tokens, latency_ms, cost_usd, created_at
+)
+SELECT id,
+       CASE WHEN purpose = 'daily_interpretation' THEN NULL ELSE session_id END,
+       CASE WHEN purpose = 'daily_interpretation' THEN session_id END,
+       reading_id, message_id, purpose, model,
+       prompt_tokens, completion_tokens, latency_ms, cost_usd, created_at
+FROM llm_usage;
+
+DROP TABLE llm_usage;
+ALTER TABLE llm_usage_new RENAME TO llm_usage;
+
+CREATE INDEX IF NOT EXISTS idx_llm_usage_reading ON llm_usage(reading_id);
+CREATE INDEX IF NOT EXISTS idx_llm_usage_created ON llm_usage(created_at);
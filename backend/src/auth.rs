@@ -0,0 +1,743 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use rand::Rng;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::db;
+use crate::error::{AppError, ErrorCode};
+use crate::models::{SessionClaim, User};
+use crate::state::AppState;
+
+/// How long an OIDC login may take between redirect and callback.
+const LOGIN_STATE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Most OIDC logins that may be waiting for their callback at once.
+const MAX_PENDING_LOGINS: usize = 10_000;
+
+/// Header carrying an anonymous session's claim token on requests that act
+/// on the session, such as exporting it.
+pub const CLAIM_HEADER: &str = "x-claim-token";
+
+/// Most anonymous sessions one login may claim.
+pub const MAX_CLAIMED_SESSIONS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub token_ttl_days: u64,
+    pub min_password_length: usize,
+    /// OIDC providers by name, e.g. `[auth.oidc.google]`.
+    pub oidc: HashMap<String, OidcProviderConfig>,
+    /// Bearer token for operator endpoints such as usage and cache stats;
+    /// unset, they refuse everyone.
+    pub admin_token: Option<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            token_ttl_days: 30,
+            min_password_length: 8,
+            oidc: HashMap::new(),
+            admin_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OidcProviderConfig {
+    pub authorize_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Must match the callback URL registered with the provider.
+    pub redirect_uri: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["openid".to_string(), "email".to_string(), "profile".to_string()]
+}
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("a valid email address is required")]
+    InvalidEmail,
+    #[error("password must be at least {0} characters")]
+    WeakPassword(usize),
+    #[error("an account with this email already exists")]
+    EmailTaken,
+    #[error("invalid email or password")]
+    InvalidCredentials,
+    #[error("authentication required")]
+    MissingToken,
+    #[error("invalid or expired token")]
+    InvalidToken,
+    #[error("admin access required")]
+    NotAdmin,
+    #[error("unknown identity provider: {0}")]
+    UnknownProvider(String),
+    #[error("login request expired or was already used")]
+    InvalidState,
+    #[error("too many logins in progress, try again shortly")]
+    TooManyLogins,
+    #[error("an account with this email already exists; log in and link the provider from there")]
+    LinkRequired,
+    #[error("this identity is already linked to another account")]
+    IdentityTaken,
+    #[error("identity provider error: {0}")]
+    Provider(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("password hashing failed: {0}")]
+    Hashing(String),
+}
+
+impl From<AuthError> for AppError {
+    fn from(error: AuthError) -> Self {
+        let code = match error {
+            AuthError::InvalidEmail | AuthError::WeakPassword(_) | AuthError::InvalidState => {
+                ErrorCode::InvalidRequest
+            }
+            AuthError::EmailTaken | AuthError::LinkRequired | AuthError::IdentityTaken => {
+                ErrorCode::Conflict
+            }
+            AuthError::TooManyLogins => ErrorCode::RateLimited,
+            AuthError::InvalidCredentials | AuthError::MissingToken | AuthError::InvalidToken => {
+                ErrorCode::Unauthorized
+            }
+            AuthError::NotAdmin => ErrorCode::Forbidden,
+            AuthError::UnknownProvider(_) => ErrorCode::NotFound,
+            AuthError::Provider(_) => ErrorCode::UpstreamError,
+            AuthError::Database(_) | AuthError::Hashing(_) => {
+                return AppError::internal("Authentication failed", error)
+            }
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+/// Who an external provider says the user is.
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+/// A third-party login. Implementations turn an authorization code from the
+/// provider's redirect into an identity.
+pub trait IdentityProvider: Send + Sync {
+    fn authorize_url(&self, state: &str) -> String;
+
+    fn exchange<'a>(&'a self, code: &'a str) -> BoxFuture<'a, Result<ExternalIdentity, AuthError>>;
+}
+
+/// Standard OIDC authorization code flow, reading the identity from the
+/// provider's userinfo endpoint.
+pub struct OidcProvider {
+    client: Client,
+    config: OidcProviderConfig,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    name: Option<String>,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcProviderConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    async fn fetch_identity(&self, code: &str) -> Result<ExternalIdentity, AuthError> {
+        let provider_error = |e: reqwest::Error| AuthError::Provider(e.to_string());
+
+        let token: TokenResponse = self
+            .client
+            .post(&self.config.token_url)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_uri),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+            ])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)?;
+
+        let info: UserInfo = self
+            .client
+            .get(&self.config.userinfo_url)
+            .bearer_auth(&token.access_token)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(provider_error)?
+            .json()
+            .await
+            .map_err(provider_error)?;
+
+        Ok(ExternalIdentity {
+            subject: info.sub,
+            email: info.email,
+            email_verified: info.email_verified,
+            name: info.name,
+        })
+    }
+}
+
+impl IdentityProvider for OidcProvider {
+    fn authorize_url(&self, state: &str) -> String {
+        let scopes = self.config.scopes.join(" ");
+        let params = [
+            ("response_type", "code"),
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("scope", scopes.as_str()),
+            ("state", state),
+        ];
+        // The URL was checked at startup
+        Url::parse_with_params(&self.config.authorize_url, &params)
+            .map(String::from)
+            .unwrap_or_default()
+    }
+
+    fn exchange<'a>(&'a self, code: &'a str) -> BoxFuture<'a, Result<ExternalIdentity, AuthError>> {
+        Box::pin(self.fetch_identity(code))
+    }
+}
+
+/// A token handed to the client; only its hash is stored.
+pub struct IssuedToken {
+    pub token: String,
+    pub expires_at: i64,
+}
+
+struct PendingLogin {
+    provider: String,
+    /// The signed-in account the identity is being linked to, if any.
+    link_user: Option<i64>,
+    started: Instant,
+}
+
+/// Accounts, passwords, bearer tokens and third-party logins.
+pub struct AuthService {
+    config: AuthConfig,
+    db: SqlitePool,
+    providers: HashMap<String, Box<dyn IdentityProvider>>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::rng().fill(&mut buf[..]);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn normalize_email(email: &str) -> Result<String, AuthError> {
+    let email = email.trim().to_lowercase();
+    let valid = email.len() <= 254
+        && !email.contains(char::is_whitespace)
+        && email
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+    if valid {
+        Ok(email)
+    } else {
+        Err(AuthError::InvalidEmail)
+    }
+}
+
+async fn hash_password(password: String) -> Result<String, AuthError> {
+    // Argon2 is deliberately slow; keep it off the async workers
+    tokio::task::spawn_blocking(move || {
+        let mut salt = [0u8; 16];
+        rand::rng().fill(&mut salt);
+        let salt = SaltString::encode_b64(&salt).map_err(|e| AuthError::Hashing(e.to_string()))?;
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| AuthError::Hashing(e.to_string()))
+    })
+    .await
+    .map_err(|e| AuthError::Hashing(e.to_string()))?
+}
+
+/// Checked against when the email is unknown, so a miss takes as long as a hit.
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| {
+        let salt = SaltString::encode_b64(b"dummy-salt-bytes").expect("static salt is valid");
+        Argon2::default()
+            .hash_password(b"dummy password", &salt)
+            .expect("hashing a static password succeeds")
+            .to_string()
+    })
+}
+
+/// The account a login may sign into and the hash its password is checked
+/// against. Unknown emails and OIDC-only accounts get the dummy hash.
+fn login_hash(credentials: Option<(User, Option<String>)>) -> (Option<User>, String) {
+    match credentials {
+        Some((user, Some(hash))) => (Some(user), hash),
+        _ => (None, dummy_hash().to_string()),
+    }
+}
+
+/// Compare digests so the time taken says nothing about the token.
+fn is_admin_token(admin_token: Option<&str>, token: &str) -> bool {
+    admin_token.is_some_and(|admin_token| hash_token(token) == hash_token(admin_token))
+}
+
+async fn verify_password(password: String, hash: String) -> Result<bool, AuthError> {
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&hash).map_err(|e| AuthError::Hashing(e.to_string()))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok())
+    })
+    .await
+    .map_err(|e| AuthError::Hashing(e.to_string()))?
+}
+
+impl AuthService {
+    pub fn new(config: AuthConfig, db: SqlitePool) -> Self {
+        let providers = config
+            .oidc
+            .iter()
+            .map(|(name, provider)| {
+                let provider: Box<dyn IdentityProvider> = Box::new(OidcProvider::new(provider.clone()));
+                (name.clone(), provider)
+            })
+            .collect();
+
+        Self {
+            config,
+            db,
+            providers,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn signup(
+        &self,
+        email: &str,
+        password: String,
+        display_name: Option<&str>,
+    ) -> Result<User, AuthError> {
+        let email = normalize_email(email)?;
+        if password.chars().count() < self.config.min_password_length {
+            return Err(AuthError::WeakPassword(self.config.min_password_length));
+        }
+
+        let password_hash = hash_password(password).await?;
+        db::create_user(&self.db, Some(&email), Some(&password_hash), display_name)
+            .await
+            .map_err(|e| match e.as_database_error() {
+                Some(db_error) if db_error.is_unique_violation() => AuthError::EmailTaken,
+                _ => AuthError::Database(e),
+            })
+    }
+
+    pub async fn login(&self, email: &str, password: String) -> Result<User, AuthError> {
+        let email = normalize_email(email).map_err(|_| AuthError::InvalidCredentials)?;
+
+        // Unknown email or an OIDC-only account: still spend the time
+        let (user, hash) = login_hash(db::get_user_credentials(&self.db, &email).await?);
+
+        match (verify_password(password, hash).await?, user) {
+            (true, Some(user)) => Ok(user),
+            _ => Err(AuthError::InvalidCredentials),
+        }
+    }
+
+    pub async fn issue_token(&self, user_id: i64) -> Result<IssuedToken, AuthError> {
+        let token = random_hex(32);
+        let now = now_secs();
+        let expires_at = now + (self.config.token_ttl_days * 24 * 60 * 60) as i64;
+
+        db::save_auth_token(&self.db, &hash_token(&token), user_id, now, expires_at).await?;
+        Ok(IssuedToken { token, expires_at })
+    }
+
+    pub async fn authenticate(&self, token: &str) -> Result<AuthUser, AuthError> {
+        db::get_token_user(&self.db, &hash_token(token), now_secs())
+            .await?
+            .map(|user| AuthUser { id: user.id })
+            .ok_or(AuthError::InvalidToken)
+    }
+
+    pub async fn revoke(&self, token: &str) -> Result<(), AuthError> {
+        db::delete_auth_token(&self.db, &hash_token(token)).await?;
+        Ok(())
+    }
+
+    /// Move anonymous sessions into an account. Each needs the claim token
+    /// handed out when it started; sessions without a matching one are
+    /// skipped.
+    pub async fn claim_sessions(&self, user_id: i64, claims: &[SessionClaim]) -> Result<u64, AuthError> {
+        if claims.is_empty() {
+            return Ok(0);
+        }
+        let claims: Vec<(&str, String)> = claims
+            .iter()
+            .take(MAX_CLAIMED_SESSIONS)
+            .map(|claim| (claim.session_id.as_str(), hash_token(&claim.claim_token)))
+            .collect();
+        let claimed = db::claim_sessions(&self.db, user_id, &claims).await?;
+        tracing::info!(user_id = user_id, claimed = claimed, "Claimed anonymous sessions");
+        Ok(claimed)
+    }
+
+    /// Start a third-party login; returns the provider URL to redirect to.
+    /// With `link_user`, the identity is linked to that signed-in account
+    /// instead of logging in.
+    pub fn begin_oidc(&self, provider: &str, link_user: Option<i64>) -> Result<String, AuthError> {
+        let identity_provider = self
+            .providers
+            .get(provider)
+            .ok_or_else(|| AuthError::UnknownProvider(provider.to_string()))?;
+
+        let state = random_hex(16);
+        let mut pending = self.pending.lock().unwrap();
+        prune_pending(&mut pending);
+        if pending.len() >= MAX_PENDING_LOGINS {
+            return Err(AuthError::TooManyLogins);
+        }
+        pending.insert(
+            state.clone(),
+            PendingLogin {
+                provider: provider.to_string(),
+                link_user,
+                started: Instant::now(),
+            },
+        );
+
+        Ok(identity_provider.authorize_url(&state))
+    }
+
+    /// Finish a third-party login, logging into the account linked to the
+    /// identity or creating one. A provider-verified email that belongs to
+    /// an existing account is not enough to log into it: the owner has to
+    /// sign in and link the provider first.
+    pub async fn complete_oidc(&self, provider: &str, state: &str, code: &str) -> Result<User, AuthError> {
+        let login = {
+            let mut pending = self.pending.lock().unwrap();
+            let login = pending.remove(state);
+            prune_pending(&mut pending);
+            login
+        }
+        .filter(|login| login.provider == provider && login.started.elapsed() < LOGIN_STATE_TTL)
+        .ok_or(AuthError::InvalidState)?;
+
+        let identity_provider = self
+            .providers
+            .get(provider)
+            .ok_or_else(|| AuthError::UnknownProvider(provider.to_string()))?;
+        let identity = identity_provider.exchange(code).await?;
+
+        let linked = db::get_user_by_identity(&self.db, provider, &identity.subject).await?;
+        let user = match (linked, login.link_user) {
+            (Some(user), None) => return Ok(user),
+            (Some(user), Some(link_user)) if user.id == link_user => return Ok(user),
+            (Some(_), Some(_)) => return Err(AuthError::IdentityTaken),
+            (None, Some(link_user)) => db::get_user(&self.db, link_user)
+                .await?
+                .ok_or(AuthError::InvalidToken)?,
+            (None, None) => {
+                let email = identity
+                    .email
+                    .as_deref()
+                    .and_then(|email| normalize_email(email).ok());
+                let email = match email {
+                    Some(email) if db::get_user_by_email(&self.db, &email).await?.is_some() => {
+                        if identity.email_verified {
+                            return Err(AuthError::LinkRequired);
+                        }
+                        // Someone else's address, unconfirmed: keep the account but not the email
+                        None
+                    }
+                    email => email,
+                };
+                db::create_user(&self.db, email.as_deref(), None, identity.name.as_deref()).await?
+            }
+        };
+
+        db::link_user_identity(&self.db, provider, &identity.subject, user.id).await?;
+        tracing::info!(user_id = user.id, provider = %provider, "Linked external identity");
+        Ok(user)
+    }
+}
+
+/// Drop logins whose callback never came.
+fn prune_pending(pending: &mut HashMap<String, PendingLogin>) {
+    pending.retain(|_, login| login.started.elapsed() < LOGIN_STATE_TTL);
+}
+
+/// The signed-in user behind a request's bearer token.
+///
+/// Use `AuthUser` for routes that require an account and `Option<AuthUser>`
+/// for routes that also serve anonymous visitors.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: i64,
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+impl FromRequestParts<Arc<AppState>> for AuthUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers).ok_or(AuthError::MissingToken)?;
+        state.auth.authenticate(token).await
+    }
+}
+
+/// An operator presenting `auth.admin_token`.
+pub struct Admin;
+
+impl FromRequestParts<Arc<AppState>> for Admin {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers).ok_or(AuthError::MissingToken)?;
+        if is_admin_token(state.config.auth.admin_token.as_deref(), token) {
+            Ok(Admin)
+        } else {
+            Err(AuthError::NotAdmin)
+        }
+    }
+}
+
+impl OptionalFromRequestParts<Arc<AppState>> for AuthUser {
+    type Rejection = AuthError;
+
+    /// No token means anonymous; a bad token is still rejected.
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        match bearer_token(&parts.headers) {
+            Some(token) => state.auth.authenticate(token).await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out the same identity for any code; the redirect URL is just
+    /// the state, so tests can complete the login with it.
+    struct MockIdentityProvider {
+        identity: ExternalIdentity,
+    }
+
+    impl IdentityProvider for MockIdentityProvider {
+        fn authorize_url(&self, state: &str) -> String {
+            state.to_string()
+        }
+
+        fn exchange<'a>(&'a self, _code: &'a str) -> BoxFuture<'a, Result<ExternalIdentity, AuthError>> {
+            let identity = self.identity.clone();
+            Box::pin(async move { Ok(identity) })
+        }
+    }
+
+    async fn service() -> AuthService {
+        let db = db::init_db("sqlite::memory:", 1).await.unwrap();
+        AuthService::new(AuthConfig::default(), db)
+    }
+
+    fn with_provider(mut auth: AuthService, email: &str, email_verified: bool) -> AuthService {
+        let identity = ExternalIdentity {
+            subject: "subject-1".to_string(),
+            email: Some(email.to_string()),
+            email_verified,
+            name: Some("Seeker".to_string()),
+        };
+        auth.providers
+            .insert("mock".to_string(), Box::new(MockIdentityProvider { identity }));
+        auth
+    }
+
+    async fn oidc_login(auth: &AuthService, link_user: Option<i64>) -> Result<User, AuthError> {
+        let state = auth.begin_oidc("mock", link_user).unwrap();
+        auth.complete_oidc("mock", &state, "code").await
+    }
+
+    #[tokio::test]
+    async fn passwords_are_hashed_and_checked() {
+        let auth = service().await;
+        let user = auth
+            .signup("Seeker@Example.com", "correct horse".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(user.email.as_deref(), Some("seeker@example.com"));
+
+        let (_, stored) = db::get_user_credentials(&auth.db, "seeker@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        let stored = stored.unwrap();
+        assert!(stored.starts_with("$argon2"));
+        assert!(!stored.contains("correct horse"));
+
+        let logged_in = auth.login("seeker@example.com", "correct horse".to_string()).await.unwrap();
+        assert_eq!(logged_in.id, user.id);
+        assert!(matches!(
+            auth.login("seeker@example.com", "wrong horse".to_string()).await,
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_users_are_checked_against_the_dummy_hash() {
+        let (user, hash) = login_hash(None);
+        assert!(user.is_none());
+        assert_eq!(hash, dummy_hash());
+        assert!(PasswordHash::new(&hash).is_ok());
+        assert!(!verify_password("correct horse".to_string(), hash).await.unwrap());
+
+        // An account without a password can't be logged into with one
+        let oidc_only = User {
+            id: 1,
+            email: Some("seeker@example.com".to_string()),
+            display_name: None,
+            created_at: String::new(),
+        };
+        let (user, hash) = login_hash(Some((oidc_only, None)));
+        assert!(user.is_none());
+        assert_eq!(hash, dummy_hash());
+
+        let auth = service().await;
+        assert!(matches!(
+            auth.login("nobody@example.com", "dummy password".to_string()).await,
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_claim_token_works_once() {
+        let auth = service().await;
+        let user = auth.signup("seeker@example.com", "correct horse".to_string(), None).await.unwrap();
+        db::create_anonymous_session(&auth.db, "session-1", &hash_token("claim-1"))
+            .await
+            .unwrap();
+        let claim = |token: &str| SessionClaim {
+            session_id: "session-1".to_string(),
+            claim_token: token.to_string(),
+        };
+
+        assert_eq!(auth.claim_sessions(user.id, &[claim("wrong")]).await.unwrap(), 0);
+        assert_eq!(auth.claim_sessions(user.id, &[claim("claim-1")]).await.unwrap(), 1);
+        assert_eq!(auth.claim_sessions(user.id, &[claim("claim-1")]).await.unwrap(), 0);
+
+        let (owner, claim_hash) = db::get_session_owner(&auth.db, "session-1").await.unwrap().unwrap();
+        assert_eq!(owner, Some(user.id));
+        assert_eq!(claim_hash, None);
+    }
+
+    #[tokio::test]
+    async fn a_verified_email_of_an_existing_account_requires_linking() {
+        let auth = with_provider(service().await, "Seeker@Example.com", true);
+        let user = auth.signup("seeker@example.com", "correct horse".to_string(), None).await.unwrap();
+
+        assert!(matches!(oidc_login(&auth, None).await, Err(AuthError::LinkRequired)));
+        assert!(db::get_user_by_identity(&auth.db, "mock", "subject-1").await.unwrap().is_none());
+
+        // Once the owner links it, the identity logs into their account
+        assert_eq!(oidc_login(&auth, Some(user.id)).await.unwrap().id, user.id);
+        assert_eq!(oidc_login(&auth, None).await.unwrap().id, user.id);
+    }
+
+    #[tokio::test]
+    async fn an_unverified_email_of_an_existing_account_is_not_kept() {
+        let auth = with_provider(service().await, "seeker@example.com", false);
+        let user = auth.signup("seeker@example.com", "correct horse".to_string(), None).await.unwrap();
+
+        let created = oidc_login(&auth, None).await.unwrap();
+        assert_ne!(created.id, user.id);
+        assert_eq!(created.email, None);
+    }
+
+    #[tokio::test]
+    async fn a_login_state_is_used_once() {
+        let auth = with_provider(service().await, "seeker@example.com", true);
+        let state = auth.begin_oidc("mock", None).unwrap();
+        auth.complete_oidc("mock", &state, "code").await.unwrap();
+        assert!(matches!(
+            auth.complete_oidc("mock", &state, "code").await,
+            Err(AuthError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn only_the_configured_admin_token_is_accepted() {
+        assert!(is_admin_token(Some("s3cret-admin"), "s3cret-admin"));
+        assert!(!is_admin_token(Some("s3cret-admin"), "s3cret-admiN"));
+        assert!(!is_admin_token(Some("s3cret-admin"), ""));
+        assert!(!is_admin_token(None, "s3cret-admin"));
+    }
+}
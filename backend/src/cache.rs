@@ -0,0 +1,358 @@
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::db;
+use crate::models::DrawnCard;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub capacity: usize,
+    pub ttl_secs: u64,
+    pub persist: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            capacity: 512,
+            ttl_secs: 7 * 24 * 60 * 60,
+            persist: true,
+        }
+    }
+}
+
+/// Everything that determines an interpretation. Two requests with the same
+/// key are answered from the cache.
+pub struct CacheKeyParts<'a> {
+    pub deck_version: &'a str,
+    pub persona: &'a str,
+    pub spread: &'a str,
+    pub cards: &'a [DrawnCard],
+    pub query: &'a str,
+    pub guardrails: Option<&'a str>,
+}
+
+impl CacheKeyParts<'_> {
+    /// Content address of the request: a SHA-256 over its canonical form.
+    pub fn key(&self) -> String {
+        let cards = self
+            .cards
+            .iter()
+            .map(|c| {
+                let orientation = if c.is_reversed { "r" } else { "u" };
+                format!("{}:{}:{}", c.position_index, c.card.id, orientation)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut hasher = Sha256::new();
+        for part in [
+            self.deck_version,
+            self.persona,
+            self.spread,
+            &cards,
+            &normalize_query(self.query),
+            self.guardrails.unwrap_or(""),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0u8]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+fn normalize_query(query: &str) -> String {
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['?', '!', '.', ' '])
+        .to_string()
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub hits: u64,
+    pub memory_hits: u64,
+    pub persistent_hits: u64,
+    pub misses: u64,
+    /// Lookups skipped because the request asked for a fresh interpretation.
+    pub bypassed: u64,
+    pub hit_rate: f64,
+    pub memory_entries: usize,
+    pub capacity: usize,
+}
+
+struct CacheEntry {
+    interpretation: String,
+    stored_at: u64,
+}
+
+/// Content-addressed interpretation cache: an in-memory LRU backed by the
+/// `interpretation_cache` table.
+pub struct InterpretationCache {
+    config: CacheConfig,
+    memory: Mutex<LruCache<String, CacheEntry>>,
+    pool: Option<SqlitePool>,
+    memory_hits: AtomicU64,
+    persistent_hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl InterpretationCache {
+    pub fn new(config: CacheConfig, pool: Option<SqlitePool>) -> Self {
+        let capacity = NonZeroUsize::new(config.capacity).unwrap_or(NonZeroUsize::MIN);
+        let pool = if config.persist { pool } else { None };
+
+        Self {
+            config,
+            memory: Mutex::new(LruCache::new(capacity)),
+            pool,
+            memory_hits: AtomicU64::new(0),
+            persistent_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            bypassed: AtomicU64::new(0),
+        }
+    }
+
+    fn is_fresh(&self, stored_at: u64) -> bool {
+        now_secs().saturating_sub(stored_at) < self.config.ttl_secs
+    }
+
+    /// Look a key up, memory first. Returns `None` when disabled or bypassed.
+    pub async fn get(&self, key: &str, bypass: bool) -> Option<String> {
+        if !self.config.enabled {
+            return None;
+        }
+        if bypass {
+            self.bypassed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        {
+            let mut memory = self.memory.lock().unwrap();
+            match memory.get(key) {
+                Some(entry) if self.is_fresh(entry.stored_at) => {
+                    self.memory_hits.fetch_add(1, Ordering::Relaxed);
+                    return Some(entry.interpretation.clone());
+                }
+                Some(_) => {
+                    memory.pop(key);
+                }
+                None => {}
+            }
+        }
+
+        if let Some(pool) = &self.pool {
+            let min_stored_at = now_secs().saturating_sub(self.config.ttl_secs);
+            match db::get_cached_interpretation(pool, key, min_stored_at as i64).await {
+                Ok(Some((interpretation, stored_at))) => {
+                    self.persistent_hits.fetch_add(1, Ordering::Relaxed);
+                    self.memory.lock().unwrap().put(
+                        key.to_string(),
+                        CacheEntry {
+                            interpretation: interpretation.clone(),
+                            stored_at: stored_at as u64,
+                        },
+                    );
+                    return Some(interpretation);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Interpretation cache lookup failed: {}", e),
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    pub async fn put(&self, key: &str, interpretation: &str) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let stored_at = now_secs();
+        self.memory.lock().unwrap().put(
+            key.to_string(),
+            CacheEntry {
+                interpretation: interpretation.to_string(),
+                stored_at,
+            },
+        );
+
+        if let Some(pool) = &self.pool {
+            if let Err(e) =
+                db::save_cached_interpretation(pool, key, interpretation, stored_at as i64).await
+            {
+                tracing::warn!("Failed to persist cached interpretation: {}", e);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let memory_hits = self.memory_hits.load(Ordering::Relaxed);
+        let persistent_hits = self.persistent_hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let hits = memory_hits + persistent_hits;
+        let lookups = hits + misses;
+
+        CacheStats {
+            enabled: self.config.enabled,
+            hits,
+            memory_hits,
+            persistent_hits,
+            misses,
+            bypassed: self.bypassed.load(Ordering::Relaxed),
+            hit_rate: if lookups == 0 { 0.0 } else { hits as f64 / lookups as f64 },
+            memory_entries: self.memory.lock().unwrap().len(),
+            capacity: self.config.capacity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(capacity: usize, ttl_secs: u64, persist: bool) -> CacheConfig {
+        CacheConfig {
+            enabled: true,
+            capacity,
+            ttl_secs,
+            persist,
+        }
+    }
+
+    async fn pool() -> SqlitePool {
+        db::init_db("sqlite::memory:", 1).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn the_least_recently_used_entry_is_evicted() {
+        let cache = InterpretationCache::new(config(2, 60, false), None);
+        cache.put("a", "first").await;
+        cache.put("b", "second").await;
+        assert_eq!(cache.get("a", false).await.as_deref(), Some("first"));
+
+        cache.put("c", "third").await;
+        assert_eq!(cache.get("b", false).await, None);
+        assert_eq!(cache.get("a", false).await.as_deref(), Some("first"));
+        assert_eq!(cache.get("c", false).await.as_deref(), Some("third"));
+
+        let stats = cache.stats();
+        assert_eq!(stats.memory_entries, 2);
+        assert_eq!((stats.memory_hits, stats.misses), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_dropped() {
+        let cache = InterpretationCache::new(config(8, 60, false), None);
+        cache.memory.lock().unwrap().put(
+            "old".to_string(),
+            CacheEntry {
+                interpretation: "stale".to_string(),
+                stored_at: now_secs() - 61,
+            },
+        );
+        cache.put("new", "fresh").await;
+
+        assert_eq!(cache.get("old", false).await, None);
+        assert_eq!(cache.get("new", false).await.as_deref(), Some("fresh"));
+        assert_eq!(cache.stats().memory_entries, 1);
+    }
+
+    #[tokio::test]
+    async fn persisted_entries_are_loaded_back() {
+        let pool = pool().await;
+        InterpretationCache::new(config(8, 60, true), Some(pool.clone()))
+            .put("k", "remembered")
+            .await;
+
+        // A new cache, as after a restart, starts with empty memory
+        let cache = InterpretationCache::new(config(8, 60, true), Some(pool));
+        assert_eq!(cache.stats().memory_entries, 0);
+        assert_eq!(cache.get("k", false).await.as_deref(), Some("remembered"));
+        assert_eq!(cache.get("k", false).await.as_deref(), Some("remembered"));
+
+        let stats = cache.stats();
+        assert_eq!((stats.persistent_hits, stats.memory_hits), (1, 1));
+        assert_eq!(stats.memory_entries, 1);
+    }
+
+    #[tokio::test]
+    async fn expired_persisted_entries_are_not_loaded() {
+        let pool = pool().await;
+        let stored_at = now_secs() as i64 - 61;
+        db::save_cached_interpretation(&pool, "old", "stale", stored_at).await.unwrap();
+
+        let cache = InterpretationCache::new(config(8, 60, true), Some(pool));
+        assert_eq!(cache.get("old", false).await, None);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[tokio::test]
+    async fn nothing_is_persisted_when_persistence_is_off() {
+        let pool = pool().await;
+        InterpretationCache::new(config(8, 60, false), Some(pool.clone()))
+            .put("k", "forgotten")
+            .await;
+
+        let cache = InterpretationCache::new(config(8, 60, true), Some(pool));
+        assert_eq!(cache.get("k", false).await, None);
+    }
+
+    #[tokio::test]
+    async fn bypassed_and_disabled_lookups_miss() {
+        let cache = InterpretationCache::new(config(8, 60, false), None);
+        cache.put("k", "cached").await;
+        assert_eq!(cache.get("k", true).await, None);
+        assert_eq!(cache.stats().bypassed, 1);
+
+        let disabled = InterpretationCache::new(
+            CacheConfig {
+                enabled: false,
+                ..config(8, 60, false)
+            },
+            None,
+        );
+        disabled.put("k", "cached").await;
+        assert_eq!(disabled.get("k", false).await, None);
+        assert_eq!(disabled.stats().memory_entries, 0);
+    }
+
+    #[test]
+    fn keys_ignore_case_spacing_and_trailing_punctuation() {
+        let key = |query| {
+            CacheKeyParts {
+                deck_version: "v1",
+                persona: "persona",
+                spread: "linear-0",
+                cards: &[],
+                query,
+                guardrails: None,
+            }
+            .key()
+        };
+        assert_eq!(key("Should I move?"), key("  should i   MOVE "));
+        assert_ne!(key("Should I move?"), key("Should I stay?"));
+    }
+}
@@ -0,0 +1,497 @@
+use axum::http::{HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+use crate::ai_service::AiConfig;
+use crate::auth::AuthConfig;
+use crate::cache::CacheConfig;
+use crate::export::ExportConfig;
+use crate::quota::QuotaConfig;
+use crate::rate_limit::RateLimitConfig;
+use crate::session::SessionConfig;
+use crate::usage::ModelPrice;
+use crate::ws_handler::WebSocketConfig;
+
+/// Read when `CONFIG_FILE` is unset; running without it uses the defaults.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to read config file {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error("failed to parse config file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// The address users reach this server at, used to build share links.
+    pub public_url: String,
+    /// How long shutdown waits for WebSocket connections to close and
+    /// interpretations in progress to be saved.
+    pub shutdown_grace_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            public_url: "http://localhost:3000".to_string(),
+            shutdown_grace_secs: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:tarot.db".to_string(),
+            max_connections: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeckConfig {
+    pub path: PathBuf,
+}
+
+impl Default for DeckConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("tarot_data.json"),
+        }
+    }
+}
+
+/// Cross-origin policy for the REST routes. WebSocket upgrades are checked
+/// against the same origin list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API; `"*"` allows any origin.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        // The Vite dev server; production deployments list their own origins
+        Self {
+            allowed_origins: vec![
+                "http://localhost:5173".to_string(),
+                "http://127.0.0.1:5173".to_string(),
+            ],
+            allowed_methods: vec![
+                "GET".to_string(),
+                "POST".to_string(),
+                "PATCH".to_string(),
+                "DELETE".to_string(),
+            ],
+            allowed_headers: vec![
+                "authorization".to_string(),
+                "content-type".to_string(),
+                "x-session-id".to_string(),
+                "x-claim-token".to_string(),
+            ],
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    /// Whether a request carrying this `Origin` header may use the API.
+    pub fn allows_origin(&self, origin: &HeaderValue) -> bool {
+        self.allows_any_origin()
+            || self
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+    }
+
+    /// Entries that don't parse were already rejected by `Config::validate`.
+    pub fn layer(&self) -> CorsLayer {
+        let origins = if self.allows_any_origin() {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(
+                self.allowed_origins
+                    .iter()
+                    .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+            )
+        };
+        let methods: Vec<Method> = self
+            .allowed_methods
+            .iter()
+            .filter_map(|method| Method::from_str(method).ok())
+            .collect();
+        let headers: Vec<HeaderName> = self
+            .allowed_headers
+            .iter()
+            .filter_map(|header| HeaderName::from_str(header).ok())
+            .collect();
+
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(AllowMethods::list(methods))
+            .allow_headers(AllowHeaders::list(headers))
+            .max_age(Duration::from_secs(self.max_age_secs))
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.allowed_origins.is_empty() {
+            errors.push("cors.allowed_origins must list at least one origin (or \"*\")".to_string());
+        }
+        for origin in &self.allowed_origins {
+            let valid = origin == "*"
+                || ((origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin.ends_with('/')
+                    && HeaderValue::from_str(origin).is_ok());
+            if !valid {
+                errors.push(format!(
+                    "cors.allowed_origins entry {:?} must be \"*\" or a scheme://host[:port] origin",
+                    origin
+                ));
+            }
+        }
+        for method in &self.allowed_methods {
+            if Method::from_str(method).is_err() {
+                errors.push(format!("cors.allowed_methods entry {:?} is not an HTTP method", method));
+            }
+        }
+        for header in &self.allowed_headers {
+            if HeaderName::from_str(header).is_err() {
+                errors.push(format!("cors.allowed_headers entry {:?} is not a header name", header));
+            }
+        }
+    }
+}
+
+/// All backend settings: a TOML file, then environment variable overrides.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub deck: DeckConfig,
+    pub cors: CorsConfig,
+    pub ai: AiConfig,
+    pub cache: CacheConfig,
+    pub quota: QuotaConfig,
+    pub rate_limit: RateLimitConfig,
+    pub auth: AuthConfig,
+    pub export: ExportConfig,
+    pub session: SessionConfig,
+    pub websocket: WebSocketConfig,
+    /// The file the settings were read from, if any.
+    #[serde(skip)]
+    pub file: Option<PathBuf>,
+}
+
+/// Applies environment overrides, collecting unparsable values as errors.
+struct EnvOverrides<'a> {
+    errors: &'a mut Vec<String>,
+}
+
+impl EnvOverrides<'_> {
+    fn parse<T>(&mut self, name: &str, target: &mut T)
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if let Ok(raw) = env::var(name) {
+            match raw.trim().parse() {
+                Ok(value) => *target = value,
+                Err(e) => self.errors.push(format!("{}={:?}: {}", name, raw, e)),
+            }
+        }
+    }
+
+    fn flag(&mut self, name: &str, target: &mut bool) {
+        if let Ok(raw) = env::var(name) {
+            match raw.trim().to_lowercase().as_str() {
+                "1" | "true" | "yes" => *target = true,
+                "0" | "false" | "no" | "" => *target = false,
+                _ => self
+                    .errors
+                    .push(format!("{}={:?}: expected true or false", name, raw)),
+            }
+        }
+    }
+
+    fn list(&mut self, name: &str, target: &mut Vec<String>) {
+        if let Ok(raw) = env::var(name) {
+            *target = raw
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect();
+        }
+    }
+}
+
+impl Config {
+    /// Load `CONFIG_FILE` (or `config.toml` when present), apply environment
+    /// overrides and validate the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let (path, required) = match env::var("CONFIG_FILE") {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+
+        let mut config = match fs::read_to_string(&path) {
+            Ok(raw) => {
+                let mut config: Config = toml::from_str(&raw)
+                    .map_err(|source| ConfigError::Parse { path: path.clone(), source })?;
+                config.file = Some(path);
+                config
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Config::default(),
+            Err(source) => return Err(ConfigError::Read { path, source }),
+        };
+
+        let mut errors = Vec::new();
+        config.apply_env(&mut EnvOverrides { errors: &mut errors });
+        if errors.is_empty() {
+            config.validate(&mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    fn apply_env(&mut self, env: &mut EnvOverrides<'_>) {
+        env.parse("BIND_ADDR", &mut self.server.bind);
+        env.parse("PUBLIC_URL", &mut self.server.public_url);
+        env.parse("SHUTDOWN_GRACE_SECS", &mut self.server.shutdown_grace_secs);
+
+        env.parse("DATABASE_URL", &mut self.database.url);
+        env.parse("DATABASE_MAX_CONNECTIONS", &mut self.database.max_connections);
+
+        env.parse("DECK_PATH", &mut self.deck.path);
+
+        env.list("CORS_ALLOWED_ORIGINS", &mut self.cors.allowed_origins);
+        env.list("CORS_ALLOWED_METHODS", &mut self.cors.allowed_methods);
+        env.list("CORS_ALLOWED_HEADERS", &mut self.cors.allowed_headers);
+        env.parse("CORS_MAX_AGE_SECS", &mut self.cors.max_age_secs);
+
+        if let Ok(key) = env::var("DEEPSEEK_API_KEY") {
+            self.ai.api_key = Some(key).filter(|key| !key.is_empty());
+        }
+        env.parse("AI_TEMPERATURE", &mut self.ai.temperature);
+        env.parse("AI_MAX_TOKENS", &mut self.ai.max_tokens);
+        env.flag("SAFETY_MODEL_CHECK", &mut self.ai.safety_model_check);
+        if let Ok(raw) = env::var("AI_PRICE_TABLE") {
+            match serde_json::from_str::<HashMap<String, ModelPrice>>(&raw) {
+                Ok(overrides) => self.ai.prices.extend(overrides),
+                Err(e) => env.errors.push(format!("AI_PRICE_TABLE: {}", e)),
+            }
+        }
+
+        let cache = &mut self.cache;
+        env.flag("AI_CACHE_ENABLED", &mut cache.enabled);
+        env.parse("AI_CACHE_CAPACITY", &mut cache.capacity);
+        env.parse("AI_CACHE_TTL_SECS", &mut cache.ttl_secs);
+        env.flag("AI_CACHE_PERSIST", &mut cache.persist);
+
+        let quota = &mut self.quota;
+        env.parse("QUOTA_READINGS_PER_HOUR_SESSION", &mut quota.readings_per_hour_per_session);
+        env.parse("QUOTA_READINGS_PER_HOUR_IP", &mut quota.readings_per_hour_per_ip);
+        env.parse("QUOTA_READINGS_PER_HOUR_GLOBAL", &mut quota.readings_per_hour_global);
+        env.parse("QUOTA_TOKENS_PER_DAY_SESSION", &mut quota.tokens_per_day_per_session);
+        env.parse("QUOTA_TOKENS_PER_DAY_IP", &mut quota.tokens_per_day_per_ip);
+        env.parse("QUOTA_TOKENS_PER_DAY_GLOBAL", &mut quota.tokens_per_day_global);
+        env.parse("QUOTA_FOLLOW_UPS_PER_READING", &mut quota.follow_ups_per_reading);
+        env.flag("TRUST_FORWARDED_FOR", &mut quota.trust_forwarded_for);
+
+        let limits = &mut self.rate_limit;
+        env.parse("RATE_LIMIT_REST_PER_MINUTE", &mut limits.rest_requests_per_minute);
+        env.parse("RATE_LIMIT_REST_BURST", &mut limits.rest_burst);
+        env.parse("RATE_LIMIT_WS_CONNECTIONS_PER_IP", &mut limits.ws_connections_per_ip);
+        env.parse("RATE_LIMIT_WS_MESSAGES_PER_SECOND", &mut limits.ws_messages_per_second);
+        env.parse("RATE_LIMIT_WS_MESSAGE_BURST", &mut limits.ws_message_burst);
+        env.parse("WS_MAX_FRAME_BYTES", &mut limits.ws_max_frame_bytes);
+        env.parse("WS_MAX_MESSAGE_BYTES", &mut limits.ws_max_message_bytes);
+
+        env.parse("AUTH_TOKEN_TTL_DAYS", &mut self.auth.token_ttl_days);
+        env.parse("AUTH_MIN_PASSWORD_LENGTH", &mut self.auth.min_password_length);
+        if let Ok(token) = env::var("ADMIN_TOKEN") {
+            self.auth.admin_token = Some(token).filter(|token| !token.is_empty());
+        }
+        // Keeps provider secrets out of the config file, e.g. OIDC_GOOGLE_CLIENT_SECRET
+        for (name, provider) in &mut self.auth.oidc {
+            let var = format!("OIDC_{}_CLIENT_SECRET", name.to_uppercase().replace('-', "_"));
+            env.parse(&var, &mut provider.client_secret);
+        }
+
+        if let Ok(path) = env::var("EXPORT_PDF_FONT") {
+            self.export.pdf_font = Some(PathBuf::from(path)).filter(|p| !p.as_os_str().is_empty());
+        }
+
+        env.parse("SESSION_RESUME_TTL_SECS", &mut self.session.resume_ttl_secs);
+        env.parse("SESSION_MAX_DETACHED", &mut self.session.max_detached);
+        env.parse("SESSION_MAX_PARTICIPANTS", &mut self.session.max_participants);
+
+        env.parse("WS_HEARTBEAT_INTERVAL_SECS", &mut self.websocket.heartbeat_interval_secs);
+        env.parse("WS_HEARTBEAT_TIMEOUT_SECS", &mut self.websocket.heartbeat_timeout_secs);
+        env.parse("WS_IDLE_TIMEOUT_SECS", &mut self.websocket.idle_timeout_secs);
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        let mut check = |ok: bool, message: &str| {
+            if !ok {
+                errors.push(message.to_string());
+            }
+        };
+
+        check(
+            reqwest::Url::parse(&self.server.public_url)
+                .is_ok_and(|u| matches!(u.scheme(), "http" | "https")),
+            "server.public_url must be an http(s) URL",
+        );
+        check(
+            self.database.url.starts_with("sqlite:"),
+            "database.url must be a sqlite: URL",
+        );
+        check(
+            self.database.max_connections >= 1,
+            "database.max_connections must be at least 1",
+        );
+        check(
+            self.deck.path.is_file(),
+            &format!("deck.path {} is not a readable file", self.deck.path.display()),
+        );
+
+        check(
+            (0.0..=2.0).contains(&self.ai.temperature),
+            "ai.temperature must be between 0.0 and 2.0",
+        );
+        check(self.ai.max_tokens >= 1, "ai.max_tokens must be at least 1");
+        for (model, price) in &self.ai.prices {
+            check(
+                price.prompt_per_million >= 0.0 && price.completion_per_million >= 0.0,
+                &format!("ai.prices.{} must not be negative", model),
+            );
+        }
+
+        check(
+            self.session.max_participants >= 1,
+            "session.max_participants must be at least 1",
+        );
+
+        let websocket = &self.websocket;
+        check(
+            websocket.heartbeat_interval_secs == 0
+                || websocket.heartbeat_timeout_secs == 0
+                || websocket.heartbeat_timeout_secs > websocket.heartbeat_interval_secs,
+            "websocket.heartbeat_timeout_secs must be longer than heartbeat_interval_secs",
+        );
+
+        check(
+            !self.cache.enabled || self.cache.capacity >= 1,
+            "cache.capacity must be at least 1 when the cache is enabled",
+        );
+
+        let limits = &self.rate_limit;
+        check(
+            limits.rest_requests_per_minute == 0 || limits.rest_burst >= 1,
+            "rate_limit.rest_burst must be at least 1",
+        );
+        check(
+            limits.ws_messages_per_second == 0 || limits.ws_message_burst >= 1,
+            "rate_limit.ws_message_burst must be at least 1",
+        );
+        check(
+            limits.ws_max_frame_bytes >= 1 && limits.ws_max_frame_bytes <= limits.ws_max_message_bytes,
+            "rate_limit.ws_max_frame_bytes must be between 1 and ws_max_message_bytes",
+        );
+
+        check(self.auth.token_ttl_days >= 1, "auth.token_ttl_days must be at least 1");
+        check(
+            self.auth.min_password_length >= 8,
+            "auth.min_password_length must be at least 8",
+        );
+        check(
+            self.auth.admin_token.as_ref().is_none_or(|token| token.len() >= 32),
+            "auth.admin_token must be at least 32 characters",
+        );
+        for (name, provider) in &self.auth.oidc {
+            for (field, url) in [
+                ("authorize_url", &provider.authorize_url),
+                ("token_url", &provider.token_url),
+                ("userinfo_url", &provider.userinfo_url),
+                ("redirect_uri", &provider.redirect_uri),
+            ] {
+                check(
+                    reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")),
+                    &format!("auth.oidc.{}.{} must be an http(s) URL", name, field),
+                );
+            }
+            check(
+                !provider.client_id.is_empty() && !provider.client_secret.is_empty(),
+                &format!("auth.oidc.{} needs a client_id and client_secret", name),
+            );
+        }
+
+        if let Some(font) = &self.export.pdf_font {
+            check(
+                font.is_file(),
+                &format!("export.pdf_font {} is not a readable file", font.display()),
+            );
+        }
+
+        self.cors.validate(errors);
+    }
+
+    /// A copy safe to log, with secrets replaced.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if config.ai.api_key.is_some() {
+            config.ai.api_key = Some(REDACTED.to_string());
+        }
+        for provider in config.auth.oidc.values_mut() {
+            provider.client_secret = REDACTED.to_string();
+        }
+        if config.auth.admin_token.is_some() {
+            config.auth.admin_token = Some(REDACTED.to_string());
+        }
+        config
+    }
+}
@@ -0,0 +1,212 @@
+use axum::{
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use thiserror::Error;
+
+use crate::db;
+use crate::error::{AppError, ErrorCode};
+use crate::models::{DailyCardRecord, TarotCard};
+use crate::rate_limit::{self, SESSION_HEADER};
+use crate::state::AppState;
+
+const DEFAULT_HISTORY_DAYS: u32 = 30;
+const MAX_HISTORY_DAYS: u32 = 366;
+
+#[derive(Debug, Error)]
+pub enum DailyError {
+    #[error("sign in or send an {} header", SESSION_HEADER)]
+    NoOwner,
+    #[error("invalid {} header", SESSION_HEADER)]
+    InvalidSession,
+    #[error("unknown timezone {0:?}")]
+    InvalidTimezone(String),
+    #[error("the deck has no cards")]
+    EmptyDeck,
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<DailyError> for AppError {
+    fn from(error: DailyError) -> Self {
+        let code = match error {
+            DailyError::NoOwner | DailyError::InvalidSession | DailyError::InvalidTimezone(_) => {
+                ErrorCode::InvalidRequest
+            }
+            DailyError::EmptyDeck | DailyError::Database(_) => {
+                return AppError::internal("Daily card request failed", error)
+            }
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+impl IntoResponse for DailyError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+/// Whose card of the day it is: an account, or an anonymous session.
+#[derive(Debug, Clone)]
+pub enum DailyOwner {
+    User(i64),
+    Session(String),
+}
+
+impl DailyOwner {
+    /// Signed-in users own their card across devices; anonymous visitors
+    /// are identified by the session header.
+    pub fn from_request(user_id: Option<i64>, headers: &HeaderMap) -> Result<Self, DailyError> {
+        if let Some(user_id) = user_id {
+            return Ok(DailyOwner::User(user_id));
+        }
+
+        let session = headers
+            .get(SESSION_HEADER)
+            .ok_or(DailyError::NoOwner)?
+            .to_str()
+            .map_err(|_| DailyError::InvalidSession)?
+            .trim();
+        if rate_limit::is_valid_session_id(session) {
+            Ok(DailyOwner::Session(session.to_string()))
+        } else {
+            Err(DailyError::InvalidSession)
+        }
+    }
+
+    /// Stable name for hashing the card and for quota accounting.
+    fn key(&self) -> String {
+        match self {
+            DailyOwner::User(id) => format!("user:{}", id),
+            DailyOwner::Session(id) => format!("session:{}", id),
+        }
+    }
+
+    fn columns(&self) -> (Option<i64>, Option<&str>) {
+        match self {
+            DailyOwner::User(id) => (Some(*id), None),
+            DailyOwner::Session(id) => (None, Some(id.as_str())),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DailyQuery {
+    /// IANA timezone the calendar day is taken in; UTC when absent.
+    pub tz: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DailyHistoryQuery {
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyCard {
+    #[serde(flatten)]
+    pub record: DailyCardRecord,
+    /// Full card details, if the card is still in the deck.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card: Option<TarotCard>,
+}
+
+fn with_details(state: &AppState, record: DailyCardRecord) -> DailyCard {
+    let card = state.deck.card(&record.card_id).cloned();
+    DailyCard { record, card }
+}
+
+fn parse_timezone(tz: Option<&str>) -> Result<Tz, DailyError> {
+    match tz.map(str::trim).filter(|t| !t.is_empty()) {
+        None => Ok(Tz::UTC),
+        Some(name) => name
+            .parse()
+            .map_err(|_| DailyError::InvalidTimezone(name.to_string())),
+    }
+}
+
+fn day_in(tz: &Tz) -> String {
+    Utc::now().with_timezone(tz).format("%Y-%m-%d").to_string()
+}
+
+/// The timezone today's card is taken in. Owners start in whatever timezone
+/// they ask for, after which the one their latest card was drawn in sticks:
+/// a different timezone is only honoured while its calendar day isn't ahead
+/// of the stored one, so hopping east can't reveal tomorrow's card early.
+async fn pinned_timezone(
+    state: &AppState,
+    owner: &DailyOwner,
+    requested: Tz,
+) -> Result<Tz, DailyError> {
+    let (user_id, session_id) = owner.columns();
+    let latest = db::get_daily_card_history(&state.db, user_id, session_id, 1).await?;
+    let stored = latest.first().and_then(|record| record.timezone.parse::<Tz>().ok());
+
+    Ok(match stored {
+        Some(stored) if day_in(&requested) > day_in(&stored) => stored,
+        _ => requested,
+    })
+}
+
+/// Today's card for `owner`. The first request of the day draws the card
+/// and its interpretation; every later request returns the stored one.
+pub async fn today(
+    state: &AppState,
+    owner: &DailyOwner,
+    query: &DailyQuery,
+    client_ip: IpAddr,
+) -> Result<DailyCard, DailyError> {
+    let tz = pinned_timezone(state, owner, parse_timezone(query.tz.as_deref())?).await?;
+    let day = day_in(&tz);
+    let (user_id, session_id) = owner.columns();
+
+    if let Some(record) = db::get_daily_card(&state.db, user_id, session_id, &day).await? {
+        return Ok(with_details(state, record));
+    }
+
+    let key = owner.key();
+    let drawn = state.deck.daily_card(&key, &day).ok_or(DailyError::EmptyDeck)?;
+
+    let offline_only = !state.quota.has_token_budget(&key, Some(client_ip));
+    let interpretation = state.ai.generate_daily_interpretation(&drawn, offline_only).await;
+
+    if let Some(usage) = &interpretation.usage {
+        let tokens = (usage.prompt_tokens + usage.completion_tokens) as u64;
+        state.quota.record_tokens(&key, Some(client_ip), tokens);
+        if let Err(e) = db::save_owner_llm_usage(&state.db, &key, usage).await {
+            tracing::error!(owner = %key, "Failed to record LLM usage: {}", e);
+        }
+    }
+
+    let record = DailyCardRecord {
+        day,
+        timezone: tz.name().to_string(),
+        card_id: drawn.card.id.clone(),
+        reversed: drawn.is_reversed,
+        interpretation: interpretation.text,
+        created_at: String::new(),
+    };
+    let record = db::save_daily_card(&state.db, user_id, session_id, &record).await?;
+    tracing::info!(owner = %key, day = %record.day, source = ?interpretation.source, "Daily card drawn");
+
+    Ok(with_details(state, record))
+}
+
+pub async fn history(
+    state: &AppState,
+    owner: &DailyOwner,
+    query: &DailyHistoryQuery,
+) -> Result<Vec<DailyCard>, DailyError> {
+    let (user_id, session_id) = owner.columns();
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_DAYS).clamp(1, MAX_HISTORY_DAYS);
+
+    Ok(db::get_daily_card_history(&state.db, user_id, session_id, limit)
+        .await?
+        .into_iter()
+        .map(|record| with_details(state, record))
+        .collect())
+}
@@ -1,11 +1,17 @@
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
-use sqlx::{FromRow, Pool, Sqlite};
+use sqlx::{FromRow, Pool, Sqlite, SqliteConnection, SqliteExecutor};
 use sqlx::migrate::MigrateDatabase;
 
-use crate::models::Message;
+use crate::export::ImportedReading;
+use crate::journal::JournalFields;
+use crate::models::{
+    Authorship, CardDraw, DailyCardRecord, DrawnCard, JournalEntry, JournalItem, JournalRevision, Message, ReadingCard,
+    ReadingSummary, Share, User,
+};
+use crate::usage::{LlmUsage, UsageQuery, UsageSummary};
 use std::str::FromStr;
 
-pub async fn init_db(database_url: &str) -> Result<Pool<Sqlite>, sqlx::Error> {
+pub async fn init_db(database_url: &str, max_connections: u32) -> Result<Pool<Sqlite>, sqlx::Error> {
     
     // Create database file if it doesn't exist
     if !sqlx::Sqlite::database_exists(database_url).await.unwrap_or(false) {
@@ -17,7 +23,7 @@ pub async fn init_db(database_url: &str) -> Result<Pool<Sqlite>, sqlx::Error> {
         .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
 
     let pool = SqlitePoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
         .connect_with(options)
         .await?;
 
@@ -29,32 +35,319 @@ pub async fn init_db(database_url: &str) -> Result<Pool<Sqlite>, sqlx::Error> {
     Ok(pool)
 }
 
+/// Save a reading; its interpretation may be filled in later with
+/// `save_interpretation`.
 pub async fn save_reading(
     pool: &Pool<Sqlite>,
     session_id: &str,
     query: &str,
-    cards_json: &serde_json::Value,
-    interpretation: &str,
+    cards: &[DrawnCard],
+    interpretation: Option<&str>,
+    injection_signals: &serde_json::Value,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let id = insert_reading(&mut tx, session_id, query, cards, interpretation, injection_signals).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// The reading, its session and its card rows, on a connection the caller
+/// holds a transaction on.
+async fn insert_reading(
+    tx: &mut SqliteConnection,
+    session_id: &str,
+    query: &str,
+    cards: &[DrawnCard],
+    interpretation: Option<&str>,
+    injection_signals: &serde_json::Value,
 ) -> Result<i64, sqlx::Error> {
+    let cards_json = serde_json::to_value(cards).unwrap_or_default();
+
     // Ensure session exists (quick dirty check or upsert)
     // SQLx SQLite upsert syntax: INSERT INTO ... ON CONFLICT DO NOTHING
     sqlx::query(
         "INSERT INTO sessions (id, user_metadata) VALUES (?1, '{}') ON CONFLICT(id) DO NOTHING",
     )
     .bind(session_id)
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
 
     let id = sqlx::query(
         r#"
-        INSERT INTO readings (session_id, user_query, drawn_cards, ai_interpretation)
-        VALUES (?1, ?2, ?3, ?4)
+        INSERT INTO readings (session_id, user_query, drawn_cards, ai_interpretation, injection_signals)
+        VALUES (?1, ?2, ?3, ?4, ?5)
         "#,
     )
     .bind(session_id)
     .bind(query)
-    .bind(cards_json)
+    .bind(&cards_json)
     .bind(interpretation)
+    .bind(injection_signals)
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    // The same cards, one row each, for statistics
+    for drawn in cards {
+        sqlx::query(
+            "INSERT INTO reading_cards (reading_id, position, card_id, reversed) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(id)
+        .bind(drawn.position_index as i64)
+        .bind(&drawn.card.id)
+        .bind(drawn.is_reversed)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(id)
+}
+
+/// Save a finished reading along with its opening exchange in `messages`
+/// and, when the provider was called, the token usage of that call.
+#[allow(clippy::too_many_arguments)]
+pub async fn save_interpreted_reading(
+    pool: &Pool<Sqlite>,
+    session_id: &str,
+    query: &str,
+    cards: &[DrawnCard],
+    interpretation: &str,
+    injection_signals: &serde_json::Value,
+    usage: Option<&LlmUsage>,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let reading_id = insert_reading(
+        &mut tx,
+        session_id,
+        query,
+        cards,
+        Some(interpretation),
+        injection_signals,
+    )
+    .await?;
+
+    save_exchange(&mut tx, session_id, reading_id, query, interpretation, usage).await?;
+    tx.commit().await?;
+    Ok(reading_id)
+}
+
+/// Fill in the interpretation of a reading saved before it was generated,
+/// along with its opening exchange and token usage.
+pub async fn save_interpretation(
+    pool: &Pool<Sqlite>,
+    session_id: &str,
+    reading_id: i64,
+    query: &str,
+    interpretation: &str,
+    usage: Option<&LlmUsage>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE readings SET ai_interpretation = ?1 WHERE id = ?2")
+        .bind(interpretation)
+        .bind(reading_id)
+        .execute(&mut *tx)
+        .await?;
+
+    save_exchange(&mut tx, session_id, reading_id, query, interpretation, usage).await?;
+    tx.commit().await
+}
+
+/// Record a follow-up interpretation of a reading as one more exchange in
+/// its messages, leaving the reading itself as it was.
+pub async fn save_follow_up(
+    pool: &Pool<Sqlite>,
+    session_id: &str,
+    reading_id: i64,
+    query: &str,
+    interpretation: &str,
+    usage: Option<&LlmUsage>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    save_exchange(&mut tx, session_id, reading_id, query, interpretation, usage).await?;
+    tx.commit().await
+}
+
+async fn save_exchange(
+    tx: &mut SqliteConnection,
+    session_id: &str,
+    reading_id: i64,
+    query: &str,
+    interpretation: &str,
+    usage: Option<&LlmUsage>,
+) -> Result<(), sqlx::Error> {
+    save_message(&mut *tx, reading_id, "user", query).await?;
+    let message_id = save_message(&mut *tx, reading_id, "assistant", interpretation).await?;
+
+    if let Some(usage) = usage {
+        save_llm_usage(&mut *tx, session_id, Some(reading_id), Some(message_id), usage).await?;
+    }
+    Ok(())
+}
+
+/// How many follow-ups a reading has had: every exchange in its messages
+/// after the first, which is the reading's own interpretation.
+pub async fn count_follow_ups(pool: &Pool<Sqlite>, reading_id: i64) -> Result<u64, sqlx::Error> {
+    let exchanges: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE reading_id = ?1 AND role = 'user'")
+            .bind(reading_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(exchanges.saturating_sub(1).max(0) as u64)
+}
+
+/// The session a reading was drawn in.
+pub async fn get_reading_session(pool: &Pool<Sqlite>, reading_id: i64) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT session_id FROM readings WHERE id = ?1")
+        .bind(reading_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Flag a reading whose interpretation the seeker stopped part way.
+pub async fn mark_interpretation_cancelled(pool: &Pool<Sqlite>, reading_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE readings SET interpretation_cancelled = 1 WHERE id = ?1")
+        .bind(reading_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Record a reader-written interpretation part by part, with who wrote it
+/// overall and the draft the reader was offered.
+pub async fn save_interpretation_parts(
+    pool: &Pool<Sqlite>,
+    reading_id: i64,
+    authorship: Authorship,
+    ai_draft: Option<&str>,
+    parts: &[(String, Authorship)],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("UPDATE readings SET authorship = ?1, ai_draft = ?2 WHERE id = ?3")
+        .bind(authorship.as_str())
+        .bind(ai_draft)
+        .bind(reading_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for (position, (content, authorship)) in parts.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO interpretation_parts (reading_id, position, content, authorship) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(reading_id)
+        .bind(position as i64)
+        .bind(content)
+        .bind(authorship.as_str())
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn save_llm_usage(
+    executor: impl SqliteExecutor<'_>,
+    session_id: &str,
+    reading_id: Option<i64>,
+    message_id: Option<i64>,
+    usage: &LlmUsage,
+) -> Result<i64, sqlx::Error> {
+    insert_llm_usage(executor, Some(session_id), None, reading_id, message_id, usage).await
+}
+
+/// Usage of a call made outside any session, such as the card of the day,
+/// attributed to `owner` ("user:<id>" or "session:<id>") instead.
+pub async fn save_owner_llm_usage(
+    executor: impl SqliteExecutor<'_>,
+    owner: &str,
+    usage: &LlmUsage,
+) -> Result<i64, sqlx::Error> {
+    insert_llm_usage(executor, None, Some(owner), None, None, usage).await
+}
+
+async fn insert_llm_usage(
+    executor: impl SqliteExecutor<'_>,
+    session_id: Option<&str>,
+    owner: Option<&str>,
+    reading_id: Option<i64>,
+    message_id: Option<i64>,
+    usage: &LlmUsage,
+) -> Result<i64, sqlx::Error> {
+    let id = sqlx::query(
+        r#"
+        INSERT INTO llm_usage (
+            session_id, owner, reading_id, message_id, purpose, model,
+            prompt_tokens, completion_tokens, latency_ms, cost_usd
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        "#,
+    )
+    .bind(session_id)
+    .bind(owner)
+    .bind(reading_id)
+    .bind(message_id)
+    .bind(usage.purpose)
+    .bind(&usage.model)
+    .bind(usage.prompt_tokens)
+    .bind(usage.completion_tokens)
+    .bind(usage.latency_ms as i64)
+    .bind(usage.cost_usd)
+    .execute(executor)
+    .await?
+    .last_insert_rowid();
+
+    Ok(id)
+}
+
+pub async fn summarize_llm_usage(
+    pool: &Pool<Sqlite>,
+    query: &UsageQuery,
+) -> Result<Vec<UsageSummary>, sqlx::Error> {
+    // The grouping column comes from a closed enum, never from user input.
+    // Only session grouping can see NULLs: daily-card calls have no session.
+    let sql = format!(
+        r#"
+        SELECT {column} AS key,
+               COUNT(*) AS calls,
+               COALESCE(SUM(prompt_tokens), 0) AS prompt_tokens,
+               COALESCE(SUM(completion_tokens), 0) AS completion_tokens,
+               COALESCE(SUM(cost_usd), 0.0) AS cost_usd,
+               COALESCE(AVG(latency_ms), 0.0) AS avg_latency_ms
+        FROM llm_usage
+        WHERE {column} IS NOT NULL
+          AND (?1 IS NULL OR date(created_at) >= date(?1))
+        GROUP BY key
+        ORDER BY key DESC
+        "#,
+        column = query.group_by.column()
+    );
+
+    sqlx::query_as(&sql)
+        .bind(query.since.as_deref())
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn save_safety_decision(
+    pool: &Pool<Sqlite>,
+    session_id: &str,
+    category: &str,
+    action: &str,
+    source: &str,
+    matched_terms: &serde_json::Value,
+) -> Result<i64, sqlx::Error> {
+    let id = sqlx::query(
+        r#"
+        INSERT INTO safety_decisions (session_id, category, action, source, matched_terms)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(session_id)
+    .bind(category)
+    .bind(action)
+    .bind(source)
+    .bind(matched_terms)
     .execute(pool)
     .await?
     .last_insert_rowid();
@@ -62,8 +355,50 @@ pub async fn save_reading(
     Ok(id)
 }
 
-pub async fn save_message(
+pub async fn get_cached_interpretation(
+    pool: &Pool<Sqlite>,
+    cache_key: &str,
+    min_stored_at: i64,
+) -> Result<Option<(String, i64)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT interpretation, stored_at
+        FROM interpretation_cache
+        WHERE cache_key = ?1 AND stored_at >= ?2
+        "#,
+    )
+    .bind(cache_key)
+    .bind(min_stored_at)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn save_cached_interpretation(
     pool: &Pool<Sqlite>,
+    cache_key: &str,
+    interpretation: &str,
+    stored_at: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO interpretation_cache (cache_key, interpretation, stored_at)
+        VALUES (?1, ?2, ?3)
+        ON CONFLICT(cache_key) DO UPDATE SET
+            interpretation = excluded.interpretation,
+            stored_at = excluded.stored_at
+        "#,
+    )
+    .bind(cache_key)
+    .bind(interpretation)
+    .bind(stored_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn save_message(
+    executor: impl SqliteExecutor<'_>,
     reading_id: i64,
     role: &str,
     content: &str,
@@ -77,7 +412,7 @@ pub async fn save_message(
     .bind(reading_id)
     .bind(role)
     .bind(content)
-    .execute(pool)
+    .execute(executor)
     .await?
     .last_insert_rowid();
 
@@ -86,8 +421,6 @@ pub async fn save_message(
 
 #[derive(FromRow)]
 struct MessageRow {
-    id: i64,
-    reading_id: i64,
     role: String,
     content: String,
     created_at: String,
@@ -99,10 +432,10 @@ pub async fn get_messages_for_reading(
 ) -> Result<Vec<Message>, sqlx::Error> {
     let rows: Vec<MessageRow> = sqlx::query_as(
         r#"
-        SELECT id, reading_id, role, content, created_at
+        SELECT role, content, created_at
         FROM messages
         WHERE reading_id = ?1
-        ORDER BY created_at ASC
+        ORDER BY created_at ASC, id ASC
         "#,
     )
     .bind(reading_id)
@@ -112,11 +445,921 @@ pub async fn get_messages_for_reading(
     Ok(rows
         .into_iter()
         .map(|r| Message {
-            id: r.id,
-            reading_id: r.reading_id,
             role: r.role,
             content: r.content,
             created_at: r.created_at,
         })
         .collect())
 }
+
+pub async fn create_user(
+    pool: &Pool<Sqlite>,
+    email: Option<&str>,
+    password_hash: Option<&str>,
+    display_name: Option<&str>,
+) -> Result<User, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        INSERT INTO users (email, password_hash, display_name)
+        VALUES (?1, ?2, ?3)
+        RETURNING id, email, display_name, created_at
+        "#,
+    )
+    .bind(email)
+    .bind(password_hash)
+    .bind(display_name)
+    .fetch_one(pool)
+    .await
+}
+
+#[derive(FromRow)]
+struct CredentialsRow {
+    id: i64,
+    email: Option<String>,
+    display_name: Option<String>,
+    created_at: String,
+    password_hash: Option<String>,
+}
+
+/// A user and their password hash, looked up by email (case-insensitive).
+pub async fn get_user_credentials(
+    pool: &Pool<Sqlite>,
+    email: &str,
+) -> Result<Option<(User, Option<String>)>, sqlx::Error> {
+    let row: Option<CredentialsRow> = sqlx::query_as(
+        r#"
+        SELECT id, email, display_name, created_at, password_hash
+        FROM users
+        WHERE email = ?1
+        "#,
+    )
+    .bind(email)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| {
+        (
+            User {
+                id: r.id,
+                email: r.email,
+                display_name: r.display_name,
+                created_at: r.created_at,
+            },
+            r.password_hash,
+        )
+    }))
+}
+
+pub async fn get_user(pool: &Pool<Sqlite>, id: i64) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as("SELECT id, email, display_name, created_at FROM users WHERE id = ?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_user_by_email(pool: &Pool<Sqlite>, email: &str) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as("SELECT id, email, display_name, created_at FROM users WHERE email = ?1")
+        .bind(email)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_user_by_identity(
+    pool: &Pool<Sqlite>,
+    provider: &str,
+    subject: &str,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT u.id, u.email, u.display_name, u.created_at
+        FROM user_identities i
+        JOIN users u ON u.id = i.user_id
+        WHERE i.provider = ?1 AND i.subject = ?2
+        "#,
+    )
+    .bind(provider)
+    .bind(subject)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn link_user_identity(
+    pool: &Pool<Sqlite>,
+    provider: &str,
+    subject: &str,
+    user_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO user_identities (provider, subject, user_id) VALUES (?1, ?2, ?3)")
+        .bind(provider)
+        .bind(subject)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn save_auth_token(
+    pool: &Pool<Sqlite>,
+    token_hash: &str,
+    user_id: i64,
+    now: i64,
+    expires_at: i64,
+) -> Result<(), sqlx::Error> {
+    // Expired tokens of the same user are cleared out on every login
+    sqlx::query("DELETE FROM auth_tokens WHERE user_id = ?1 AND expires_at <= ?2")
+        .bind(user_id)
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+    sqlx::query("INSERT INTO auth_tokens (token_hash, user_id, expires_at) VALUES (?1, ?2, ?3)")
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// The owner of a token that is still valid at `now`.
+pub async fn get_token_user(
+    pool: &Pool<Sqlite>,
+    token_hash: &str,
+    now: i64,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT u.id, u.email, u.display_name, u.created_at
+        FROM auth_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = ?1 AND t.expires_at > ?2
+        "#,
+    )
+    .bind(token_hash)
+    .bind(now)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn delete_auth_token(pool: &Pool<Sqlite>, token_hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM auth_tokens WHERE token_hash = ?1")
+        .bind(token_hash)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Move anonymous sessions into an account, given `(session_id,
+/// claim_token_hash)` pairs. Sessions that already belong to an account, or
+/// whose claim token doesn't match, are left alone. Returns how many were
+/// claimed.
+pub async fn claim_sessions(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    claims: &[(&str, String)],
+) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut claimed = 0;
+
+    for (session_id, claim_token_hash) in claims {
+        claimed += sqlx::query(
+            r#"
+            UPDATE sessions SET user_id = ?1, claim_token_hash = NULL
+            WHERE id = ?2 AND user_id IS NULL AND claim_token_hash = ?3
+            "#,
+        )
+        .bind(user_id)
+        .bind(session_id)
+        .bind(claim_token_hash)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+
+    tx.commit().await?;
+    Ok(claimed)
+}
+
+/// Create an anonymous session with the hash of its claim token. Returns
+/// false if the session already exists, so only its first start hands out a
+/// token.
+pub async fn create_anonymous_session(
+    pool: &Pool<Sqlite>,
+    session_id: &str,
+    claim_token_hash: &str,
+) -> Result<bool, sqlx::Error> {
+    let created = sqlx::query(
+        r#"
+        INSERT INTO sessions (id, user_metadata, claim_token_hash) VALUES (?1, '{}', ?2)
+        ON CONFLICT(id) DO NOTHING
+        "#,
+    )
+    .bind(session_id)
+    .bind(claim_token_hash)
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(created > 0)
+}
+
+/// Record that a session was started by a signed-in user.
+pub async fn attach_session_user(
+    pool: &Pool<Sqlite>,
+    session_id: &str,
+    user_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO sessions (id, user_metadata, user_id) VALUES (?1, '{}', ?2)
+        ON CONFLICT(id) DO UPDATE SET user_id = COALESCE(sessions.user_id, excluded.user_id)
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Columns selected by every journal listing, joined from a reading `r`, its
+/// session `s` and its optional journal entry `j`.
+const JOURNAL_ITEM_COLUMNS: &str = r#"
+    r.id, r.session_id, r.user_query, r.drawn_cards, r.ai_interpretation,
+    r.interpretation_cancelled, r.authorship, r.created_at,
+    j.id AS entry_id, j.notes, j.mood, j.tags, j.outcome, j.outcome_recorded_at,
+    j.starred, j.created_at AS entry_created_at, j.updated_at
+"#;
+
+#[derive(FromRow)]
+struct JournalItemRow {
+    id: i64,
+    session_id: String,
+    user_query: String,
+    drawn_cards: Option<String>,
+    ai_interpretation: Option<String>,
+    interpretation_cancelled: bool,
+    authorship: String,
+    created_at: String,
+    entry_id: Option<i64>,
+    notes: Option<String>,
+    mood: Option<String>,
+    tags: Option<String>,
+    outcome: Option<String>,
+    outcome_recorded_at: Option<String>,
+    starred: Option<bool>,
+    entry_created_at: Option<String>,
+    updated_at: Option<String>,
+}
+
+fn parse_tags(tags: Option<&str>) -> Vec<String> {
+    tags.and_then(|t| serde_json::from_str(t).ok()).unwrap_or_default()
+}
+
+impl From<JournalItemRow> for JournalItem {
+    fn from(r: JournalItemRow) -> Self {
+        let entry = r.entry_id.map(|entry_id| JournalEntry {
+            id: entry_id,
+            reading_id: r.id,
+            notes: r.notes,
+            mood: r.mood,
+            tags: parse_tags(r.tags.as_deref()),
+            outcome: r.outcome,
+            outcome_recorded_at: r.outcome_recorded_at,
+            starred: r.starred.unwrap_or(false),
+            created_at: r.entry_created_at.unwrap_or_default(),
+            updated_at: r.updated_at.unwrap_or_default(),
+        });
+
+        JournalItem {
+            reading: ReadingSummary {
+                id: r.id,
+                session_id: r.session_id,
+                user_query: r.user_query,
+                drawn_cards: r
+                    .drawn_cards
+                    .and_then(|c| serde_json::from_str(&c).ok())
+                    .unwrap_or_default(),
+                interpretation: r.ai_interpretation,
+                interpretation_cancelled: r.interpretation_cancelled,
+                authorship: Authorship::from_label(&r.authorship).unwrap_or(Authorship::Ai),
+                created_at: r.created_at,
+            },
+            entry,
+        }
+    }
+}
+
+/// A reading and its journal entry, if the reading belongs to `user_id`.
+pub async fn get_journal_item(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    reading_id: i64,
+) -> Result<Option<JournalItem>, sqlx::Error> {
+    let row: Option<JournalItemRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT {JOURNAL_ITEM_COLUMNS}
+        FROM readings r
+        JOIN sessions s ON s.id = r.session_id
+        LEFT JOIN journal_entries j ON j.reading_id = r.id
+        WHERE r.id = ?1 AND s.user_id = ?2
+        "#
+    ))
+    .bind(reading_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(JournalItem::from))
+}
+
+/// A user's readings, newest first, optionally only starred ones or those
+/// carrying `tag`.
+pub async fn list_journal(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    starred_only: bool,
+    tag: Option<&str>,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<JournalItem>, sqlx::Error> {
+    let rows: Vec<JournalItemRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT {JOURNAL_ITEM_COLUMNS}
+        FROM readings r
+        JOIN sessions s ON s.id = r.session_id
+        LEFT JOIN journal_entries j ON j.reading_id = r.id
+        WHERE s.user_id = ?1
+          AND (?2 = 0 OR j.starred = 1)
+          AND (?3 IS NULL OR EXISTS (SELECT 1 FROM json_each(j.tags) WHERE value = ?3))
+        ORDER BY r.created_at DESC, r.id DESC
+        LIMIT ?4 OFFSET ?5
+        "#
+    ))
+    .bind(user_id)
+    .bind(starred_only)
+    .bind(tag)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(JournalItem::from).collect())
+}
+
+/// Full-text search over a user's readings and journal entries. `fts_query`
+/// must already be a valid FTS5 expression.
+pub async fn search_journal(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    fts_query: &str,
+    limit: u32,
+) -> Result<Vec<JournalItem>, sqlx::Error> {
+    let rows: Vec<JournalItemRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT {JOURNAL_ITEM_COLUMNS}
+        FROM readings r
+        JOIN sessions s ON s.id = r.session_id
+        LEFT JOIN journal_entries j ON j.reading_id = r.id
+        WHERE s.user_id = ?1
+          AND (
+            r.id IN (SELECT rowid FROM readings_fts WHERE readings_fts MATCH ?2)
+            OR j.id IN (SELECT rowid FROM journal_fts WHERE journal_fts MATCH ?2)
+          )
+        ORDER BY r.created_at DESC, r.id DESC
+        LIMIT ?3
+        "#
+    ))
+    .bind(user_id)
+    .bind(fts_query)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(JournalItem::from).collect())
+}
+
+#[derive(FromRow)]
+struct JournalEntryRow {
+    id: i64,
+    reading_id: i64,
+    notes: Option<String>,
+    mood: Option<String>,
+    tags: String,
+    outcome: Option<String>,
+    outcome_recorded_at: Option<String>,
+    starred: bool,
+    created_at: String,
+    updated_at: String,
+}
+
+impl From<JournalEntryRow> for JournalEntry {
+    fn from(r: JournalEntryRow) -> Self {
+        JournalEntry {
+            id: r.id,
+            reading_id: r.reading_id,
+            notes: r.notes,
+            mood: r.mood,
+            tags: parse_tags(Some(&r.tags)),
+            outcome: r.outcome,
+            outcome_recorded_at: r.outcome_recorded_at,
+            starred: r.starred,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+/// Create or replace the journal entry of a reading. The previous version,
+/// if any, is kept in `journal_revisions` by a trigger.
+pub async fn save_journal_entry(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    reading_id: i64,
+    fields: &JournalFields,
+) -> Result<JournalEntry, sqlx::Error> {
+    let tags = serde_json::to_string(&fields.tags).unwrap_or_else(|_| "[]".to_string());
+
+    let r: JournalEntryRow = sqlx::query_as(
+        r#"
+        INSERT INTO journal_entries (reading_id, user_id, notes, mood, tags, outcome, outcome_recorded_at, starred)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, CASE WHEN ?6 IS NULL THEN NULL ELSE CURRENT_TIMESTAMP END, ?7)
+        ON CONFLICT(reading_id) DO UPDATE SET
+            notes = excluded.notes,
+            mood = excluded.mood,
+            tags = excluded.tags,
+            outcome = excluded.outcome,
+            outcome_recorded_at = CASE
+                WHEN excluded.outcome IS journal_entries.outcome THEN journal_entries.outcome_recorded_at
+                ELSE excluded.outcome_recorded_at
+            END,
+            starred = excluded.starred,
+            updated_at = CURRENT_TIMESTAMP
+        RETURNING id, reading_id, notes, mood, tags, outcome, outcome_recorded_at, starred, created_at, updated_at
+        "#,
+    )
+    .bind(reading_id)
+    .bind(user_id)
+    .bind(&fields.notes)
+    .bind(&fields.mood)
+    .bind(tags)
+    .bind(&fields.outcome)
+    .bind(fields.starred)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(r.into())
+}
+
+pub async fn get_journal_entry(
+    pool: &Pool<Sqlite>,
+    reading_id: i64,
+) -> Result<Option<JournalEntry>, sqlx::Error> {
+    let row: Option<JournalEntryRow> = sqlx::query_as(
+        r#"
+        SELECT id, reading_id, notes, mood, tags, outcome, outcome_recorded_at, starred, created_at, updated_at
+        FROM journal_entries
+        WHERE reading_id = ?1
+        "#,
+    )
+    .bind(reading_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(JournalEntry::from))
+}
+
+/// Remove a reading's journal entry along with its history.
+pub async fn delete_journal_entry(pool: &Pool<Sqlite>, reading_id: i64) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query("DELETE FROM journal_entries WHERE reading_id = ?1")
+        .bind(reading_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(deleted > 0)
+}
+
+#[derive(FromRow)]
+struct JournalRevisionRow {
+    id: i64,
+    notes: Option<String>,
+    mood: Option<String>,
+    tags: String,
+    outcome: Option<String>,
+    starred: bool,
+    edited_at: Option<String>,
+    replaced_at: String,
+}
+
+/// Earlier versions of a journal entry, newest first.
+pub async fn get_journal_revisions(
+    pool: &Pool<Sqlite>,
+    entry_id: i64,
+) -> Result<Vec<JournalRevision>, sqlx::Error> {
+    let rows: Vec<JournalRevisionRow> = sqlx::query_as(
+        r#"
+        SELECT id, notes, mood, tags, outcome, starred, edited_at, replaced_at
+        FROM journal_revisions
+        WHERE entry_id = ?1
+        ORDER BY id DESC
+        "#,
+    )
+    .bind(entry_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| JournalRevision {
+            id: r.id,
+            notes: r.notes,
+            mood: r.mood,
+            tags: parse_tags(Some(&r.tags)),
+            outcome: r.outcome,
+            starred: r.starred,
+            edited_at: r.edited_at,
+            replaced_at: r.replaced_at,
+        })
+        .collect())
+}
+
+/// Every card a user has drawn, oldest reading first, optionally limited to
+/// readings made between two dates (inclusive, `YYYY-MM-DD`).
+pub async fn get_user_card_draws(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    since: Option<&str>,
+    until: Option<&str>,
+) -> Result<Vec<CardDraw>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT r.id AS reading_id, r.created_at, rc.card_id, rc.reversed
+        FROM readings r
+        JOIN sessions s ON s.id = r.session_id
+        JOIN reading_cards rc ON rc.reading_id = r.id
+        WHERE s.user_id = ?1
+          AND (?2 IS NULL OR date(r.created_at) >= date(?2))
+          AND (?3 IS NULL OR date(r.created_at) <= date(?3))
+        ORDER BY r.created_at, r.id, rc.position
+        "#,
+    )
+    .bind(user_id)
+    .bind(since)
+    .bind(until)
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(FromRow)]
+struct ReadingRow {
+    id: i64,
+    session_id: String,
+    user_query: String,
+    drawn_cards: Option<String>,
+    ai_interpretation: Option<String>,
+    interpretation_cancelled: bool,
+    authorship: String,
+    created_at: String,
+}
+
+impl From<ReadingRow> for ReadingSummary {
+    fn from(r: ReadingRow) -> Self {
+        ReadingSummary {
+            id: r.id,
+            session_id: r.session_id,
+            user_query: r.user_query,
+            drawn_cards: r
+                .drawn_cards
+                .and_then(|c| serde_json::from_str(&c).ok())
+                .unwrap_or_default(),
+            interpretation: r.ai_interpretation,
+            interpretation_cancelled: r.interpretation_cancelled,
+            authorship: Authorship::from_label(&r.authorship).unwrap_or(Authorship::Ai),
+            created_at: r.created_at,
+        }
+    }
+}
+
+/// Readings to export, oldest first: one reading of a user, one session, or
+/// everything a user owns. Unset filters match anything.
+pub async fn get_export_readings(
+    pool: &Pool<Sqlite>,
+    reading_id: Option<i64>,
+    session_id: Option<&str>,
+    user_id: Option<i64>,
+) -> Result<Vec<ReadingSummary>, sqlx::Error> {
+    let rows: Vec<ReadingRow> = sqlx::query_as(
+        r#"
+        SELECT r.id, r.session_id, r.user_query, r.drawn_cards, r.ai_interpretation,
+               r.interpretation_cancelled, r.authorship, r.created_at
+        FROM readings r
+        JOIN sessions s ON s.id = r.session_id
+        WHERE (?1 IS NULL OR r.id = ?1)
+          AND (?2 IS NULL OR r.session_id = ?2)
+          AND (?3 IS NULL OR s.user_id = ?3)
+        ORDER BY r.created_at, r.id
+        "#,
+    )
+    .bind(reading_id)
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(ReadingSummary::from).collect())
+}
+
+pub async fn get_reading_cards(
+    pool: &Pool<Sqlite>,
+    reading_id: i64,
+) -> Result<Vec<ReadingCard>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT position, card_id, reversed FROM reading_cards WHERE reading_id = ?1 ORDER BY position",
+    )
+    .bind(reading_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// `None` if the session doesn't exist, otherwise its owner (`None` while
+/// anonymous) and the hash of its claim token.
+pub async fn get_session_owner(
+    pool: &Pool<Sqlite>,
+    session_id: &str,
+) -> Result<Option<(Option<i64>, Option<String>)>, sqlx::Error> {
+    sqlx::query_as("SELECT user_id, claim_token_hash FROM sessions WHERE id = ?1")
+        .bind(session_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Store imported readings in new sessions owned by `user_id`, all or nothing.
+pub async fn import_readings(
+    pool: &Pool<Sqlite>,
+    user_id: i64,
+    readings: &[ImportedReading],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    for reading in readings {
+        sqlx::query(
+            r#"
+            INSERT INTO sessions (id, user_metadata, user_id) VALUES (?1, '{"imported": true}', ?2)
+            ON CONFLICT(id) DO NOTHING
+            "#,
+        )
+        .bind(&reading.session_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let reading_id = sqlx::query(
+            r#"
+            INSERT INTO readings (session_id, user_query, drawn_cards, ai_interpretation, injection_signals, created_at)
+            VALUES (?1, ?2, ?3, ?4, '[]', ?5)
+            "#,
+        )
+        .bind(&reading.session_id)
+        .bind(&reading.query)
+        .bind(serde_json::to_value(&reading.cards).unwrap_or_default())
+        .bind(&reading.interpretation)
+        .bind(&reading.created_at)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        for drawn in &reading.cards {
+            sqlx::query(
+                "INSERT INTO reading_cards (reading_id, position, card_id, reversed) VALUES (?1, ?2, ?3, ?4)",
+            )
+            .bind(reading_id)
+            .bind(drawn.position_index as i64)
+            .bind(&drawn.card.id)
+            .bind(drawn.is_reversed)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for message in &reading.messages {
+            sqlx::query(
+                "INSERT INTO messages (reading_id, role, content, created_at) VALUES (?1, ?2, ?3, ?4)",
+            )
+            .bind(reading_id)
+            .bind(&message.role)
+            .bind(&message.content)
+            .bind(&message.created_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if let Some(journal) = &reading.journal {
+            sqlx::query(
+                r#"
+                INSERT INTO journal_entries (reading_id, user_id, notes, mood, tags, outcome, outcome_recorded_at, starred)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, CASE WHEN ?6 IS NULL THEN NULL ELSE CURRENT_TIMESTAMP END, ?7)
+                "#,
+            )
+            .bind(reading_id)
+            .bind(user_id)
+            .bind(&journal.notes)
+            .bind(&journal.mood)
+            .bind(serde_json::to_string(&journal.tags).unwrap_or_else(|_| "[]".to_string()))
+            .bind(&journal.outcome)
+            .bind(journal.starred)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn reading_belongs_to(
+    pool: &Pool<Sqlite>,
+    reading_id: i64,
+    user_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let found: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT r.id
+        FROM readings r
+        JOIN sessions s ON s.id = r.session_id
+        WHERE r.id = ?1 AND s.user_id = ?2
+        "#,
+    )
+    .bind(reading_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(found.is_some())
+}
+
+pub async fn create_share(
+    pool: &Pool<Sqlite>,
+    token: &str,
+    reading_id: i64,
+    user_id: i64,
+    include_query: bool,
+) -> Result<Share, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        INSERT INTO reading_shares (token, reading_id, user_id, include_query)
+        VALUES (?1, ?2, ?3, ?4)
+        RETURNING token, reading_id, include_query, view_count, created_at, revoked_at
+        "#,
+    )
+    .bind(token)
+    .bind(reading_id)
+    .bind(user_id)
+    .bind(include_query)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn list_shares(pool: &Pool<Sqlite>, reading_id: i64) -> Result<Vec<Share>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT token, reading_id, include_query, view_count, created_at, revoked_at
+        FROM reading_shares
+        WHERE reading_id = ?1
+        ORDER BY created_at DESC, rowid DESC
+        "#,
+    )
+    .bind(reading_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Revoke one of the user's active links. Returns false if there was none.
+pub async fn revoke_share(pool: &Pool<Sqlite>, token: &str, user_id: i64) -> Result<bool, sqlx::Error> {
+    let revoked = sqlx::query(
+        r#"
+        UPDATE reading_shares SET revoked_at = CURRENT_TIMESTAMP
+        WHERE token = ?1 AND user_id = ?2 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(token)
+    .bind(user_id)
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(revoked > 0)
+}
+
+/// The reading behind an active link and whether its question may be
+/// shown. Counts the view.
+pub async fn open_share(
+    pool: &Pool<Sqlite>,
+    token: &str,
+) -> Result<Option<(ReadingSummary, bool)>, sqlx::Error> {
+    let include_query: Option<bool> = sqlx::query_scalar(
+        r#"
+        UPDATE reading_shares SET view_count = view_count + 1
+        WHERE token = ?1 AND revoked_at IS NULL
+        RETURNING include_query
+        "#,
+    )
+    .bind(token)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(include_query) = include_query else {
+        return Ok(None);
+    };
+
+    let row: Option<ReadingRow> = sqlx::query_as(
+        r#"
+        SELECT r.id, r.session_id, r.user_query, r.drawn_cards, r.ai_interpretation,
+               r.interpretation_cancelled, r.authorship, r.created_at
+        FROM reading_shares sh
+        JOIN readings r ON r.id = sh.reading_id
+        WHERE sh.token = ?1
+        "#,
+    )
+    .bind(token)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| (r.into(), include_query)))
+}
+
+/// The owner's card for `day`. Exactly one of `user_id` and `session_id` is set.
+pub async fn get_daily_card(
+    pool: &Pool<Sqlite>,
+    user_id: Option<i64>,
+    session_id: Option<&str>,
+    day: &str,
+) -> Result<Option<DailyCardRecord>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT day, timezone, card_id, reversed, interpretation, created_at
+        FROM daily_cards
+        WHERE user_id IS ?1 AND session_id IS ?2 AND day = ?3
+        "#,
+    )
+    .bind(user_id)
+    .bind(session_id)
+    .bind(day)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Store the owner's card for the day unless one is already stored, and
+/// return whichever card is kept.
+pub async fn save_daily_card(
+    pool: &Pool<Sqlite>,
+    user_id: Option<i64>,
+    session_id: Option<&str>,
+    card: &DailyCardRecord,
+) -> Result<DailyCardRecord, sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO daily_cards (user_id, session_id, day, timezone, card_id, reversed, interpretation)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#,
+    )
+    .bind(user_id)
+    .bind(session_id)
+    .bind(&card.day)
+    .bind(&card.timezone)
+    .bind(&card.card_id)
+    .bind(card.reversed)
+    .bind(&card.interpretation)
+    .execute(pool)
+    .await?;
+
+    get_daily_card(pool, user_id, session_id, &card.day)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)
+}
+
+/// Past cards of the day, newest first.
+pub async fn get_daily_card_history(
+    pool: &Pool<Sqlite>,
+    user_id: Option<i64>,
+    session_id: Option<&str>,
+    limit: u32,
+) -> Result<Vec<DailyCardRecord>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT day, timezone, card_id, reversed, interpretation, created_at
+        FROM daily_cards
+        WHERE user_id IS ?1 AND session_id IS ?2
+        ORDER BY day DESC
+        LIMIT ?3
+        "#,
+    )
+    .bind(user_id)
+    .bind(session_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,146 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::fmt;
+
+/// Stable, machine-readable reason for a failure. The same codes appear in
+/// REST error bodies and WebSocket `error` messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The request isn't allowed in the session's current state.
+    InvalidState,
+    CardAlreadySelected,
+    InvalidCard,
+    /// A field has an unacceptable value.
+    InvalidRequest,
+    /// The message couldn't be parsed.
+    BadMessage,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    /// Too many requests in a short time.
+    RateLimited,
+    /// A reading or token quota is used up.
+    QuotaExceeded,
+    /// The interpretation model couldn't be reached.
+    AiUnavailable,
+    /// An external identity provider failed.
+    UpstreamError,
+    /// The session is gone or the resume token doesn't match.
+    ResumeFailed,
+    /// `hello` asked for a protocol version the server can't speak.
+    UnsupportedVersion,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidCard
+            | ErrorCode::InvalidRequest
+            | ErrorCode::BadMessage
+            | ErrorCode::UnsupportedVersion => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InvalidState
+            | ErrorCode::CardAlreadySelected
+            | ErrorCode::Conflict
+            | ErrorCode::ResumeFailed => StatusCode::CONFLICT,
+            ErrorCode::RateLimited | ErrorCode::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::AiUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::UpstreamError => StatusCode::BAD_GATEWAY,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::RateLimited
+                | ErrorCode::QuotaExceeded
+                | ErrorCode::AiUnavailable
+                | ErrorCode::UpstreamError
+                | ErrorCode::InternalError
+        )
+    }
+}
+
+/// An error as clients see it. Module errors convert into this; anything
+/// internal is logged and replaced with a generic message.
+#[derive(Debug, Clone)]
+pub struct AppError {
+    pub code: ErrorCode,
+    /// Safe to show to the user.
+    pub message: String,
+    pub retryable: bool,
+    pub retry_after_secs: Option<u64>,
+    /// Unix seconds at which an exceeded quota resets.
+    pub reset_at: Option<u64>,
+    /// Extra fields merged into the REST body, e.g. which quota ran out.
+    pub details: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retryable: code.retryable(),
+            retry_after_secs: None,
+            reset_at: None,
+            details: None,
+        }
+    }
+
+    /// Log `detail` and give the client nothing but "internal error".
+    pub fn internal(context: &str, detail: impl fmt::Display) -> Self {
+        tracing::error!("{}: {}", context, detail);
+        Self::new(ErrorCode::InternalError, "internal error")
+    }
+
+    pub fn with_detail(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.details
+            .get_or_insert_with(serde_json::Map::new)
+            .insert(key.to_string(), value.into());
+        self
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.code.status();
+
+        let mut body = self.details.unwrap_or_default();
+        body.insert("error".to_string(), self.message.into());
+        body.insert("code".to_string(), serde_json::to_value(self.code).unwrap_or_default());
+        body.insert("retryable".to_string(), self.retryable.into());
+        if let Some(reset_at) = self.reset_at {
+            body.insert("reset_at".to_string(), reset_at.into());
+        }
+
+        let mut response = (status, Json(body)).into_response();
+        let headers = response.headers_mut();
+        if let Some(secs) = self.retry_after_secs {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        if status == StatusCode::UNAUTHORIZED {
+            headers.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
@@ -0,0 +1,768 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{NaiveDateTime, Utc};
+use printpdf::{
+    path::PaintMode, BuiltinFont, Color, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference,
+    PdfLayerReference, Rect, Rgb,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::auth;
+use crate::db;
+use crate::error::{AppError, ErrorCode};
+use crate::journal::JournalFields;
+use crate::models::{DrawnCard, JournalEntry, JournalUpdate, ReadingSummary};
+use crate::state::AppState;
+use uuid::Uuid;
+
+/// Identifies our canonical JSON export.
+pub const EXPORT_FORMAT: &str = "immersive-tarot";
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Most readings accepted in one import.
+const MAX_IMPORT_READINGS: usize = 1000;
+const MAX_IMPORT_TEXT_CHARS: usize = 20_000;
+const MAX_IMPORT_MESSAGES: usize = 200;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    /// TrueType font embedded in PDF exports. Without one, PDFs use
+    /// Helvetica and can only show Latin text.
+    pub pdf_font: Option<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("nothing to export")]
+    NotFound,
+    #[error("invalid import: {0}")]
+    Invalid(String),
+    #[error("rendering the export failed: {0}")]
+    Render(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<ExportError> for AppError {
+    fn from(error: ExportError) -> Self {
+        let code = match error {
+            ExportError::NotFound => ErrorCode::NotFound,
+            ExportError::Invalid(_) => ErrorCode::InvalidRequest,
+            ExportError::Render(_) | ExportError::Database(_) => {
+                return AppError::internal("Export failed", error)
+            }
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+impl IntoResponse for ExportError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    #[serde(alias = "markdown")]
+    Md,
+    Json,
+    Pdf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Which readings to export.
+#[derive(Debug, Clone)]
+pub enum ExportScope {
+    Reading { user_id: i64, reading_id: i64 },
+    Session(String),
+    Account(i64),
+}
+
+impl ExportScope {
+    fn file_stem(&self) -> String {
+        match self {
+            ExportScope::Reading { reading_id, .. } => format!("tarot-reading-{}", reading_id),
+            ExportScope::Session(id) => format!("tarot-session-{}", id),
+            ExportScope::Account(_) => "tarot-journal".to_string(),
+        }
+    }
+}
+
+/// The canonical export: everything needed to re-import the readings.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportDocument {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub deck_version: String,
+    pub readings: Vec<ExportedReading>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedReading {
+    pub session_id: String,
+    pub created_at: String,
+    pub query: String,
+    pub cards: Vec<ExportedCard>,
+    pub interpretation: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<ExportedMessage>,
+    #[serde(default)]
+    pub journal: Option<ExportedJournal>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedCard {
+    pub position: usize,
+    pub card_id: String,
+    pub name: String,
+    pub reversed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedMessage {
+    pub role: String,
+    pub content: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedJournal {
+    pub notes: Option<String>,
+    pub mood: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub outcome: Option<String>,
+    #[serde(default)]
+    pub starred: bool,
+}
+
+impl From<JournalEntry> for ExportedJournal {
+    fn from(entry: JournalEntry) -> Self {
+        Self {
+            notes: entry.notes,
+            mood: entry.mood,
+            tags: entry.tags,
+            outcome: entry.outcome,
+            starred: entry.starred,
+        }
+    }
+}
+
+/// A validated reading ready to be stored by `db::import_readings`.
+#[derive(Debug)]
+pub struct ImportedReading {
+    pub session_id: String,
+    pub created_at: String,
+    pub query: String,
+    pub cards: Vec<DrawnCard>,
+    pub interpretation: Option<String>,
+    pub messages: Vec<ExportedMessage>,
+    pub journal: Option<JournalFields>,
+}
+
+/// Renders exports; holds the PDF font so it is read once at startup.
+pub struct Exporter {
+    pdf_font: Option<Arc<Vec<u8>>>,
+}
+
+impl Exporter {
+    pub fn new(config: &ExportConfig) -> std::io::Result<Self> {
+        let pdf_font = match &config.pdf_font {
+            Some(path) => Some(Arc::new(std::fs::read(path)?)),
+            None => None,
+        };
+        Ok(Self { pdf_font })
+    }
+}
+
+/// A session can be exported by its owner or, while it is still anonymous,
+/// by whoever holds the claim token issued when it started.
+pub async fn session_scope(
+    state: &AppState,
+    session_id: &str,
+    user_id: Option<i64>,
+    claim_token: Option<&str>,
+) -> Result<ExportScope, ExportError> {
+    let allowed = match db::get_session_owner(&state.db, session_id).await? {
+        Some((Some(owner), _)) => Some(owner) == user_id,
+        Some((None, Some(claim_token_hash))) => {
+            claim_token.is_some_and(|token| auth::hash_token(token) == claim_token_hash)
+        }
+        _ => false,
+    };
+
+    if allowed {
+        Ok(ExportScope::Session(session_id.to_string()))
+    } else {
+        Err(ExportError::NotFound)
+    }
+}
+
+/// Collect the readings in `scope` into the canonical document.
+pub async fn document(state: &AppState, scope: &ExportScope) -> Result<ExportDocument, ExportError> {
+    let readings = match scope {
+        ExportScope::Reading { user_id, reading_id } => {
+            db::get_export_readings(&state.db, Some(*reading_id), None, Some(*user_id)).await?
+        }
+        ExportScope::Session(session_id) => {
+            db::get_export_readings(&state.db, None, Some(session_id), None).await?
+        }
+        ExportScope::Account(user_id) => {
+            db::get_export_readings(&state.db, None, None, Some(*user_id)).await?
+        }
+    };
+
+    if readings.is_empty() && !matches!(scope, ExportScope::Account(_)) {
+        return Err(ExportError::NotFound);
+    }
+
+    let mut exported = Vec::with_capacity(readings.len());
+    for reading in readings {
+        exported.push(export_reading(state, reading).await?);
+    }
+
+    Ok(ExportDocument {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: Utc::now().format(TIMESTAMP_FORMAT).to_string(),
+        deck_version: state.deck.version().to_string(),
+        readings: exported,
+    })
+}
+
+/// A reading's cards in spread order, named from the current deck.
+pub async fn reading_cards(state: &AppState, reading_id: i64) -> Result<Vec<ExportedCard>, sqlx::Error> {
+    Ok(db::get_reading_cards(&state.db, reading_id)
+        .await?
+        .into_iter()
+        .map(|c| ExportedCard {
+            position: c.position.max(0) as usize,
+            name: state
+                .deck
+                .card(&c.card_id)
+                .map_or_else(|| c.card_id.clone(), |card| card.name.clone()),
+            card_id: c.card_id,
+            reversed: c.reversed,
+        })
+        .collect())
+}
+
+async fn export_reading(state: &AppState, reading: ReadingSummary) -> Result<ExportedReading, ExportError> {
+    let cards = reading_cards(state, reading.id).await?;
+
+    let messages = db::get_messages_for_reading(&state.db, reading.id)
+        .await?
+        .into_iter()
+        .map(|m| ExportedMessage {
+            role: m.role,
+            content: m.content,
+            created_at: m.created_at,
+        })
+        .collect();
+
+    let journal = db::get_journal_entry(&state.db, reading.id)
+        .await?
+        .map(ExportedJournal::from);
+
+    Ok(ExportedReading {
+        session_id: reading.session_id,
+        created_at: reading.created_at,
+        query: reading.user_query,
+        cards,
+        interpretation: reading.interpretation,
+        messages,
+        journal,
+    })
+}
+
+/// Export `scope` as a downloadable file in `format`.
+pub async fn download(
+    state: &AppState,
+    scope: &ExportScope,
+    format: ExportFormat,
+) -> Result<Response, ExportError> {
+    let document = document(state, scope).await?;
+    let stem = scope.file_stem();
+
+    let (content_type, extension, body) = match format {
+        ExportFormat::Md => ("text/markdown; charset=utf-8", "md", render_markdown(&document).into_bytes()),
+        ExportFormat::Json => (
+            "application/json",
+            "json",
+            serde_json::to_vec_pretty(&document).map_err(|e| ExportError::Render(e.to_string()))?,
+        ),
+        ExportFormat::Pdf => {
+            // printpdf documents are not Send and rendering is CPU-bound
+            let font = state.export.pdf_font.clone();
+            let body = tokio::task::spawn_blocking(move || render_pdf(&document, font.as_deref().map(Vec::as_slice)))
+                .await
+                .map_err(|e| ExportError::Render(e.to_string()))??;
+            ("application/pdf", "pdf", body)
+        }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", stem, extension),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Validate a canonical export and store its readings in new sessions owned
+/// by `user_id`. Returns how many readings were imported.
+pub async fn import(
+    state: &AppState,
+    user_id: i64,
+    document: ExportDocument,
+) -> Result<usize, ExportError> {
+    if document.format != EXPORT_FORMAT || document.version != EXPORT_VERSION {
+        return Err(ExportError::Invalid(format!(
+            "expected format {:?} version {}",
+            EXPORT_FORMAT, EXPORT_VERSION
+        )));
+    }
+    if document.readings.len() > MAX_IMPORT_READINGS {
+        return Err(ExportError::Invalid(format!(
+            "at most {} readings can be imported at once",
+            MAX_IMPORT_READINGS
+        )));
+    }
+
+    let cards_by_id: HashMap<&str, _> = state.deck.cards().iter().map(|c| (c.id.as_str(), c)).collect();
+    // Imported sessions get fresh IDs, keeping readings that shared a session together
+    let mut session_ids: HashMap<String, String> = HashMap::new();
+    let mut readings = Vec::with_capacity(document.readings.len());
+
+    for (index, reading) in document.readings.into_iter().enumerate() {
+        let invalid = |message: String| ExportError::Invalid(format!("readings[{}]: {}", index, message));
+
+        check_timestamp(&reading.created_at).map_err(invalid)?;
+        check_text("query", &reading.query).map_err(invalid)?;
+        if let Some(interpretation) = &reading.interpretation {
+            check_text("interpretation", interpretation).map_err(invalid)?;
+        }
+        if reading.messages.len() > MAX_IMPORT_MESSAGES {
+            return Err(invalid(format!("at most {} messages are allowed", MAX_IMPORT_MESSAGES)));
+        }
+        for message in &reading.messages {
+            if !matches!(message.role.as_str(), "user" | "assistant") {
+                return Err(invalid(format!("unknown message role {:?}", message.role)));
+            }
+            check_text("message", &message.content).map_err(invalid)?;
+            check_timestamp(&message.created_at).map_err(invalid)?;
+        }
+
+        let mut cards = Vec::with_capacity(reading.cards.len());
+        for card in &reading.cards {
+            let deck_card = cards_by_id
+                .get(card.card_id.as_str())
+                .ok_or_else(|| invalid(format!("unknown card {:?}", card.card_id)))?;
+            if cards.iter().any(|c: &DrawnCard| c.position_index == card.position) {
+                return Err(invalid(format!("position {} is used twice", card.position)));
+            }
+            if cards.iter().any(|c: &DrawnCard| c.card.id == card.card_id) {
+                return Err(invalid(format!("card {:?} is drawn twice", card.card_id)));
+            }
+            cards.push(DrawnCard {
+                card: (*deck_card).clone(),
+                is_reversed: card.reversed,
+                position_index: card.position,
+            });
+        }
+        if cards.is_empty() {
+            return Err(invalid("a reading needs at least one card".to_string()));
+        }
+
+        let journal = reading
+            .journal
+            .map(|j| {
+                JournalFields::from_update(JournalUpdate {
+                    notes: j.notes,
+                    mood: j.mood,
+                    tags: Some(j.tags),
+                    outcome: j.outcome,
+                    starred: Some(j.starred),
+                })
+            })
+            .transpose()
+            .map_err(|e| invalid(e.to_string()))?;
+
+        let session_id = session_ids
+            .entry(reading.session_id)
+            .or_insert_with(|| Uuid::new_v4().to_string())
+            .clone();
+
+        readings.push(ImportedReading {
+            session_id,
+            created_at: reading.created_at,
+            query: reading.query,
+            cards,
+            interpretation: reading.interpretation,
+            messages: reading.messages,
+            journal,
+        });
+    }
+
+    db::import_readings(&state.db, user_id, &readings).await?;
+    Ok(readings.len())
+}
+
+fn check_timestamp(value: &str) -> Result<(), String> {
+    NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .map(|_| ())
+        .map_err(|_| format!("timestamp {:?} must look like 2026-10-18 20:27:22", value))
+}
+
+fn check_text(name: &str, value: &str) -> Result<(), String> {
+    if value.chars().count() > MAX_IMPORT_TEXT_CHARS {
+        Err(format!("{} must be at most {} characters", name, MAX_IMPORT_TEXT_CHARS))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn orientation(reversed: bool) -> &'static str {
+    if reversed {
+        "Reversed"
+    } else {
+        "Upright"
+    }
+}
+
+/// The conversation after the opening question and interpretation.
+fn follow_ups(reading: &ExportedReading) -> &[ExportedMessage] {
+    reading.messages.get(2..).unwrap_or_default()
+}
+
+fn speaker(role: &str) -> &'static str {
+    if role == "user" {
+        "You"
+    } else {
+        "Reader"
+    }
+}
+
+pub fn render_markdown(document: &ExportDocument) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Tarot Readings\n");
+    let _ = writeln!(out, "_Exported {} UTC · deck {}_\n", document.exported_at, document.deck_version);
+
+    for reading in &document.readings {
+        let _ = writeln!(out, "## Reading of {}\n", reading.created_at);
+        for line in reading.query.lines() {
+            let _ = writeln!(out, "> {}", line);
+        }
+        out.push('\n');
+
+        let _ = writeln!(out, "### Cards\n");
+        let _ = writeln!(out, "| Position | Card | Orientation |");
+        let _ = writeln!(out, "|---|---|---|");
+        for card in &reading.cards {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} |",
+                card.position + 1,
+                card.name.replace('|', "\\|"),
+                orientation(card.reversed)
+            );
+        }
+        out.push('\n');
+
+        if let Some(interpretation) = &reading.interpretation {
+            let _ = writeln!(out, "### Interpretation\n\n{}\n", interpretation.trim());
+        }
+
+        let thread = follow_ups(reading);
+        if !thread.is_empty() {
+            let _ = writeln!(out, "### Follow-up\n");
+            for message in thread {
+                let _ = writeln!(out, "**{}:** {}\n", speaker(&message.role), message.content.trim());
+            }
+        }
+
+        if let Some(journal) = &reading.journal {
+            let _ = writeln!(out, "### Journal\n");
+            if journal.starred {
+                let _ = writeln!(out, "★ Favourite\n");
+            }
+            if let Some(mood) = &journal.mood {
+                let _ = writeln!(out, "- **Mood:** {}", mood);
+            }
+            if !journal.tags.is_empty() {
+                let _ = writeln!(out, "- **Tags:** {}", journal.tags.join(", "));
+            }
+            if journal.mood.is_some() || !journal.tags.is_empty() {
+                out.push('\n');
+            }
+            if let Some(notes) = &journal.notes {
+                let _ = writeln!(out, "{}\n", notes.trim());
+            }
+            if let Some(outcome) = &journal.outcome {
+                let _ = writeln!(out, "**What happened:** {}\n", outcome.trim());
+            }
+        }
+
+        let _ = writeln!(out, "---\n");
+    }
+
+    out
+}
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const PT_TO_MM: f32 = 0.3528;
+
+const CARD_WIDTH: f32 = 28.0;
+const CARD_HEIGHT: f32 = 46.0;
+const CARD_GAP: f32 = 6.0;
+const CARDS_PER_ROW: usize = 5;
+
+/// East Asian characters take about a full em; everything else about half.
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF | 0x2E80..=0x9FFF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFF00..=0xFF60)
+}
+
+fn text_width_mm(text: &str, size: f32) -> f32 {
+    text.chars()
+        .map(|c| if is_wide(c) { 1.0 } else { 0.5 })
+        .sum::<f32>()
+        * size
+        * PT_TO_MM
+}
+
+/// Greedy word wrap to `width_mm`, breaking inside words that don't fit.
+fn wrap(text: &str, size: f32, width_mm: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if text_width_mm(&candidate, size) <= width_mm {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                if !line.is_empty() && text_width_mm(&format!("{}{}", line, c), size) > width_mm {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(c);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Helvetica only covers Windows-1252; mark anything else visibly.
+fn latin_only(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if (c as u32) < 0x100 || "‘’“”–—…•€™".contains(c) {
+                c
+            } else {
+                '?'
+            }
+        })
+        .collect()
+}
+
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    embedded: bool,
+    /// Distance from the bottom of the page to the next baseline
+    y: f32,
+}
+
+impl PdfWriter {
+    fn new(title: &str, font: Option<&[u8]>) -> Result<Self, ExportError> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let pdf_error = |e: printpdf::Error| ExportError::Render(e.to_string());
+
+        let (regular, bold, embedded) = match font {
+            Some(bytes) => {
+                let font = doc.add_external_font(bytes).map_err(pdf_error)?;
+                (font.clone(), font, true)
+            }
+            None => (
+                doc.add_builtin_font(BuiltinFont::Helvetica).map_err(pdf_error)?,
+                doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(pdf_error)?,
+                false,
+            ),
+        };
+
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Self {
+            doc,
+            layer,
+            regular,
+            bold,
+            embedded,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    fn text_at(&self, text: &str, size: f32, x: f32, y: f32, bold: bool) {
+        let text = if self.embedded { text.to_string() } else { latin_only(text) };
+        let font = if bold { &self.bold } else { &self.regular };
+        self.layer.use_text(text, size, Mm(x), Mm(y), font);
+    }
+
+    fn paragraph(&mut self, text: &str, size: f32, bold: bool) {
+        let line_height = size * PT_TO_MM * 1.4;
+        for line in wrap(text, size, PAGE_WIDTH - 2.0 * MARGIN) {
+            self.ensure_space(line_height);
+            self.y -= line_height;
+            self.text_at(&line, size, MARGIN, self.y, bold);
+        }
+        self.y -= line_height * 0.5;
+    }
+
+    /// The spread as rows of card outlines with position, name and orientation.
+    fn spread(&mut self, cards: &[ExportedCard]) {
+        for row in cards.chunks(CARDS_PER_ROW) {
+            self.ensure_space(CARD_HEIGHT + CARD_GAP);
+            let top = self.y - CARD_GAP;
+            let bottom = top - CARD_HEIGHT;
+            let row_width = row.len() as f32 * (CARD_WIDTH + CARD_GAP) - CARD_GAP;
+            let mut left = (PAGE_WIDTH - row_width) / 2.0;
+
+            for card in row {
+                let fill = if card.reversed { 0.85 } else { 0.95 };
+                self.layer
+                    .set_fill_color(Color::Rgb(Rgb::new(fill, fill, 0.98, None)));
+                self.layer.add_rect(
+                    Rect::new(Mm(left), Mm(bottom), Mm(left + CARD_WIDTH), Mm(top))
+                        .with_mode(PaintMode::FillStroke),
+                );
+                self.layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+
+                self.text_at(&(card.position + 1).to_string(), 12.0, left + 2.5, top - 6.0, true);
+                let mut line_y = top - 16.0;
+                for line in wrap(&card.name, 8.0, CARD_WIDTH - 4.0).iter().take(4) {
+                    self.text_at(line, 8.0, left + 2.0, line_y, false);
+                    line_y -= 4.0;
+                }
+                self.text_at(orientation(card.reversed), 7.0, left + 2.0, bottom + 3.0, card.reversed);
+
+                left += CARD_WIDTH + CARD_GAP;
+            }
+            self.y = bottom - CARD_GAP;
+        }
+    }
+}
+
+pub fn render_pdf(document: &ExportDocument, font: Option<&[u8]>) -> Result<Vec<u8>, ExportError> {
+    let mut pdf = PdfWriter::new("Tarot Readings", font)?;
+
+    pdf.paragraph("Tarot Readings", 20.0, true);
+    pdf.paragraph(
+        &format!("Exported {} UTC · deck {}", document.exported_at, document.deck_version),
+        9.0,
+        false,
+    );
+
+    for (index, reading) in document.readings.iter().enumerate() {
+        if index > 0 {
+            pdf.new_page();
+        }
+        pdf.paragraph(&format!("Reading of {}", reading.created_at), 15.0, true);
+        pdf.paragraph(&reading.query, 11.0, false);
+        pdf.spread(&reading.cards);
+
+        if let Some(interpretation) = &reading.interpretation {
+            pdf.paragraph("Interpretation", 13.0, true);
+            pdf.paragraph(interpretation.trim(), 10.0, false);
+        }
+
+        let thread = follow_ups(reading);
+        if !thread.is_empty() {
+            pdf.paragraph("Follow-up", 13.0, true);
+            for message in thread {
+                pdf.paragraph(speaker(&message.role), 10.0, true);
+                pdf.paragraph(message.content.trim(), 10.0, false);
+            }
+        }
+
+        if let Some(journal) = &reading.journal {
+            pdf.paragraph("Journal", 13.0, true);
+            let mut details = Vec::new();
+            if journal.starred {
+                details.push("Favourite".to_string());
+            }
+            if let Some(mood) = &journal.mood {
+                details.push(format!("Mood: {}", mood));
+            }
+            if !journal.tags.is_empty() {
+                details.push(format!("Tags: {}", journal.tags.join(", ")));
+            }
+            if !details.is_empty() {
+                pdf.paragraph(&details.join(" · "), 9.0, false);
+            }
+            if let Some(notes) = &journal.notes {
+                pdf.paragraph(notes.trim(), 10.0, false);
+            }
+            if let Some(outcome) = &journal.outcome {
+                pdf.paragraph("What happened", 10.0, true);
+                pdf.paragraph(outcome.trim(), 10.0, false);
+            }
+        }
+    }
+
+    pdf.doc
+        .save_to_bytes()
+        .map_err(|e| ExportError::Render(e.to_string()))
+}
@@ -1,40 +1,459 @@
 use axum::{
-    extract::{State, Json},
-    response::IntoResponse,
+    extract::{ConnectInfo, Path, Query, State, Json},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
 };
+use serde::Deserialize;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use crate::state::AppState;
-use crate::models::{DrawRequest, DrawResponse};
-use crate::ai_service;
+use crate::models::{
+    AuthResponse, ClaimRequest, ClaimResponse, DrawRequest, DrawResponse, JournalEntry,
+    JournalItem, JournalRevision, JournalUpdate, LoginRequest, OidcLinkResponse, SessionClaim,
+    ShareRequest, SignupRequest, User,
+};
+use crate::ai_service::{self, InterpretationRequest};
+use crate::auth::{self, Admin, AuthError, AuthUser};
+use crate::daily::{self, DailyCard, DailyError, DailyHistoryQuery, DailyOwner, DailyQuery};
 use crate::db;
+use crate::error::AppError;
+use crate::export::{self, ExportDocument, ExportError, ExportQuery, ExportScope};
+use crate::insights::{self, Insights, InsightsError, InsightsQuery};
+use crate::journal::{self, JournalError, JournalListQuery, JournalSearchQuery};
+use crate::quota;
+use crate::safety;
+use crate::session::{SessionError, MAX_SPREAD_SIZE};
+use crate::sse::{self, StreamedDraw};
+use crate::share::{self, ShareError, ShareLink, SharedReading};
+use crate::usage::UsageQuery;
 use uuid::Uuid;
 
 pub async fn draw_cards(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    user: Option<AuthUser>,
     Json(payload): Json<DrawRequest>,
-) -> impl IntoResponse {
+) -> Response {
+    // Simple session ID for now (random every request if not provided, ideally from cookie)
+    let session_id = Uuid::new_v4().to_string();
+    let client_ip = state.quota.client_ip(&headers, peer);
+    let quota_key = quota::rest_session_key(user.as_ref().map(|user| user.id), &headers)
+        .unwrap_or_else(|| session_id.clone());
+
+    if let Some(user) = &user {
+        if let Err(e) = db::attach_session_user(&state.db, &session_id, user.id).await {
+            tracing::error!(session_id = %session_id, "Failed to attach session to user: {}", e);
+        }
+    }
+
+    // A streamed reading goes through the session machine, which checks the count
+    if payload.stream && !(1..=MAX_SPREAD_SIZE).contains(&payload.count) {
+        return AppError::from(SessionError::InvalidSpreadSize).into_response();
+    }
+
+    // Before the screen, which may call the model too
+    if let Err(exceeded) = state.quota.try_start_reading(&quota_key, Some(client_ip)) {
+        tracing::warn!(session_id = %session_id, ip = %client_ip, "{}", exceeded);
+        return exceeded.into_response();
+    }
+
+    // Screen the query before anything is drawn
+    let decision = safety::screen_query(&state, &session_id, &quota_key, client_ip, &payload.user_query).await;
+    if let Some(message) = decision.response() {
+        return Json(DrawResponse {
+            session_id,
+            reading_id: None,
+            cards: Vec::new(),
+            interpretation_prompt: message.to_string(),
+            safety_category: decision.category,
+            stream_token: None,
+            claim_token: None,
+        })
+        .into_response();
+    }
+
+    // Only whoever holds this token may later move the reading into an account
+    let claim_token = match &user {
+        Some(_) => None,
+        None => {
+            let token = auth::random_hex(32);
+            match db::create_anonymous_session(&state.db, &session_id, &auth::hash_token(&token)).await {
+                Ok(_) => Some(token),
+                Err(e) => return AppError::internal("Failed to create session", e).into_response(),
+            }
+        }
+    };
+
+    let injection = ai_service::detect_injection(&payload.user_query);
+    if injection.is_suspicious() {
+        tracing::warn!(session_id = %session_id, signals = ?injection.signals, "Possible prompt injection in query");
+    }
+
+    let guardrails = decision.guardrails();
+    if payload.stream {
+        let draw = StreamedDraw {
+            session_id: &session_id,
+            quota_key: &quota_key,
+            client_ip,
+            user_id: user.as_ref().map(|user| user.id),
+            query: &payload.user_query,
+            count: payload.count,
+            bypass_cache: payload.bypass_cache,
+            guardrails,
+            injection,
+        };
+        return match sse::start_streamed_reading(&state, draw).await {
+            Ok(reading) => Json(DrawResponse {
+                session_id,
+                reading_id: Some(reading.reading_id),
+                cards: reading.cards,
+                interpretation_prompt: String::new(),
+                safety_category: decision.category,
+                stream_token: Some(reading.stream_token),
+                claim_token,
+            })
+            .into_response(),
+            Err(e) => e.into_response(),
+        };
+    }
+
     let cards = state.deck.draw_with_context(&payload.user_query, payload.count);
     
     // Generate Interpretation
-    let interpretation = ai_service::generate_interpretation(&payload.user_query, &cards).await;
+    let interpretation = state
+        .ai
+        .generate_interpretation(&InterpretationRequest {
+            query: &payload.user_query,
+            cards: &cards,
+            guardrails: guardrails.as_deref(),
+            follow_up: None,
+            bypass_cache: payload.bypass_cache,
+            offline_only: !state.quota.has_token_budget(&quota_key, Some(client_ip)),
+        })
+        .await;
+
+    if let Some(usage) = &interpretation.usage {
+        let tokens = (usage.prompt_tokens + usage.completion_tokens) as u64;
+        state.quota.record_tokens(&quota_key, Some(client_ip), tokens);
+    }
     
+    tracing::info!(session_id = %session_id, source = ?interpretation.source, "Interpretation ready");
+
     // Save to DB
-    // Simple session ID for now (random every request if not provided, ideally from cookie)
-    let session_id = Uuid::new_v4().to_string();
-    let cards_json = serde_json::to_value(&cards).unwrap_or_default();
+    let injection_json = serde_json::to_value(&injection).unwrap_or_default();
     
-    let _ = db::save_reading(
+    let reading_id = db::save_interpreted_reading(
         &state.db, 
         &session_id, 
         &payload.user_query, 
-        &cards_json, 
-        &interpretation
+        &cards, 
+        &interpretation.text,
+        &injection_json,
+        interpretation.usage.as_ref(),
     ).await;
+    if let Err(e) = &reading_id {
+        tracing::error!(session_id = %session_id, "Failed to save reading: {}", e);
+    }
 
     let response = DrawResponse {
+        session_id,
+        reading_id: reading_id.ok(),
         cards,
-        interpretation_prompt: interpretation,
+        interpretation_prompt: interpretation.text,
+        safety_category: decision.category,
+        stream_token: None,
+        claim_token,
     };
     
-    Json(response)
+    Json(response).into_response()
+}
+
+pub async fn cache_stats(_admin: Admin, State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.ai.cache().stats())
+}
+
+pub async fn usage_summary(
+    _admin: Admin,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UsageQuery>,
+) -> impl IntoResponse {
+    match db::summarize_llm_usage(&state.db, &query).await {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => AppError::internal("Failed to summarize LLM usage", e).into_response(),
+    }
+}
+
+async fn finish_login(
+    state: &AppState,
+    user: User,
+    anonymous_sessions: &[SessionClaim],
+) -> Result<AuthResponse, AuthError> {
+    let claimed_sessions = state.auth.claim_sessions(user.id, anonymous_sessions).await?;
+    let token = state.auth.issue_token(user.id).await?;
+
+    Ok(AuthResponse {
+        token: token.token,
+        expires_at: token.expires_at,
+        user,
+        claimed_sessions,
+    })
+}
+
+pub async fn signup(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SignupRequest>,
+) -> Result<(StatusCode, Json<AuthResponse>), AuthError> {
+    let user = state
+        .auth
+        .signup(&payload.email, payload.password, payload.display_name.as_deref())
+        .await?;
+    tracing::info!(user_id = user.id, "Account created");
+
+    let response = finish_login(&state, user, &payload.anonymous_sessions).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<AuthResponse>, AuthError> {
+    let user = state.auth.login(&payload.email, payload.password).await?;
+    Ok(Json(finish_login(&state, user, &payload.anonymous_sessions).await?))
+}
+
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    _user: AuthUser,
+    headers: HeaderMap,
+) -> Result<StatusCode, AuthError> {
+    if let Some(token) = auth::bearer_token(&headers) {
+        state.auth.revoke(token).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn current_user(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<User>, AuthError> {
+    db::get_user(&state.db, user.id)
+        .await?
+        .map(Json)
+        .ok_or(AuthError::InvalidToken)
+}
+
+pub async fn claim_sessions(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<ClaimRequest>,
+) -> Result<Json<ClaimResponse>, AuthError> {
+    let claimed_sessions = state.auth.claim_sessions(user.id, &payload.anonymous_sessions).await?;
+    Ok(Json(ClaimResponse { claimed_sessions }))
+}
+
+pub async fn oidc_start(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+) -> Result<Redirect, AuthError> {
+    let url = state.auth.begin_oidc(&provider, None)?;
+    Ok(Redirect::to(&url))
+}
+
+/// Link a provider to the signed-in account. Returns the provider URL
+/// rather than redirecting, since the request carries a bearer token.
+pub async fn oidc_link(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+    user: AuthUser,
+) -> Result<Json<OidcLinkResponse>, AuthError> {
+    let url = state.auth.begin_oidc(&provider, Some(user.id))?;
+    Ok(Json(OidcLinkResponse { url }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: String,
+    pub state: String,
+}
+
+pub async fn oidc_callback(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Result<Json<AuthResponse>, AuthError> {
+    let user = state
+        .auth
+        .complete_oidc(&provider, &query.state, &query.code)
+        .await?;
+    Ok(Json(finish_login(&state, user, &[]).await?))
+}
+
+pub async fn journal_list(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<JournalListQuery>,
+) -> Result<Json<Vec<JournalItem>>, JournalError> {
+    Ok(Json(journal::list(&state.db, user.id, &query).await?))
+}
+
+pub async fn journal_search(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<JournalSearchQuery>,
+) -> Result<Json<Vec<JournalItem>>, JournalError> {
+    Ok(Json(journal::search(&state.db, user.id, &query).await?))
+}
+
+pub async fn journal_entry(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(reading_id): Path<i64>,
+) -> Result<Json<JournalItem>, JournalError> {
+    Ok(Json(journal::get(&state.db, user.id, reading_id).await?))
+}
+
+pub async fn update_journal_entry(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(reading_id): Path<i64>,
+    Json(payload): Json<JournalUpdate>,
+) -> Result<Json<JournalEntry>, JournalError> {
+    Ok(Json(journal::update(&state.db, user.id, reading_id, payload).await?))
+}
+
+pub async fn delete_journal_entry(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(reading_id): Path<i64>,
+) -> Result<StatusCode, JournalError> {
+    journal::delete(&state.db, user.id, reading_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn journal_history(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(reading_id): Path<i64>,
+) -> Result<Json<Vec<JournalRevision>>, JournalError> {
+    Ok(Json(journal::history(&state.db, user.id, reading_id).await?))
+}
+
+pub async fn insights(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<InsightsQuery>,
+) -> Result<Json<Insights>, InsightsError> {
+    Ok(Json(insights::for_user(&state.db, &state.deck, user.id, &query).await?))
+}
+
+pub async fn export_reading(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(reading_id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ExportError> {
+    let scope = ExportScope::Reading {
+        user_id: user.id,
+        reading_id,
+    };
+    export::download(&state, &scope, query.format).await
+}
+
+pub async fn export_session(
+    State(state): State<Arc<AppState>>,
+    user: Option<AuthUser>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ExportError> {
+    let claim_token = headers.get(auth::CLAIM_HEADER).and_then(|v| v.to_str().ok());
+    let scope = export::session_scope(&state, &session_id, user.map(|u| u.id), claim_token).await?;
+    export::download(&state, &scope, query.format).await
+}
+
+pub async fn export_account(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ExportError> {
+    export::download(&state, &ExportScope::Account(user.id), query.format).await
+}
+
+pub async fn import_readings(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(document): Json<ExportDocument>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ExportError> {
+    let imported = export::import(&state, user.id, document).await?;
+    tracing::info!(user_id = user.id, imported, "Imported readings");
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "imported": imported }))))
+}
+
+pub async fn create_share(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(reading_id): Path<i64>,
+    payload: Option<Json<ShareRequest>>,
+) -> Result<(StatusCode, Json<ShareLink>), ShareError> {
+    let request = payload.map(|Json(r)| r).unwrap_or_default();
+    let link = share::create(&state, user.id, reading_id, request).await?;
+    Ok((StatusCode::CREATED, Json(link)))
+}
+
+pub async fn list_shares(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(reading_id): Path<i64>,
+) -> Result<Json<Vec<ShareLink>>, ShareError> {
+    Ok(Json(share::list(&state, user.id, reading_id).await?))
+}
+
+pub async fn revoke_share(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(token): Path<String>,
+) -> Result<StatusCode, ShareError> {
+    share::revoke(&state, user.id, &token).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn shared_reading(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<Json<SharedReading>, ShareError> {
+    Ok(Json(share::open(&state, &token).await?))
+}
+
+pub async fn shared_reading_page(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Response {
+    match share::open(&state, &token).await {
+        Ok(reading) => share::render_page(&reading),
+        Err(ShareError::NotFound) => share::render_not_found(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn daily_card(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    user: Option<AuthUser>,
+    Query(query): Query<DailyQuery>,
+) -> Result<Json<DailyCard>, DailyError> {
+    let owner = DailyOwner::from_request(user.map(|u| u.id), &headers)?;
+    let client_ip = state.quota.client_ip(&headers, peer);
+    Ok(Json(daily::today(&state, &owner, &query, client_ip).await?))
+}
+
+pub async fn daily_history(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    user: Option<AuthUser>,
+    Query(query): Query<DailyHistoryQuery>,
+) -> Result<Json<Vec<DailyCard>>, DailyError> {
+    let owner = DailyOwner::from_request(user.map(|u| u.id), &headers)?;
+    Ok(Json(daily::history(&state, &owner, &query).await?))
 }
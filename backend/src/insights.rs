@@ -0,0 +1,521 @@
+use axum::response::{IntoResponse, Response};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use thiserror::Error;
+
+use crate::db;
+use crate::error::{AppError, ErrorCode};
+use crate::models::{CardDraw, TarotCard};
+use crate::tarot_engine::{TarotDeck, REVERSED_PROBABILITY};
+
+/// Most card streaks reported.
+const MAX_CARD_STREAKS: usize = 10;
+
+/// Chi-square critical values at p = 0.05 for 1 to 4 degrees of freedom.
+const CHI_SQUARE_CRITICAL: [f64; 4] = [3.841, 5.991, 7.815, 9.488];
+
+/// Below this expected count per group a chi-square test is not meaningful.
+const MIN_EXPECTED_COUNT: f64 = 5.0;
+
+#[derive(Debug, Error)]
+pub enum InsightsError {
+    #[error("{0} must be a date in YYYY-MM-DD format")]
+    InvalidDate(&'static str),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<InsightsError> for AppError {
+    fn from(error: InsightsError) -> Self {
+        let code = match error {
+            InsightsError::InvalidDate(_) => ErrorCode::InvalidRequest,
+            InsightsError::Database(_) => return AppError::internal("Insights request failed", error),
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+impl IntoResponse for InsightsError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsightsQuery {
+    pub since: Option<String>, // YYYY-MM-DD, inclusive
+    pub until: Option<String>, // YYYY-MM-DD, inclusive
+}
+
+/// How often something was drawn compared with an unbiased draw from the deck.
+#[derive(Debug, Serialize)]
+pub struct Frequency {
+    pub count: usize,
+    pub share: f64,
+    pub expected_share: f64,
+    /// `share / expected_share`; above 1 means drawn more often than chance
+    pub ratio: f64,
+}
+
+impl Frequency {
+    fn new(count: usize, total: usize, expected_share: f64) -> Self {
+        let share = if total == 0 { 0.0 } else { count as f64 / total as f64 };
+        let ratio = if expected_share > 0.0 { share / expected_share } else { 0.0 };
+        Self {
+            count,
+            share: round(share),
+            expected_share: round(expected_share),
+            ratio: round(ratio),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CardFrequency {
+    pub card_id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub frequency: Frequency,
+    pub reversed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SuitFrequency {
+    pub suit: String, // "major" for the major arcana
+    #[serde(flatten)]
+    pub frequency: Frequency,
+}
+
+/// Chi-square goodness of fit of the suit counts against the deck.
+#[derive(Debug, Serialize)]
+pub struct GoodnessOfFit {
+    pub chi_square: f64,
+    pub degrees_of_freedom: usize,
+    /// Whether the difference is unlikely to be chance (p < 0.05)
+    pub significant: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Orientation {
+    pub upright: usize,
+    pub reversed: usize,
+    pub reversed_share: f64,
+    pub expected_reversed_share: f64,
+}
+
+/// A card that came up in consecutive readings.
+#[derive(Debug, Serialize)]
+pub struct CardStreak {
+    pub card_id: String,
+    pub name: String,
+    pub readings: usize,
+    pub started_at: String,
+    pub ended_at: String,
+    /// Still running as of the latest reading
+    pub ongoing: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Streaks {
+    /// Consecutive days with a reading, up to today or yesterday
+    pub current_days: usize,
+    pub longest_days: usize,
+    pub cards: Vec<CardStreak>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonthSummary {
+    pub month: String, // YYYY-MM
+    pub readings: usize,
+    pub cards: usize,
+    pub major_share: f64,
+    pub reversed_share: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Insights {
+    pub readings: usize,
+    pub cards_drawn: usize,
+    pub first_reading_at: Option<String>,
+    pub last_reading_at: Option<String>,
+    pub cards: Vec<CardFrequency>,
+    pub suits: Vec<SuitFrequency>,
+    pub suit_fit: Option<GoodnessOfFit>,
+    pub major_arcana: Frequency,
+    pub orientation: Orientation,
+    pub streaks: Streaks,
+    pub by_month: Vec<MonthSummary>,
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+fn parse_date(value: Option<&str>, name: &'static str) -> Result<Option<NaiveDate>, InsightsError> {
+    value
+        .map(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| InsightsError::InvalidDate(name)))
+        .transpose()
+}
+
+fn reading_date(created_at: &str) -> Option<NaiveDate> {
+    NaiveDateTime::parse_from_str(created_at, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| t.date())
+}
+
+pub async fn for_user(
+    db: &SqlitePool,
+    deck: &TarotDeck,
+    user_id: i64,
+    query: &InsightsQuery,
+) -> Result<Insights, InsightsError> {
+    let since = parse_date(query.since.as_deref(), "since")?;
+    let until = parse_date(query.until.as_deref(), "until")?;
+
+    let draws = db::get_user_card_draws(
+        db,
+        user_id,
+        since.map(|d| d.to_string()).as_deref(),
+        until.map(|d| d.to_string()).as_deref(),
+    )
+    .await?;
+
+    Ok(compute(deck, &draws, Utc::now().date_naive()))
+}
+
+/// The cards of one reading.
+struct Reading<'a> {
+    created_at: &'a str,
+    cards: Vec<&'a CardDraw>,
+}
+
+fn group_readings(draws: &[CardDraw]) -> Vec<Reading<'_>> {
+    let mut readings: Vec<Reading> = Vec::new();
+    let mut current_id = None;
+    for draw in draws {
+        if current_id != Some(draw.reading_id) {
+            current_id = Some(draw.reading_id);
+            readings.push(Reading {
+                created_at: &draw.created_at,
+                cards: Vec::new(),
+            });
+        }
+        if let Some(reading) = readings.last_mut() {
+            reading.cards.push(draw);
+        }
+    }
+    readings
+}
+
+/// Build the statistics from draws ordered oldest first.
+fn compute(deck: &TarotDeck, draws: &[CardDraw], today: NaiveDate) -> Insights {
+    let deck_size = deck.cards().len().max(1);
+    let total = draws.len();
+    let by_id: HashMap<&str, &TarotCard> =
+        deck.cards().iter().map(|c| (c.id.as_str(), c)).collect();
+    let name_of = |id: &str| by_id.get(id).map_or_else(|| id.to_string(), |c| c.name.clone());
+    let suit_of = |card: &TarotCard| card.suit.clone().unwrap_or_else(|| card.arcana.clone());
+    let is_major = |id: &str| by_id.get(id).is_some_and(|c| c.arcana == "major");
+
+    // Per card
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for draw in draws {
+        let entry = counts.entry(draw.card_id.as_str()).or_default();
+        entry.0 += 1;
+        if draw.reversed {
+            entry.1 += 1;
+        }
+    }
+    let card_share = 1.0 / deck_size as f64;
+    let mut cards: Vec<CardFrequency> = counts
+        .iter()
+        .map(|(id, (count, reversed))| CardFrequency {
+            card_id: id.to_string(),
+            name: name_of(id),
+            frequency: Frequency::new(*count, total, card_share),
+            reversed: *reversed,
+        })
+        .collect();
+    cards.sort_by(|a, b| {
+        b.frequency
+            .count
+            .cmp(&a.frequency.count)
+            .then_with(|| a.card_id.cmp(&b.card_id))
+    });
+
+    // Per suit, in deck order, with the major arcana as its own group
+    let mut suit_sizes: Vec<(String, usize)> = Vec::new();
+    for card in deck.cards() {
+        let suit = suit_of(card);
+        match suit_sizes.iter_mut().find(|(s, _)| *s == suit) {
+            Some((_, size)) => *size += 1,
+            None => suit_sizes.push((suit, 1)),
+        }
+    }
+    let mut suit_counts: HashMap<String, usize> = HashMap::new();
+    for draw in draws {
+        if let Some(card) = by_id.get(draw.card_id.as_str()) {
+            *suit_counts.entry(suit_of(card)).or_default() += 1;
+        }
+    }
+    let suits: Vec<SuitFrequency> = suit_sizes
+        .iter()
+        .map(|(suit, size)| SuitFrequency {
+            suit: suit.clone(),
+            frequency: Frequency::new(
+                suit_counts.get(suit).copied().unwrap_or(0),
+                total,
+                *size as f64 / deck_size as f64,
+            ),
+        })
+        .collect();
+    let suit_fit = goodness_of_fit(&suit_sizes, &suit_counts, deck_size);
+
+    let major_size = deck.cards().iter().filter(|c| c.arcana == "major").count();
+    let major_arcana = Frequency::new(
+        draws.iter().filter(|d| is_major(&d.card_id)).count(),
+        total,
+        major_size as f64 / deck_size as f64,
+    );
+
+    let reversed = draws.iter().filter(|d| d.reversed).count();
+    let orientation = Orientation {
+        upright: total - reversed,
+        reversed,
+        reversed_share: round(if total == 0 { 0.0 } else { reversed as f64 / total as f64 }),
+        expected_reversed_share: REVERSED_PROBABILITY,
+    };
+
+    let readings = group_readings(draws);
+    let (current_days, longest_days) = day_streaks(&readings, today);
+    let streaks = Streaks {
+        current_days,
+        longest_days,
+        cards: card_streaks(&readings, &name_of),
+    };
+
+    let mut months: BTreeMap<String, (usize, usize, usize, usize)> = BTreeMap::new();
+    for reading in &readings {
+        let month = reading.created_at.get(..7).unwrap_or_default().to_string();
+        let entry = months.entry(month).or_default();
+        entry.0 += 1;
+        entry.1 += reading.cards.len();
+        entry.2 += reading.cards.iter().filter(|d| is_major(&d.card_id)).count();
+        entry.3 += reading.cards.iter().filter(|d| d.reversed).count();
+    }
+    let by_month = months
+        .into_iter()
+        .map(|(month, (readings, cards, major, reversed))| {
+            let share = |n: usize| round(if cards == 0 { 0.0 } else { n as f64 / cards as f64 });
+            MonthSummary {
+                month,
+                readings,
+                cards,
+                major_share: share(major),
+                reversed_share: share(reversed),
+            }
+        })
+        .collect();
+
+    Insights {
+        readings: readings.len(),
+        cards_drawn: total,
+        first_reading_at: readings.first().map(|r| r.created_at.to_string()),
+        last_reading_at: readings.last().map(|r| r.created_at.to_string()),
+        cards,
+        suits,
+        suit_fit,
+        major_arcana,
+        orientation,
+        streaks,
+        by_month,
+    }
+}
+
+fn goodness_of_fit(
+    suit_sizes: &[(String, usize)],
+    suit_counts: &HashMap<String, usize>,
+    deck_size: usize,
+) -> Option<GoodnessOfFit> {
+    let total: usize = suit_counts.values().sum();
+    let degrees_of_freedom = suit_sizes.len().checked_sub(1)?;
+    let critical = *CHI_SQUARE_CRITICAL.get(degrees_of_freedom.checked_sub(1)?)?;
+
+    let mut chi_square = 0.0;
+    for (suit, size) in suit_sizes {
+        let expected = total as f64 * *size as f64 / deck_size as f64;
+        if expected < MIN_EXPECTED_COUNT {
+            return None;
+        }
+        let observed = suit_counts.get(suit).copied().unwrap_or(0) as f64;
+        chi_square += (observed - expected).powi(2) / expected;
+    }
+
+    Some(GoodnessOfFit {
+        chi_square: round(chi_square),
+        degrees_of_freedom,
+        significant: chi_square > critical,
+    })
+}
+
+/// Current and longest runs of consecutive days with at least one reading.
+fn day_streaks(readings: &[Reading], today: NaiveDate) -> (usize, usize) {
+    let days: Vec<NaiveDate> = readings
+        .iter()
+        .filter_map(|r| reading_date(r.created_at))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in &days {
+        run = match previous {
+            Some(p) if p.succ_opt() == Some(*day) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+
+    // A streak is still alive if the last reading was today or yesterday
+    let current = match previous {
+        Some(last) if (today - last).num_days() <= 1 => run,
+        _ => 0,
+    };
+
+    (current, longest)
+}
+
+/// Cards that appeared in two or more consecutive readings, longest first.
+fn card_streaks(readings: &[Reading], name_of: &dyn Fn(&str) -> String) -> Vec<CardStreak> {
+    let mut streaks = Vec::new();
+    // card id -> (readings in the run, index of the first reading)
+    let mut running: HashMap<&str, (usize, usize)> = HashMap::new();
+
+    for (index, reading) in readings.iter().enumerate() {
+        let present: HashSet<&str> = reading.cards.iter().map(|d| d.card_id.as_str()).collect();
+
+        // Close runs of cards missing from this reading
+        running.retain(|card_id, (length, start)| {
+            if present.contains(card_id) {
+                return true;
+            }
+            if *length >= 2 {
+                streaks.push(CardStreak {
+                    card_id: card_id.to_string(),
+                    name: name_of(card_id),
+                    readings: *length,
+                    started_at: readings[*start].created_at.to_string(),
+                    ended_at: readings[index - 1].created_at.to_string(),
+                    ongoing: false,
+                });
+            }
+            false
+        });
+
+        for card_id in present {
+            running.entry(card_id).or_insert((0, index)).0 += 1;
+        }
+    }
+
+    if let Some(last) = readings.last() {
+        for (card_id, (length, start)) in running {
+            if length >= 2 {
+                streaks.push(CardStreak {
+                    card_id: card_id.to_string(),
+                    name: name_of(card_id),
+                    readings: length,
+                    started_at: readings[start].created_at.to_string(),
+                    ended_at: last.created_at.to_string(),
+                    ongoing: true,
+                });
+            }
+        }
+    }
+
+    streaks.sort_by(|a, b| {
+        b.readings
+            .cmp(&a.readings)
+            .then_with(|| b.ended_at.cmp(&a.ended_at))
+            .then_with(|| a.card_id.cmp(&b.card_id))
+    });
+    streaks.truncate(MAX_CARD_STREAKS);
+    streaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Rider-Waite-Smith deck: 22 major arcana and four suits of 14.
+    fn suit_sizes() -> Vec<(String, usize)> {
+        [("major", 22), ("wands", 14), ("cups", 14), ("swords", 14), ("pentacles", 14)]
+            .iter()
+            .map(|&(suit, size)| (suit.to_string(), size))
+            .collect()
+    }
+
+    fn suit_fit(counts: [usize; 5]) -> Option<GoodnessOfFit> {
+        let sizes = suit_sizes();
+        let counts = sizes
+            .iter()
+            .zip(counts)
+            .map(|((suit, _), count)| (suit.clone(), count))
+            .collect();
+        goodness_of_fit(&sizes, &counts, 78)
+    }
+
+    #[test]
+    fn draws_in_proportion_to_the_deck_fit_perfectly() {
+        let fit = suit_fit([44, 28, 28, 28, 28]).unwrap();
+        assert_eq!(fit.chi_square, 0.0);
+        assert_eq!(fit.degrees_of_freedom, 4);
+        assert!(!fit.significant);
+    }
+
+    #[test]
+    fn chi_square_matches_hand_computed_values() {
+        // Expected 22 major and 14 of each suit:
+        // 8²/22 + 4 · 2²/14 = 4.0519
+        let fit = suit_fit([30, 12, 12, 12, 12]).unwrap();
+        assert_eq!(fit.chi_square, 4.0519);
+        assert!(!fit.significant);
+
+        // 28²/22 + 4 · 7²/14 = 49.6364
+        let fit = suit_fit([50, 7, 7, 7, 7]).unwrap();
+        assert_eq!(fit.chi_square, 49.6364);
+        assert!(fit.significant);
+    }
+
+    #[test]
+    fn significance_uses_the_critical_value_for_four_degrees_of_freedom() {
+        // Expected 44 major and 28 of each suit; the critical value is 9.488
+        let below = suit_fit([60, 22, 22, 26, 26]).unwrap();
+        assert_eq!(below.chi_square, 8.6753);
+        assert!(!below.significant);
+
+        let above = suit_fit([62, 22, 22, 25, 25]).unwrap();
+        assert_eq!(above.chi_square, 10.5779);
+        assert!(above.significant);
+    }
+
+    #[test]
+    fn suits_never_drawn_count_as_zero() {
+        let sizes = suit_sizes();
+        let counts = HashMap::from([("major".to_string(), 78)]);
+        let fit = goodness_of_fit(&sizes, &counts, 78).unwrap();
+        // 56²/22 + 4 · 14 = 198.5455
+        assert_eq!(fit.chi_square, 198.5455);
+        assert!(fit.significant);
+    }
+
+    #[test]
+    fn too_few_draws_give_no_test() {
+        // 14 of 78 cards expects fewer than five of a suit in 20 draws
+        assert!(suit_fit([6, 4, 4, 3, 3]).is_none());
+    }
+}
@@ -0,0 +1,244 @@
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use thiserror::Error;
+
+use crate::db;
+use crate::error::{AppError, ErrorCode};
+use crate::models::{JournalEntry, JournalItem, JournalRevision, JournalUpdate};
+
+const MAX_TEXT_CHARS: usize = 10_000;
+const MAX_MOOD_CHARS: usize = 32;
+const MAX_TAGS: usize = 20;
+const MAX_TAG_CHARS: usize = 32;
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error("reading not found")]
+    NotFound,
+    #[error("{0}")]
+    Invalid(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<JournalError> for AppError {
+    fn from(error: JournalError) -> Self {
+        let code = match error {
+            JournalError::NotFound => ErrorCode::NotFound,
+            JournalError::Invalid(_) => ErrorCode::InvalidRequest,
+            JournalError::Database(_) => return AppError::internal("Journal request failed", error),
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+impl IntoResponse for JournalError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+/// The editable part of an entry, after an update has been applied and
+/// validated.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JournalFields {
+    pub notes: Option<String>,
+    pub mood: Option<String>,
+    pub tags: Vec<String>,
+    pub outcome: Option<String>,
+    pub starred: bool,
+}
+
+impl From<&JournalEntry> for JournalFields {
+    fn from(entry: &JournalEntry) -> Self {
+        Self {
+            notes: entry.notes.clone(),
+            mood: entry.mood.clone(),
+            tags: entry.tags.clone(),
+            outcome: entry.outcome.clone(),
+            starred: entry.starred,
+        }
+    }
+}
+
+impl JournalFields {
+    /// A new entry's fields from an update, validated the same way as edits.
+    pub fn from_update(update: JournalUpdate) -> Result<Self, JournalError> {
+        let mut fields = Self::default();
+        fields.apply(update)?;
+        Ok(fields)
+    }
+
+    fn apply(&mut self, update: JournalUpdate) -> Result<(), JournalError> {
+        if let Some(notes) = update.notes {
+            self.notes = text_field("notes", notes, MAX_TEXT_CHARS)?;
+        }
+        if let Some(mood) = update.mood {
+            self.mood = text_field("mood", mood, MAX_MOOD_CHARS)?;
+        }
+        if let Some(tags) = update.tags {
+            self.tags = normalize_tags(tags)?;
+        }
+        if let Some(outcome) = update.outcome {
+            self.outcome = text_field("outcome", outcome, MAX_TEXT_CHARS)?;
+        }
+        if let Some(starred) = update.starred {
+            self.starred = starred;
+        }
+        Ok(())
+    }
+}
+
+/// Trimmed text, `None` when blank.
+fn text_field(name: &str, value: String, max_chars: usize) -> Result<Option<String>, JournalError> {
+    let value = value.trim();
+    if value.chars().count() > max_chars {
+        return Err(JournalError::Invalid(format!(
+            "{} must be at most {} characters",
+            name, max_chars
+        )));
+    }
+    Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+/// Lowercase, trimmed, de-duplicated tags in the order given.
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, JournalError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || normalized.contains(&tag) {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_CHARS {
+            return Err(JournalError::Invalid(format!(
+                "tags must be at most {} characters",
+                MAX_TAG_CHARS
+            )));
+        }
+        normalized.push(tag);
+    }
+
+    if normalized.len() > MAX_TAGS {
+        return Err(JournalError::Invalid(format!("at most {} tags are allowed", MAX_TAGS)));
+    }
+    Ok(normalized)
+}
+
+/// Turn free text into an FTS5 expression matching every word as a prefix,
+/// so "job offer" finds "job offers" and Korean words with particles
+/// attached. Returns `None` if there is nothing to search for.
+fn fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JournalListQuery {
+    #[serde(default)]
+    pub starred: bool,
+    pub tag: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JournalSearchQuery {
+    pub q: String,
+    pub limit: Option<u32>,
+}
+
+fn page_size(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+pub async fn list(
+    db: &SqlitePool,
+    user_id: i64,
+    query: &JournalListQuery,
+) -> Result<Vec<JournalItem>, JournalError> {
+    let tag = query
+        .tag
+        .as_deref()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty());
+
+    Ok(db::list_journal(
+        db,
+        user_id,
+        query.starred,
+        tag.as_deref(),
+        page_size(query.limit),
+        query.offset.unwrap_or(0),
+    )
+    .await?)
+}
+
+pub async fn search(
+    db: &SqlitePool,
+    user_id: i64,
+    query: &JournalSearchQuery,
+) -> Result<Vec<JournalItem>, JournalError> {
+    let expression = fts_query(&query.q)
+        .ok_or_else(|| JournalError::Invalid("search text is required".to_string()))?;
+
+    Ok(db::search_journal(db, user_id, &expression, page_size(query.limit)).await?)
+}
+
+pub async fn get(db: &SqlitePool, user_id: i64, reading_id: i64) -> Result<JournalItem, JournalError> {
+    db::get_journal_item(db, user_id, reading_id)
+        .await?
+        .ok_or(JournalError::NotFound)
+}
+
+/// Apply `update` to the reading's entry, creating it on first write.
+/// Updates that change nothing are not saved, so they leave no revision.
+pub async fn update(
+    db: &SqlitePool,
+    user_id: i64,
+    reading_id: i64,
+    update: JournalUpdate,
+) -> Result<JournalEntry, JournalError> {
+    let item = get(db, user_id, reading_id).await?;
+
+    let current = item.entry.as_ref().map(JournalFields::from).unwrap_or_default();
+    let mut fields = current.clone();
+    fields.apply(update)?;
+
+    match item.entry {
+        Some(entry) if fields == current => Ok(entry),
+        _ => Ok(db::save_journal_entry(db, user_id, reading_id, &fields).await?),
+    }
+}
+
+pub async fn delete(db: &SqlitePool, user_id: i64, reading_id: i64) -> Result<(), JournalError> {
+    get(db, user_id, reading_id).await?;
+
+    if db::delete_journal_entry(db, reading_id).await? {
+        Ok(())
+    } else {
+        Err(JournalError::NotFound)
+    }
+}
+
+pub async fn history(
+    db: &SqlitePool,
+    user_id: i64,
+    reading_id: i64,
+) -> Result<Vec<JournalRevision>, JournalError> {
+    let entry = get(db, user_id, reading_id)
+        .await?
+        .entry
+        .ok_or(JournalError::NotFound)?;
+
+    Ok(db::get_journal_revisions(db, entry.id).await?)
+}
@@ -0,0 +1,624 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Table geometry. The deck lies around the origin, x grows to the right and
+// y towards the seeker; rotations are in degrees. Every card gets its own
+// z_index, so cards the client drops into a physics scene never start
+// inside each other.
+const CARD_WIDTH: f32 = 60.0;
+const CARD_HEIGHT: f32 = 100.0;
+/// Each card of a stack sits this far up and right of the one below it.
+const STACK_STEP: f32 = 0.25;
+/// Pivot distance of the fan; the cards' lower edges meet near it.
+const FAN_RADIUS: f32 = 320.0;
+const FAN_DEGREES_PER_CARD: f32 = 1.8;
+const FAN_MAX_DEGREES: f32 = 150.0;
+/// A circle never gets smaller than this, however few cards it holds.
+const CIRCLE_MIN_RADIUS: f32 = 120.0;
+/// Slots for the selected cards, in rows in front of the deck.
+const SLOT_ROW_Y: f32 = 120.0;
+const SLOT_GAP: f32 = 20.0;
+const SLOTS_PER_ROW: usize = 5;
+/// Where the two halves of a riffle or a lifted deck go.
+const PILE_OFFSET: f32 = 110.0;
+const PILE_TILT: f32 = 8.0;
+
+// Choreography timing.
+const LIFT_MS: u32 = 250;
+const SETTLE_MS: u32 = 120;
+/// Between two cards falling in a riffle.
+const RIFFLE_DROP_MS: u32 = 6;
+/// Between two packets falling in an overhand shuffle.
+const OVERHAND_PACKET_MS: u32 = 110;
+const OVERHAND_PACKET_SIZES: std::ops::RangeInclusive<usize> = 3..=8;
+const CUT_MS: u32 = 300;
+
+/// How the face-down deck is spread on the table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeckLayout {
+    /// An arc to pick from, top card on the left.
+    #[default]
+    Fan,
+    /// A ring, top card at twelve o'clock, going clockwise.
+    Circle,
+    /// A squared deck.
+    Stack,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardPosition {
+    pub card_id: String,
+    pub x: f32,
+    pub y: f32,
+    pub rotation: f32,
+    pub is_face_up: bool,
+    pub z_index: usize,
+}
+
+/// One card moving during a shuffle. Steps start `delay_ms` after the
+/// animation does; a card may have several.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShuffleStep {
+    pub card_id: String,
+    pub phase: ShuffleMove,
+    pub from: Position,
+    pub to: Position,
+    /// Layer of the card once it arrives.
+    pub z_index: usize,
+    pub delay_ms: u32,
+    pub duration_ms: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+    pub rotation: f32,
+}
+
+/// The phases a shuffle choreography is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShuffleMove {
+    Overhand,
+    Riffle,
+    Cut,
+}
+
+/// The id the client knows a physical card by. Cards are numbered where
+/// they lay in the session's first deck; the number says nothing about
+/// which card it is.
+pub fn card_id(card: usize) -> String {
+    format!("card_{}", card)
+}
+
+/// The whole table: `cards[i]` is the card at deck position `i`, face down
+/// in `layout`, except the `selected` positions, which lie face up in their
+/// spread slots in the order they were picked.
+pub fn table(layout: DeckLayout, cards: &[usize], selected: &[usize], spread_size: usize) -> Vec<CardPosition> {
+    let count = cards.len();
+    cards
+        .iter()
+        .enumerate()
+        .map(|(index, &card)| {
+            let (position, is_face_up, z_index) = match selected.iter().position(|&s| s == index) {
+                Some(slot) => (slot_position(layout, count, slot, spread_size), true, count + slot),
+                None => (deck_position(layout, index, count), false, deck_z_index(layout, index, count)),
+            };
+            CardPosition {
+                card_id: card_id(card),
+                x: position.x,
+                y: position.y,
+                rotation: position.rotation,
+                is_face_up,
+                z_index,
+            }
+        })
+        .collect()
+}
+
+fn deck_position(layout: DeckLayout, index: usize, count: usize) -> Position {
+    match layout {
+        DeckLayout::Fan => fan_position(index, count),
+        DeckLayout::Circle => circle_position(index, count),
+        DeckLayout::Stack => stack_position(index, count, 0.0),
+    }
+}
+
+/// Fans and circles are laid left to right; a stack has its top card
+/// (position 0) uppermost.
+fn deck_z_index(layout: DeckLayout, index: usize, count: usize) -> usize {
+    match layout {
+        DeckLayout::Fan | DeckLayout::Circle => index,
+        DeckLayout::Stack => stack_z_index(index, count),
+    }
+}
+
+fn stack_z_index(index: usize, count: usize) -> usize {
+    count - 1 - index
+}
+
+/// Position `index` of a squared deck of `count` cards centred on `x`.
+fn stack_position(index: usize, count: usize, x: f32) -> Position {
+    let height = stack_z_index(index, count) as f32 * STACK_STEP;
+    position(x + height, -height, 0.0)
+}
+
+/// The arc widens with the deck until it reaches `FAN_MAX_DEGREES`.
+fn fan_position(index: usize, count: usize) -> Position {
+    let spread = (count.saturating_sub(1) as f32 * FAN_DEGREES_PER_CARD).min(FAN_MAX_DEGREES);
+    let angle = if count > 1 {
+        -spread / 2.0 + spread * index as f32 / (count - 1) as f32
+    } else {
+        0.0
+    };
+    let radians = angle.to_radians();
+    position(
+        FAN_RADIUS * radians.sin(),
+        -FAN_RADIUS * (1.0 - radians.cos()),
+        angle,
+    )
+}
+
+/// The ring grows so neighbouring cards overlap by no more than a third.
+fn circle_radius(count: usize) -> f32 {
+    let circumference = count as f32 * CARD_WIDTH * 2.0 / 3.0;
+    (circumference / std::f32::consts::TAU).max(CIRCLE_MIN_RADIUS)
+}
+
+fn circle_position(index: usize, count: usize) -> Position {
+    let radius = circle_radius(count);
+    let angle = 360.0 * index as f32 / count.max(1) as f32;
+    let radians = angle.to_radians();
+    position(radius * radians.sin(), -radius * radians.cos(), angle)
+}
+
+/// Slot `slot` of a spread of `spread_size`, in front of a deck of
+/// `count` cards: one centred row, or rows of `SLOTS_PER_ROW` for bigger
+/// spreads.
+pub fn slot_position(layout: DeckLayout, count: usize, slot: usize, spread_size: usize) -> Position {
+    let front = match layout {
+        DeckLayout::Fan | DeckLayout::Stack => 0.0,
+        DeckLayout::Circle => circle_radius(count),
+    };
+    let row = slot / SLOTS_PER_ROW;
+    let in_row = spread_size.saturating_sub(row * SLOTS_PER_ROW).clamp(1, SLOTS_PER_ROW);
+    let column = (slot % SLOTS_PER_ROW) as f32 - (in_row - 1) as f32 / 2.0;
+    position(
+        column * (CARD_WIDTH + SLOT_GAP),
+        front + SLOT_ROW_Y + row as f32 * (CARD_HEIGHT + SLOT_GAP),
+        0.0,
+    )
+}
+
+/// Rounded to hundredths; nothing on screen needs more and it keeps the
+/// messages short. Adding zero turns `-0.0` into `0.0`.
+fn position(x: f32, y: f32, rotation: f32) -> Position {
+    let round = |v: f32| (v * 100.0).round() / 100.0 + 0.0;
+    Position {
+        x: round(x),
+        y: round(y),
+        rotation: round(rotation),
+    }
+}
+
+/// Animate a shuffle of a squared deck from the order `before` to `after`,
+/// both listing cards top to bottom: an overhand pass, riffles and a
+/// final cut.
+///
+/// The overhand pass and the cut are random. The riffles are chosen so the
+/// deck ends exactly in `after`: each riffle can sort the deck by one bit of
+/// a card's target position, so ⌈log₂ n⌉ of them reach any order (seven
+/// for a full deck).
+pub fn choreograph(before: &[usize], after: &[usize]) -> Vec<ShuffleStep> {
+    let count = before.len();
+    if count < 2 || after.len() != count {
+        return Vec::new();
+    }
+    let mut rng = rand::rng();
+    let mut dealer = Dealer::new(before.to_vec());
+
+    dealer.overhand(&mut rng);
+
+    // The cut moves the top `cut` cards to the bottom, so the riffles
+    // aim for `after` with those cards still on top
+    let cut = rng.random_range(count / 3..=count * 2 / 3).max(1);
+    let mut before_cut = after[count - cut..].to_vec();
+    before_cut.extend_from_slice(&after[..count - cut]);
+
+    for packets in riffles(&dealer.order, &before_cut) {
+        dealer.riffle(&packets);
+    }
+    dealer.cut(cut);
+
+    debug_assert_eq!(dealer.order, after);
+    dealer.steps
+}
+
+/// The riffles that turn `from` into `to`, each as which packet every card
+/// of the result comes from (`false` for the top half).
+///
+/// Built backwards: undoing a riffle deals the deck into two piles, which
+/// is one pass of a radix sort. Sorting `to` by each card's position in
+/// `from`, lowest bit first, gives back `from`; replaying those passes in
+/// reverse as riffles goes from `from` to `to`.
+fn riffles(from: &[usize], to: &[usize]) -> Vec<Vec<bool>> {
+    let max_card = from.iter().copied().max().unwrap_or(0);
+    let mut rank = vec![0; max_card + 1];
+    for (position, &card) in from.iter().enumerate() {
+        rank[card] = position;
+    }
+
+    let bits = usize::BITS - (from.len() - 1).leading_zeros();
+    let mut deck = to.to_vec();
+    let mut passes = Vec::with_capacity(bits as usize);
+    for bit in 0..bits {
+        let packets: Vec<bool> = deck.iter().map(|&card| (rank[card] >> bit) & 1 == 1).collect();
+        let pile = |bottom: bool| {
+            deck.iter()
+                .zip(&packets)
+                .filter(move |&(_, &b)| b == bottom)
+                .map(|(&card, _)| card)
+        };
+        deck = pile(false).chain(pile(true)).collect();
+        passes.push(packets);
+    }
+    debug_assert_eq!(deck, from);
+
+    passes.reverse();
+    passes
+}
+
+/// Tracks the deck while the choreography is written.
+struct Dealer {
+    order: Vec<usize>,
+    steps: Vec<ShuffleStep>,
+    /// When the next move starts.
+    clock_ms: u32,
+    /// The move being written.
+    phase: ShuffleMove,
+}
+
+impl Dealer {
+    fn new(order: Vec<usize>) -> Self {
+        Self {
+            order,
+            steps: Vec::new(),
+            clock_ms: 0,
+            phase: ShuffleMove::Overhand,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.order.len()
+    }
+
+    fn step(&mut self, card: usize, from: Position, to: Position, z_index: usize, delay_ms: u32, duration_ms: u32) {
+        self.steps.push(ShuffleStep {
+            card_id: card_id(card),
+            phase: self.phase,
+            from,
+            to,
+            z_index,
+            delay_ms,
+            duration_ms,
+        });
+    }
+
+    /// Lift the deck to the right, then pull small packets off its top onto
+    /// the table, each landing on the one before.
+    fn overhand(&mut self, rng: &mut impl Rng) {
+        self.phase = ShuffleMove::Overhand;
+        let count = self.count();
+        let start = self.clock_ms;
+        for index in 0..count {
+            let card = self.order[index];
+            let z_index = stack_z_index(index, count);
+            self.step(
+                card,
+                stack_position(index, count, 0.0),
+                stack_position(index, count, PILE_OFFSET),
+                z_index,
+                start,
+                LIFT_MS,
+            );
+        }
+
+        let mut packets = Vec::new();
+        let mut taken = 0;
+        while taken < count {
+            let size = rng.random_range(OVERHAND_PACKET_SIZES).min(count - taken);
+            packets.push(taken..taken + size);
+            taken += size;
+        }
+
+        let mut order = Vec::with_capacity(count);
+        for packet in packets.iter().rev() {
+            order.extend_from_slice(&self.order[packet.clone()]);
+        }
+
+        let drop_start = start + LIFT_MS;
+        let mut landed = 0;
+        for (n, packet) in packets.iter().enumerate() {
+            // The packet lands under everything dropped so far
+            let delay_ms = drop_start + n as u32 * OVERHAND_PACKET_MS;
+            landed += packet.len();
+            for (offset, old_index) in packet.clone().enumerate() {
+                let new_index = count - landed + offset;
+                self.step(
+                    self.order[old_index],
+                    stack_position(old_index, count, PILE_OFFSET),
+                    stack_position(new_index, count, 0.0),
+                    stack_z_index(new_index, count),
+                    delay_ms,
+                    SETTLE_MS,
+                );
+            }
+        }
+
+        self.order = order;
+        self.clock_ms = drop_start + packets.len() as u32 * OVERHAND_PACKET_MS + SETTLE_MS;
+    }
+
+    /// Split the deck into a left and a right half and let them fall
+    /// together, bottom card first, interleaved as `packets` says.
+    fn riffle(&mut self, packets: &[bool]) {
+        self.phase = ShuffleMove::Riffle;
+        let count = self.count();
+        let split = packets.iter().filter(|&&b| !b).count();
+        let start = self.clock_ms;
+        let half = |index: usize| {
+            if index < split {
+                (index, split, -PILE_OFFSET, -PILE_TILT)
+            } else {
+                (index - split, count - split, PILE_OFFSET, PILE_TILT)
+            }
+        };
+
+        for index in 0..count {
+            let (in_half, half_size, x, tilt) = half(index);
+            let mut to = stack_position(in_half, half_size, x);
+            to.rotation = tilt;
+            self.step(
+                self.order[index],
+                stack_position(index, count, 0.0),
+                to,
+                stack_z_index(in_half, half_size),
+                start,
+                LIFT_MS,
+            );
+        }
+
+        let mut order = Vec::with_capacity(count);
+        let (mut top, mut bottom) = (0, split);
+        for (new_index, &from_bottom) in packets.iter().enumerate() {
+            let old_index = if from_bottom { &mut bottom } else { &mut top };
+            let (in_half, half_size, x, tilt) = half(*old_index);
+            let mut from = stack_position(in_half, half_size, x);
+            from.rotation = tilt;
+            let card = self.order[*old_index];
+            self.step(
+                card,
+                from,
+                stack_position(new_index, count, 0.0),
+                stack_z_index(new_index, count),
+                start + LIFT_MS + stack_z_index(new_index, count) as u32 * RIFFLE_DROP_MS,
+                SETTLE_MS,
+            );
+            order.push(card);
+            *old_index += 1;
+        }
+
+        self.order = order;
+        self.clock_ms = start + LIFT_MS + count as u32 * RIFFLE_DROP_MS + SETTLE_MS;
+    }
+
+    /// Set the top `cut` cards aside and put the rest on top of them.
+    fn cut(&mut self, cut: usize) {
+        self.phase = ShuffleMove::Cut;
+        let count = self.count();
+        let start = self.clock_ms;
+        for index in 0..cut {
+            self.step(
+                self.order[index],
+                stack_position(index, count, 0.0),
+                stack_position(index, cut, -PILE_OFFSET),
+                stack_z_index(index, cut),
+                start,
+                CUT_MS,
+            );
+        }
+
+        let mut order = self.order[cut..].to_vec();
+        order.extend_from_slice(&self.order[..cut]);
+        for (new_index, &card) in order.iter().enumerate() {
+            let from = if new_index < count - cut {
+                stack_position(new_index + cut, count, 0.0)
+            } else {
+                stack_position(new_index - (count - cut), cut, -PILE_OFFSET)
+            };
+            self.step(
+                card,
+                from,
+                stack_position(new_index, count, 0.0),
+                stack_z_index(new_index, count),
+                start + CUT_MS,
+                CUT_MS,
+            );
+        }
+
+        self.order = order;
+        self.clock_ms = start + 2 * CUT_MS;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+    use std::collections::BTreeSet;
+
+    fn shuffled(count: usize, rng: &mut impl Rng) -> Vec<usize> {
+        let mut cards: Vec<usize> = (0..count).collect();
+        cards.shuffle(rng);
+        cards
+    }
+
+    /// The order each phase leaves the deck in, read from where the phase's
+    /// last step puts every card. Also checks each card starts every step
+    /// where its previous one ended.
+    fn replay(before: &[usize], steps: &[ShuffleStep]) -> Vec<(ShuffleMove, Vec<usize>)> {
+        let count = before.len();
+        let mut at: Vec<Position> = (0..count).map(|index| stack_position(index, count, 0.0)).collect();
+        let card_at = |card_id: &str| before.iter().position(|&card| self::card_id(card) == card_id).unwrap();
+
+        let mut phases: Vec<(ShuffleMove, Vec<ShuffleStep>)> = Vec::new();
+        for step in steps {
+            match phases.last_mut() {
+                Some((phase, phase_steps)) if *phase == step.phase => phase_steps.push(step.clone()),
+                _ => phases.push((step.phase, vec![step.clone()])),
+            }
+        }
+
+        phases
+            .into_iter()
+            .map(|(phase, phase_steps)| {
+                let mut order = vec![None; count];
+                for step in &phase_steps {
+                    let slot = card_at(&step.card_id);
+                    assert_eq!(step.from, at[slot], "{} jumps before a {:?} step", step.card_id, phase);
+                    at[slot] = step.to;
+                    // Every phase ends with the deck squared again
+                    order[count - 1 - step.z_index] = Some(before[slot]);
+                }
+                let moved: BTreeSet<&str> = phase_steps.iter().map(|step| step.card_id.as_str()).collect();
+                assert_eq!(moved.len(), count, "a card sits out the {:?}", phase);
+                (phase, order.into_iter().map(Option::unwrap).collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn riffles_reach_any_order() {
+        let mut rng = rand::rng();
+        for count in 2..=78 {
+            let from = shuffled(count, &mut rng);
+            let to = shuffled(count, &mut rng);
+            let passes = riffles(&from, &to);
+            assert_eq!(passes.len(), count.next_power_of_two().trailing_zeros() as usize);
+
+            let mut deck = from.clone();
+            for packets in &passes {
+                let split = packets.iter().filter(|&&bottom| !bottom).count();
+                let (mut top, mut bottom) = (deck[..split].iter(), deck[split..].iter());
+                deck = packets
+                    .iter()
+                    .map(|&from_bottom| *if from_bottom { bottom.next() } else { top.next() }.unwrap())
+                    .collect();
+            }
+            assert_eq!(deck, to, "{} cards", count);
+        }
+    }
+
+    #[test]
+    fn choreography_ends_in_the_committed_order() {
+        let mut rng = rand::rng();
+        for count in 2..=78 {
+            let before = shuffled(count, &mut rng);
+            let mut after = before.clone();
+            after.shuffle(&mut rng);
+
+            let phases = replay(&before, &choreograph(&before, &after));
+            let moves: Vec<ShuffleMove> = phases.iter().map(|(phase, _)| *phase).collect();
+            assert_eq!(moves.first(), Some(&ShuffleMove::Overhand));
+            assert_eq!(moves.last(), Some(&ShuffleMove::Cut));
+            assert!(moves[1..moves.len() - 1].iter().all(|&phase| phase == ShuffleMove::Riffle));
+            assert_eq!(phases.last().unwrap().1, after, "{} cards", count);
+        }
+    }
+
+    #[test]
+    fn choreography_keeps_the_cards_it_is_given() {
+        // A deck with cards taken out still has numbers up to the full deck
+        let before = vec![77, 3, 40, 12, 5];
+        let after = vec![5, 40, 77, 12, 3];
+        let phases = replay(&before, &choreograph(&before, &after));
+        assert_eq!(phases.last().unwrap().1, after);
+    }
+
+    #[test]
+    fn nothing_to_animate_for_a_single_card() {
+        assert!(choreograph(&[7], &[7]).is_empty());
+        assert!(choreograph(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn fan_is_symmetric_around_its_middle_card() {
+        assert_eq!(fan_position(0, 1), position(0.0, 0.0, 0.0));
+        assert_eq!(fan_position(1, 3), position(0.0, 0.0, 0.0));
+
+        let (left, right) = (fan_position(0, 3), fan_position(2, 3));
+        assert_eq!(left.rotation, -1.8);
+        assert_eq!(right.rotation, 1.8);
+        assert_eq!(left.x, -right.x);
+        assert_eq!(left.y, right.y);
+        assert!(left.y < 0.0);
+
+        // A full deck stops widening at the card spacing, short of the maximum
+        assert_eq!(fan_position(0, 78).rotation, -69.3);
+        assert_eq!(fan_position(77, 78).rotation, 69.3);
+    }
+
+    #[test]
+    fn circle_starts_at_twelve_and_goes_clockwise() {
+        let ring: Vec<Position> = (0..4).map(|index| circle_position(index, 4)).collect();
+        assert_eq!(
+            ring,
+            vec![
+                position(0.0, -CIRCLE_MIN_RADIUS, 0.0),
+                position(CIRCLE_MIN_RADIUS, 0.0, 90.0),
+                position(0.0, CIRCLE_MIN_RADIUS, 180.0),
+                position(-CIRCLE_MIN_RADIUS, 0.0, 270.0),
+            ]
+        );
+        assert!(circle_radius(78) > CIRCLE_MIN_RADIUS);
+    }
+
+    #[test]
+    fn slots_are_centred_in_rows_in_front_of_the_deck() {
+        let xs = |spread_size: usize| -> Vec<f32> {
+            (0..spread_size)
+                .map(|slot| slot_position(DeckLayout::Fan, 78, slot, spread_size).x)
+                .collect()
+        };
+        assert_eq!(xs(1), vec![0.0]);
+        assert_eq!(xs(3), vec![-80.0, 0.0, 80.0]);
+        assert_eq!(xs(7), vec![-160.0, -80.0, 0.0, 80.0, 160.0, -40.0, 40.0]);
+
+        assert_eq!(slot_position(DeckLayout::Stack, 10, 0, 3).y, SLOT_ROW_Y);
+        assert_eq!(slot_position(DeckLayout::Fan, 78, 6, 7).y, SLOT_ROW_Y + CARD_HEIGHT + SLOT_GAP);
+        // A ring's slots sit in front of the ring, however big it grows
+        assert_eq!(
+            slot_position(DeckLayout::Circle, 78, 0, 3).y,
+            position(0.0, circle_radius(78) + SLOT_ROW_Y, 0.0).y
+        );
+    }
+
+    #[test]
+    fn selected_cards_lie_face_up_above_the_deck() {
+        let cards = [4, 2, 0, 1, 3];
+        let table = table(DeckLayout::Stack, &cards, &[3, 0], 2);
+
+        let face_up: Vec<(&str, usize)> = table
+            .iter()
+            .filter(|card| card.is_face_up)
+            .map(|card| (card.card_id.as_str(), card.z_index))
+            .collect();
+        assert_eq!(face_up, vec![("card_4", 6), ("card_1", 5)]);
+
+        let top = &table[1];
+        assert_eq!((top.x, top.y), (stack_position(1, 5, 0.0).x, stack_position(1, 5, 0.0).y));
+        assert_eq!(top.z_index, 3);
+    }
+}
@@ -1,22 +1,51 @@
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
 };
 use dotenv::dotenv;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tower_http::cors::CorsLayer;
+use tokio::sync::watch;
+use tokio::time::Instant;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod auth;
+mod config;
+mod daily;
 mod db;
+mod error;
+mod export;
 mod handlers;
+mod insights;
+mod journal;
+mod layout;
 mod state;
 mod models;
+mod quota;
+mod rate_limit;
 mod tarot_engine;
 mod ai_service;
+mod cache;
+mod moderation;
+mod protocol;
+mod safety;
+mod session;
+mod share;
+mod sse;
+mod usage;
 mod ws_handler;
 
+use crate::ai_service::{AiService, DeepSeekProvider};
+use crate::auth::AuthService;
+use crate::cache::InterpretationCache;
+use crate::config::Config;
+use crate::export::Exporter;
+use crate::quota::QuotaManager;
+use crate::rate_limit::RateLimiter;
+use crate::session::SessionRegistry;
 use crate::state::AppState;
 use crate::tarot_engine::TarotDeck;
 
@@ -32,32 +61,178 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    match &config.file {
+        Some(path) => tracing::info!("Loaded configuration from {}", path.display()),
+        None => tracing::info!("No config file found, using defaults and environment"),
+    }
+    tracing::info!(
+        "Effective configuration:\n{}",
+        toml::to_string_pretty(&config.redacted())?
+    );
+
     // Database setup
-    let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:tarot.db".to_string());
-    let pool = db::init_db(&database_url).await?;
+    let pool = db::init_db(&config.database.url, config.database.max_connections).await?;
 
     // Initialize Deck
-    let deck = TarotDeck::new();
+    let deck = TarotDeck::load(&config.deck.path)?;
+
+    // AI Service
+    let cache = InterpretationCache::new(config.cache.clone(), Some(pool.clone()));
+    let ai = AiService::new(
+        Box::new(DeepSeekProvider::new(config.ai.api_key.clone())),
+        cache,
+        &config.ai,
+        &deck,
+    );
+
+    let export = Exporter::new(&config.export)?;
+
+    let cors = config.cors.layer();
+    let addr = config.server.bind;
+
+    let (shutdown_tx, shutdown) = watch::channel(false);
 
     // Shared State
     let state = Arc::new(AppState {
-        db: pool,
+        db: pool.clone(),
         deck,
+        ai,
+        quota: QuotaManager::new(config.quota.clone()),
+        rate_limit: RateLimiter::new(config.rate_limit.clone()),
+        auth: AuthService::new(config.auth.clone(), pool.clone()),
+        export,
+        sessions: SessionRegistry::new(config.session.clone()),
+        config,
+        shutdown,
     });
 
     // Router
+    // The WebSocket route throttles per connection in the handler instead
+    let api = Router::new()
+        .route("/api/draw", post(handlers::draw_cards))
+        .route("/api/cache/stats", get(handlers::cache_stats))
+        .route("/api/usage", get(handlers::usage_summary))
+        .route("/api/auth/signup", post(handlers::signup))
+        .route("/api/auth/login", post(handlers::login))
+        .route("/api/auth/logout", post(handlers::logout))
+        .route("/api/auth/me", get(handlers::current_user))
+        .route("/api/auth/claim", post(handlers::claim_sessions))
+        .route("/api/auth/oidc/{provider}/start", get(handlers::oidc_start))
+        .route("/api/auth/oidc/{provider}/link", post(handlers::oidc_link))
+        .route("/api/auth/oidc/{provider}/callback", get(handlers::oidc_callback))
+        .route("/api/journal", get(handlers::journal_list))
+        .route("/api/journal/search", get(handlers::journal_search))
+        .route(
+            "/api/readings/{id}/journal",
+            get(handlers::journal_entry)
+                .patch(handlers::update_journal_entry)
+                .delete(handlers::delete_journal_entry),
+        )
+        .route("/api/readings/{id}/journal/history", get(handlers::journal_history))
+        .route("/api/insights", get(handlers::insights))
+        .route("/api/daily", get(handlers::daily_card))
+        .route("/api/daily/history", get(handlers::daily_history))
+        .route("/api/readings/{id}/stream", get(sse::interpretation_stream))
+        .route("/api/readings/{id}/export", get(handlers::export_reading))
+        .route("/api/sessions/{session_id}/export", get(handlers::export_session))
+        .route("/api/export", get(handlers::export_account))
+        .route("/api/import", post(handlers::import_readings))
+        .route(
+            "/api/readings/{id}/shares",
+            get(handlers::list_shares).post(handlers::create_share),
+        )
+        .route(
+            "/api/shares/{token}",
+            get(handlers::shared_reading).delete(handlers::revoke_share),
+        )
+        .route("/s/{token}", get(handlers::shared_reading_page))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_rest,
+        ));
+
+    // CORS covers the routes above it; browsers don't apply it to WebSocket
+    // upgrades, so `ws_upgrade` checks the origin itself
     let app = Router::new()
         .route("/health", get(|| async { "OK" }))
-        .route("/api/draw", post(handlers::draw_cards))
+        .merge(api)
+        .layer(cors)
         .route("/ws", get(ws_handler::ws_upgrade))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+        .with_state(state.clone());
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     tracing::info!("listening on {}", addr);
     
     let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutting down");
+        let _ = shutdown_tx.send(true);
+    })
+    .await?;
+
+    // Upgraded WebSocket connections and spawned interpretations outlive
+    // the HTTP server; give them a moment to close and save
+    drain(&state, Duration::from_secs(state.config.server.shutdown_grace_secs)).await;
 
     Ok(())
 }
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Wait for WebSocket connections to close and interpretations in progress
+/// to be saved, for at most `grace`.
+async fn drain(state: &AppState, grace: Duration) {
+    let deadline = Instant::now() + grace;
+    loop {
+        let connections = state.rate_limit.open_ws_connections();
+        let interpreting = state.sessions.interpreting();
+        if connections == 0 && interpreting == 0 {
+            tracing::info!("Shutdown complete");
+            return;
+        }
+        if Instant::now() >= deadline {
+            tracing::warn!(connections, interpreting, "Shutdown grace period over; exiting anyway");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
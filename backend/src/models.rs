@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+
+use crate::safety::SafetyCategory;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TarotCard {
@@ -13,6 +14,20 @@ pub struct TarotCard {
     pub situational_tags: Vec<String>,
 }
 
+/// Layout of `tarot_data.json`.
+#[derive(Debug, Deserialize)]
+pub struct DeckFile {
+    pub deck: DeckInfo,
+    pub cards: Vec<TarotCard>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeckInfo {
+    pub name: String,
+    pub version: String,
+    pub total_cards: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Keywords {
     pub upright: Vec<String>,
@@ -30,19 +45,219 @@ pub struct DrawnCard {
 pub struct DrawRequest {
     pub user_query: String, // "I am worried about my job"
     pub count: usize,       // Number of cards to draw (e.g. 3)
+    #[serde(default)]
+    pub bypass_cache: bool, // Force a fresh interpretation
+    #[serde(default)]
+    pub stream: bool, // Leave the interpretation for GET /api/readings/{id}/stream
 }
 
 #[derive(Debug, Serialize)]
 pub struct DrawResponse {
+    pub session_id: String, // Lets an anonymous client claim the reading on login
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_token: Option<String>, // Proves an anonymous client started the session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reading_id: Option<i64>, // Set once the reading is saved; used by the journal
     pub cards: Vec<DrawnCard>,
     pub interpretation_prompt: String, // The prompt sent to AI (for debugging/transparency)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_category: Option<SafetyCategory>, // Set when the query was screened as sensitive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_token: Option<String>, // Opens the interpretation stream of a `stream` draw
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
-    pub id: i64,
-    pub reading_id: i64,
     pub role: String,
     pub content: String,
     pub created_at: String,
 }
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct User {
+    pub id: i64,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignupRequest {
+    pub email: String,
+    pub password: String,
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub anonymous_sessions: Vec<SessionClaim>, // Sessions to move into the new account
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+    #[serde(default)]
+    pub anonymous_sessions: Vec<SessionClaim>,
+}
+
+/// An anonymous session and the claim token issued when it started.
+#[derive(Debug, Deserialize)]
+pub struct SessionClaim {
+    pub session_id: String,
+    pub claim_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClaimRequest {
+    pub anonymous_sessions: Vec<SessionClaim>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClaimResponse {
+    pub claimed_sessions: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OidcLinkResponse {
+    pub url: String, // Provider page to send the browser to
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthResponse {
+    pub token: String,
+    pub expires_at: i64, // Unix seconds
+    pub user: User,
+    pub claimed_sessions: u64, // Anonymous sessions moved into the account
+}
+
+/// Who wrote interpretation text: the model, a human reader, or a reader
+/// working from the model's draft.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Authorship {
+    Human,
+    Ai,
+    Both,
+}
+
+impl Authorship {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Authorship::Human => "human",
+            Authorship::Ai => "ai",
+            Authorship::Both => "both",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "human" => Some(Authorship::Human),
+            "ai" => Some(Authorship::Ai),
+            "both" => Some(Authorship::Both),
+            _ => None,
+        }
+    }
+}
+
+/// A past reading as shown in the journal.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadingSummary {
+    pub id: i64,
+    pub session_id: String,
+    pub user_query: String,
+    pub drawn_cards: serde_json::Value,
+    pub interpretation: Option<String>,
+    /// The interpretation was stopped and is only the part delivered.
+    pub interpretation_cancelled: bool,
+    /// Who wrote the interpretation, taken over all its parts.
+    pub authorship: Authorship,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JournalEntry {
+    pub id: i64,
+    pub reading_id: i64,
+    pub notes: Option<String>,
+    pub mood: Option<String>,
+    pub tags: Vec<String>,
+    pub outcome: Option<String>, // What actually happened, recorded later
+    pub outcome_recorded_at: Option<String>,
+    pub starred: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// A previous version of a journal entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalRevision {
+    pub id: i64,
+    pub notes: Option<String>,
+    pub mood: Option<String>,
+    pub tags: Vec<String>,
+    pub outcome: Option<String>,
+    pub starred: bool,
+    pub edited_at: Option<String>, // When this version was written
+    pub replaced_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JournalItem {
+    pub reading: ReadingSummary,
+    pub entry: Option<JournalEntry>, // None until the seeker writes about the reading
+}
+
+/// Partial update of a journal entry: absent fields keep their value and
+/// empty strings clear them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JournalUpdate {
+    pub notes: Option<String>,
+    pub mood: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub outcome: Option<String>,
+    pub starred: Option<bool>,
+}
+
+/// One card from one of a user's readings, oldest reading first.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CardDraw {
+    pub reading_id: i64,
+    pub created_at: String,
+    pub card_id: String,
+    pub reversed: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ReadingCard {
+    pub position: i64,
+    pub card_id: String,
+    pub reversed: bool,
+}
+
+/// A public, read-only link to one reading.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Share {
+    pub token: String,
+    pub reading_id: i64,
+    pub include_query: bool, // The question stays hidden unless the owner opted in
+    pub view_count: i64,
+    pub created_at: String,
+    pub revoked_at: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShareRequest {
+    #[serde(default)]
+    pub include_query: bool,
+}
+
+/// A stored card of the day.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DailyCardRecord {
+    pub day: String,
+    pub timezone: String,
+    pub card_id: String,
+    pub reversed: bool,
+    pub interpretation: String,
+    pub created_at: String,
+}
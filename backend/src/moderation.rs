@@ -0,0 +1,285 @@
+use thiserror::Error;
+
+use crate::ai_service::SYSTEM_PROMPT;
+use crate::models::DrawnCard;
+
+/// Longest interpretation we send or store, in characters.
+const MAX_INTERPRETATION_CHARS: usize = 4000;
+
+/// Headings and phrases that only appear when the model echoes its instructions.
+const LEAKAGE_MARKERS: &[&str] = &[
+    "## persona guidelines",
+    "## interaction flow",
+    "## input format",
+    "## safety guardrails",
+    "system prompt",
+    "you are a **mystical tarot master**",
+];
+
+/// Claims of certain doom that the persona's free-will rules forbid.
+const FATALISTIC_PHRASES: &[&str] = &[
+    "you will die",
+    "you're going to die",
+    "you are going to die",
+    "you will be dead",
+    "your death is certain",
+    "your death is near",
+    "you are doomed",
+    "you're doomed",
+    "nothing can save you",
+    "there is no hope for you",
+    "you will never recover",
+    "당신은 죽",
+    "죽게 될",
+];
+
+#[derive(Error, Debug)]
+pub enum ModerationError {
+    #[error("interpretation was empty after post-processing")]
+    Empty,
+    #[error("interpretation contains a fatalistic claim: {0:?}")]
+    Fatalistic(String),
+    #[error("interpretation describes cards that were not drawn: {0:?}")]
+    WrongCards(Vec<String>),
+}
+
+#[derive(Debug, Default)]
+pub struct ModerationReport {
+    pub leaked_lines: usize,
+    pub truncated: bool,
+    pub undrawn_cards: Vec<String>,
+}
+
+impl ModerationReport {
+    pub fn is_clean(&self) -> bool {
+        self.leaked_lines == 0 && !self.truncated && self.undrawn_cards.is_empty()
+    }
+}
+
+/// Run the post-processing pipeline over a raw model response.
+///
+/// `known_card_names` is every card name in the active deck; it is used to
+/// spot cards the text talks about that were never drawn.
+pub fn moderate(
+    raw: &str,
+    cards: &[DrawnCard],
+    known_card_names: &[String],
+) -> Result<(String, ModerationReport), ModerationError> {
+    let mut report = ModerationReport::default();
+
+    let (text, leaked_lines) = strip_prompt_leakage(raw);
+    report.leaked_lines = leaked_lines;
+
+    if text.trim().is_empty() {
+        return Err(ModerationError::Empty);
+    }
+
+    if let Some(phrase) = find_fatalistic_claim(&text) {
+        return Err(ModerationError::Fatalistic(phrase.to_string()));
+    }
+
+    let (text, truncated) = enforce_max_length(&text, MAX_INTERPRETATION_CHARS);
+    report.truncated = truncated;
+
+    let drawn_names: Vec<&str> = cards.iter().map(|c| c.card.name.as_str()).collect();
+    let mentioned: Vec<&String> = known_card_names
+        .iter()
+        .filter(|name| mentions(&text, name))
+        .collect();
+
+    report.undrawn_cards = mentioned
+        .iter()
+        .filter(|name| !drawn_names.contains(&name.as_str()))
+        .map(|name| name.to_string())
+        .collect();
+
+    // A stray mention is only flagged, but a text that names other cards and
+    // none of the drawn ones is a reading of the wrong spread.
+    let mentions_drawn = mentioned
+        .iter()
+        .any(|name| drawn_names.contains(&name.as_str()));
+    if !report.undrawn_cards.is_empty() && !mentions_drawn {
+        return Err(ModerationError::WrongCards(report.undrawn_cards));
+    }
+
+    Ok((text, report))
+}
+
+fn strip_prompt_leakage(raw: &str) -> (String, usize) {
+    // Any sufficiently long line of the system prompt showing up verbatim is leakage
+    let prompt_lines: Vec<String> = SYSTEM_PROMPT
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|line| line.len() >= 40)
+        .collect();
+
+    let mut leaked = 0;
+    let kept: Vec<&str> = raw
+        .lines()
+        .filter(|line| {
+            let lower = line.trim().to_lowercase();
+            let is_leak = LEAKAGE_MARKERS.iter().any(|m| lower.contains(m))
+                || prompt_lines.iter().any(|p| lower.contains(p.as_str()));
+            if is_leak {
+                leaked += 1;
+            }
+            !is_leak
+        })
+        .collect();
+
+    (kept.join("\n").trim().to_string(), leaked)
+}
+
+fn find_fatalistic_claim(text: &str) -> Option<&'static str> {
+    let lower = text.to_lowercase();
+    FATALISTIC_PHRASES
+        .iter()
+        .find(|phrase| lower.contains(*phrase))
+        .copied()
+}
+
+fn enforce_max_length(text: &str, max_chars: usize) -> (String, bool) {
+    if text.chars().count() <= max_chars {
+        return (text.to_string(), false);
+    }
+
+    let cut: String = text.chars().take(max_chars).collect();
+    // Prefer ending on a full sentence over a clipped one
+    let end = cut
+        .rfind(['.', '!', '?'])
+        .map(|i| i + 1)
+        .unwrap_or(cut.len());
+
+    (cut[..end].trim_end().to_string(), true)
+}
+
+/// Whole-word, case-sensitive match so "Death" the card doesn't match "deathly".
+fn mentions(text: &str, name: &str) -> bool {
+    text.match_indices(name).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + name.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric()) && !after.is_some_and(|c| c.is_alphanumeric())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Keywords, TarotCard};
+
+    fn drawn(name: &str) -> DrawnCard {
+        DrawnCard {
+            card: TarotCard {
+                id: name.to_lowercase().replace(' ', "_"),
+                name: name.to_string(),
+                arcana: "major".to_string(),
+                suit: None,
+                number: 0,
+                archetype: String::new(),
+                keywords: Keywords {
+                    upright: Vec::new(),
+                    reversed: Vec::new(),
+                },
+                situational_tags: Vec::new(),
+            },
+            is_reversed: false,
+            position_index: 0,
+        }
+    }
+
+    fn known() -> Vec<String> {
+        ["The Fool", "The Tower", "Death", "The Star"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    fn moderate_fool(raw: &str) -> Result<(String, ModerationReport), ModerationError> {
+        moderate(raw, &[drawn("The Fool")], &known())
+    }
+
+    #[test]
+    fn prompt_leakage_lines_are_stripped() {
+        let prompt_line = SYSTEM_PROMPT
+            .lines()
+            .map(str::trim)
+            .find(|line| line.len() >= 40)
+            .unwrap();
+        let raw = format!(
+            "## Persona Guidelines\nYou are a **Mystical Tarot Master**.\n{}\nThe Fool invites a fresh start.",
+            prompt_line
+        );
+
+        let (text, report) = moderate_fool(&raw).unwrap();
+        assert_eq!(text, "The Fool invites a fresh start.");
+        assert_eq!(report.leaked_lines, 3);
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn a_reply_that_is_all_leakage_is_empty() {
+        let raw = "## Persona Guidelines\nThis is my system prompt.";
+        assert!(matches!(moderate_fool(raw), Err(ModerationError::Empty)));
+    }
+
+    #[test]
+    fn fatalistic_claims_are_rejected() {
+        let cases = [
+            ("The Fool stumbles. You Will Die before spring.", "you will die"),
+            ("The Fool warns that you're doomed.", "you're doomed"),
+            ("The Fool: 당신은 죽을 운명입니다.", "당신은 죽"),
+        ];
+        for (raw, phrase) in cases {
+            match moderate_fool(raw) {
+                Err(ModerationError::Fatalistic(found)) => assert_eq!(found, phrase, "{:?}", raw),
+                other => panic!("{:?} was not rejected: {:?}", raw, other.map(|(text, _)| text)),
+            }
+        }
+    }
+
+    #[test]
+    fn long_replies_are_cut_at_a_sentence_end() {
+        let raw = "The Fool leaps into the unknown. ".repeat(200);
+        let (text, report) = moderate_fool(&raw).unwrap();
+
+        assert!(report.truncated);
+        assert!(text.chars().count() <= MAX_INTERPRETATION_CHARS);
+        assert!(text.ends_with("unknown."));
+
+        let short = "The Fool leaps. ".repeat(10);
+        let (_, report) = moderate_fool(&short).unwrap();
+        assert!(!report.truncated);
+    }
+
+    #[test]
+    fn long_replies_without_a_sentence_end_are_cut_at_the_limit() {
+        let raw = format!("The Fool {}", "ä".repeat(MAX_INTERPRETATION_CHARS));
+        let (text, report) = moderate_fool(&raw).unwrap();
+
+        assert!(report.truncated);
+        assert_eq!(text.chars().count(), MAX_INTERPRETATION_CHARS);
+    }
+
+    #[test]
+    fn a_reading_of_other_cards_is_rejected() {
+        match moderate_fool("The Tower and The Star speak of upheaval and hope.") {
+            Err(ModerationError::WrongCards(cards)) => assert_eq!(cards, vec!["The Tower", "The Star"]),
+            other => panic!("not rejected: {:?}", other.map(|(text, _)| text)),
+        }
+    }
+
+    #[test]
+    fn a_stray_undrawn_card_is_only_flagged() {
+        let (text, report) = moderate_fool("The Fool leaps where The Tower once stood.").unwrap();
+        assert_eq!(text, "The Fool leaps where The Tower once stood.");
+        assert_eq!(report.undrawn_cards, vec!["The Tower"]);
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn card_names_match_whole_words_only() {
+        let (_, report) = moderate_fool("The Fool walks a deathly quiet road to the Starlight inn.").unwrap();
+        assert!(report.undrawn_cards.is_empty());
+        assert!(report.is_clean());
+    }
+}
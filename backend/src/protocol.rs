@@ -0,0 +1,131 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::error::{AppError, ErrorCode};
+
+/// The WebSocket protocol version this server speaks.
+///
+/// 1. The original protocol, assumed for clients that never send `hello`.
+/// 2. `hello` negotiation, capabilities and request ids.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// The oldest version a client may still negotiate.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// What clients that never send `hello` are treated as.
+const LEGACY_VERSION: u32 = 1;
+
+/// Optional protocol features a client can ask for in `hello`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Interpretations arrive as a series of chunks rather than one message.
+    Streaming,
+    /// A structured `interpretation_result` follows each interpretation.
+    StructuredOutput,
+    /// `start_session` may choose a spread size other than the default.
+    Spreads,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [
+        Capability::Streaming,
+        Capability::StructuredOutput,
+        Capability::Spreads,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Capability::Streaming => "streaming",
+            Capability::StructuredOutput => "structured_output",
+            Capability::Spreads => "spreads",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == name)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    #[error("protocol version {0} is not supported; this server speaks {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("hello was already sent on this connection")]
+    AlreadyNegotiated,
+    #[error("{} requires the {} capability", .feature, .capability.as_str())]
+    CapabilityRequired {
+        feature: &'static str,
+        capability: Capability,
+    },
+}
+
+impl From<ProtocolError> for AppError {
+    fn from(error: ProtocolError) -> Self {
+        let code = match error {
+            ProtocolError::UnsupportedVersion(_) => ErrorCode::UnsupportedVersion,
+            ProtocolError::AlreadyNegotiated => ErrorCode::InvalidState,
+            ProtocolError::CapabilityRequired { .. } => ErrorCode::InvalidRequest,
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+/// What a connection agreed on. Belongs to the connection, not the session,
+/// so a resumed session follows whatever its new connection negotiated.
+#[derive(Debug, Clone)]
+pub struct Protocol {
+    pub version: u32,
+    capabilities: Vec<Capability>,
+    negotiated: bool,
+}
+
+impl Default for Protocol {
+    /// Clients written before `hello` existed get everything they used to.
+    fn default() -> Self {
+        Self {
+            version: LEGACY_VERSION,
+            capabilities: vec![Capability::Streaming, Capability::Spreads],
+            negotiated: false,
+        }
+    }
+}
+
+impl Protocol {
+    /// Settle on the highest version both sides speak and the capabilities
+    /// both know. Names this server doesn't recognise are ignored.
+    pub fn negotiate(&mut self, client_version: u32, requested: &[String]) -> Result<(), ProtocolError> {
+        if self.negotiated {
+            return Err(ProtocolError::AlreadyNegotiated);
+        }
+        if client_version < MIN_PROTOCOL_VERSION {
+            return Err(ProtocolError::UnsupportedVersion(client_version));
+        }
+
+        let mut capabilities: Vec<Capability> =
+            requested.iter().filter_map(|name| Capability::parse(name)).collect();
+        capabilities.sort_by_key(|c| *c as u8);
+        capabilities.dedup();
+
+        *self = Self {
+            version: client_version.min(PROTOCOL_VERSION),
+            capabilities,
+            negotiated: true,
+        };
+        Ok(())
+    }
+
+    pub fn capabilities(&self) -> &[Capability] {
+        &self.capabilities
+    }
+
+    pub fn has(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    pub fn require(&self, capability: Capability, feature: &'static str) -> Result<(), ProtocolError> {
+        if self.has(capability) {
+            Ok(())
+        } else {
+            Err(ProtocolError::CapabilityRequired { feature, capability })
+        }
+    }
+}
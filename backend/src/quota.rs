@@ -0,0 +1,294 @@
+use axum::{
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{AppError, ErrorCode};
+use crate::rate_limit::{self, SESSION_HEADER};
+
+const HOUR_SECS: u64 = 60 * 60;
+const DAY_SECS: u64 = 24 * HOUR_SECS;
+
+/// Limits on AI usage. A limit of 0 means unlimited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaConfig {
+    pub readings_per_hour_per_session: u64,
+    pub readings_per_hour_per_ip: u64,
+    pub readings_per_hour_global: u64,
+    pub tokens_per_day_per_session: u64,
+    pub tokens_per_day_per_ip: u64,
+    pub tokens_per_day_global: u64,
+    pub follow_ups_per_reading: u64,
+    /// Take the client IP from `X-Forwarded-For` (only behind a trusted proxy).
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            readings_per_hour_per_session: 10,
+            readings_per_hour_per_ip: 30,
+            readings_per_hour_global: 1000,
+            tokens_per_day_per_session: 50_000,
+            tokens_per_day_per_ip: 150_000,
+            tokens_per_day_global: 5_000_000,
+            follow_ups_per_reading: 5,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuotaExceeded {
+    pub quota: &'static str,
+    pub scope: &'static str,
+    /// Unix seconds at which the window resets; `None` if it never does.
+    pub reset_at: Option<u64>,
+    pub retry_after_secs: Option<u64>,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} quota exceeded for this {}", self.quota, self.scope)?;
+        if let Some(secs) = self.retry_after_secs {
+            write!(f, "; it resets in {} seconds", secs)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+impl From<QuotaExceeded> for AppError {
+    fn from(exceeded: QuotaExceeded) -> Self {
+        let mut error = AppError::new(ErrorCode::QuotaExceeded, exceeded.to_string())
+            .with_detail("quota", exceeded.quota)
+            .with_detail("scope", exceeded.scope);
+        // A quota that never resets won't let a retry through
+        error.retryable = exceeded.reset_at.is_some();
+        error.reset_at = exceeded.reset_at;
+        error.retry_after_secs = exceeded.retry_after_secs;
+        error
+    }
+}
+
+impl IntoResponse for QuotaExceeded {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Scope {
+    Session(String),
+    Ip(IpAddr),
+    Global,
+}
+
+impl Scope {
+    fn label(&self) -> &'static str {
+        match self {
+            Scope::Session(_) => "session",
+            Scope::Ip(_) => "ip",
+            Scope::Global => "global",
+        }
+    }
+}
+
+/// A counter over a fixed window aligned to the epoch (hour or UTC day).
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: u64,
+    used: u64,
+}
+
+impl Window {
+    fn current(&mut self, now: u64, length: u64) -> &mut Self {
+        let start = now - now % length;
+        if self.start != start {
+            self.start = start;
+            self.used = 0;
+        }
+        self
+    }
+}
+
+#[derive(Default)]
+struct QuotaState {
+    readings: HashMap<Scope, Window>,
+    tokens: HashMap<Scope, Window>,
+}
+
+impl QuotaState {
+    /// Drop counters whose window has passed so per-session keys don't pile up.
+    fn prune(&mut self, now: u64) {
+        self.readings.retain(|_, w| w.start + HOUR_SECS > now);
+        self.tokens.retain(|_, w| w.start + DAY_SECS > now);
+    }
+}
+
+/// In-memory quota accounting for readings and tokens, and the limit on
+/// follow-ups, which are counted with the reading they belong to.
+pub struct QuotaManager {
+    config: QuotaConfig,
+    state: Mutex<QuotaState>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Who a REST request's session quota belongs to: the account when signed
+/// in, else the client's session header. Without either, requests share no
+/// session key and only the IP and global limits apply.
+pub fn rest_session_key(user_id: Option<i64>, headers: &HeaderMap) -> Option<String> {
+    if let Some(user_id) = user_id {
+        return Some(format!("user:{}", user_id));
+    }
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| rate_limit::is_valid_session_id(id))
+        .map(|id| format!("session:{}", id))
+}
+
+fn scopes(session_id: &str, ip: Option<IpAddr>) -> Vec<Scope> {
+    let mut scopes = vec![Scope::Session(session_id.to_string())];
+    if let Some(ip) = ip {
+        scopes.push(Scope::Ip(ip));
+    }
+    scopes.push(Scope::Global);
+    scopes
+}
+
+impl QuotaManager {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(QuotaState::default()),
+        }
+    }
+
+    fn reading_limit(&self, scope: &Scope) -> u64 {
+        match scope {
+            Scope::Session(_) => self.config.readings_per_hour_per_session,
+            Scope::Ip(_) => self.config.readings_per_hour_per_ip,
+            Scope::Global => self.config.readings_per_hour_global,
+        }
+    }
+
+    fn token_limit(&self, scope: &Scope) -> u64 {
+        match scope {
+            Scope::Session(_) => self.config.tokens_per_day_per_session,
+            Scope::Ip(_) => self.config.tokens_per_day_per_ip,
+            Scope::Global => self.config.tokens_per_day_global,
+        }
+    }
+
+    /// Count a new reading against the hourly limits, or refuse it if any
+    /// scope is already at its limit. Nothing is counted on refusal.
+    pub fn try_start_reading(&self, session_id: &str, ip: Option<IpAddr>) -> Result<(), QuotaExceeded> {
+        let now = now_secs();
+        let scopes = scopes(session_id, ip);
+        let mut state = self.state.lock().unwrap();
+        state.prune(now);
+
+        for scope in &scopes {
+            let limit = self.reading_limit(scope);
+            let window = state
+                .readings
+                .entry(scope.clone())
+                .or_insert(Window { start: 0, used: 0 })
+                .current(now, HOUR_SECS);
+            if limit > 0 && window.used >= limit {
+                let reset_at = window.start + HOUR_SECS;
+                return Err(QuotaExceeded {
+                    quota: "readings_per_hour",
+                    scope: scope.label(),
+                    reset_at: Some(reset_at),
+                    retry_after_secs: Some(reset_at.saturating_sub(now)),
+                });
+            }
+        }
+
+        for scope in scopes {
+            if let Some(window) = state.readings.get_mut(&scope) {
+                window.used += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether every scope still has daily token budget left. Past the
+    /// budget, callers degrade to the offline interpreter.
+    pub fn has_token_budget(&self, session_id: &str, ip: Option<IpAddr>) -> bool {
+        let now = now_secs();
+        let mut state = self.state.lock().unwrap();
+
+        scopes(session_id, ip).into_iter().all(|scope| {
+            let limit = self.token_limit(&scope);
+            let window = state
+                .tokens
+                .entry(scope)
+                .or_insert(Window { start: 0, used: 0 })
+                .current(now, DAY_SECS);
+            limit == 0 || window.used < limit
+        })
+    }
+
+    pub fn record_tokens(&self, session_id: &str, ip: Option<IpAddr>, tokens: u64) {
+        let now = now_secs();
+        let mut state = self.state.lock().unwrap();
+
+        for scope in scopes(session_id, ip) {
+            state
+                .tokens
+                .entry(scope)
+                .or_insert(Window { start: 0, used: 0 })
+                .current(now, DAY_SECS)
+                .used += tokens;
+        }
+    }
+
+    /// Allow one more follow-up interpretation of a reading that already
+    /// has `used` of them. The allowance is for the reading's whole life,
+    /// so it never resets.
+    pub fn check_follow_up(&self, used: u64) -> Result<(), QuotaExceeded> {
+        let limit = self.config.follow_ups_per_reading;
+        if limit > 0 && used >= limit {
+            return Err(QuotaExceeded {
+                quota: "follow_ups_per_reading",
+                scope: "reading",
+                reset_at: None,
+                retry_after_secs: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// The client address used as the IP quota key.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        if self.config.trust_forwarded_for {
+            let forwarded = headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|v| v.trim().parse().ok());
+            if let Some(ip) = forwarded {
+                return ip;
+            }
+        }
+        peer.ip()
+    }
+}
@@ -0,0 +1,386 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{AppError, ErrorCode};
+use crate::state::AppState;
+
+/// Header a REST client can send to be rate limited per session as well as per IP.
+pub const SESSION_HEADER: &str = "x-session-id";
+/// Longest session id accepted in `SESSION_HEADER`.
+const MAX_SESSION_ID_CHARS: usize = 64;
+
+/// Whether a client-chosen session id is fit to key quotas and daily cards by.
+pub fn is_valid_session_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_SESSION_ID_CHARS
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Above this many tracked buckets, full (idle) ones are dropped.
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// Request-rate limits. A rate of 0 disables that limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub rest_requests_per_minute: u32,
+    pub rest_burst: u32,
+    pub ws_connections_per_ip: usize,
+    pub ws_messages_per_second: u32,
+    pub ws_message_burst: u32,
+    pub ws_max_frame_bytes: usize,
+    pub ws_max_message_bytes: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            rest_requests_per_minute: 60,
+            rest_burst: 20,
+            ws_connections_per_ip: 5,
+            ws_messages_per_second: 5,
+            ws_message_burst: 20,
+            ws_max_frame_bytes: 16 * 1024,
+            ws_max_message_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// A classic token bucket: holds up to `capacity` tokens and refills
+/// continuously at `refill_per_sec`.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, refill_per_sec: f64) -> Self {
+        // A bucket must hold at least one token or it could never be drawn from
+        let capacity = capacity.max(1) as f64;
+        Self {
+            capacity,
+            refill_per_sec,
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.capacity
+    }
+
+    /// How long until one token is available; zero if one is available now.
+    fn wait_time(&self) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec)
+        }
+    }
+
+    /// Take one token, or return how long to wait for the next one.
+    pub fn try_take(&mut self) -> Result<(), Duration> {
+        self.take(Instant::now())
+    }
+
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        match self.wait_time() {
+            Duration::ZERO => {
+                self.tokens -= 1.0;
+                Ok(())
+            }
+            wait => Err(wait),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimited {
+    pub scope: &'static str,
+    pub retry_after_secs: u64,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "too many requests for this {}; retry in {} seconds",
+            self.scope, self.retry_after_secs
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl From<RateLimited> for AppError {
+    fn from(limited: RateLimited) -> Self {
+        let mut error =
+            AppError::new(ErrorCode::RateLimited, limited.to_string()).with_detail("scope", limited.scope);
+        error.retry_after_secs = Some(limited.retry_after_secs);
+        error
+    }
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum BucketKey {
+    Ip(IpAddr),
+    Session(String),
+}
+
+impl BucketKey {
+    fn label(&self) -> &'static str {
+        match self {
+            BucketKey::Ip(_) => "ip",
+            BucketKey::Session(_) => "session",
+        }
+    }
+}
+
+type ConnectionCounts = Arc<Mutex<HashMap<IpAddr, usize>>>;
+
+/// Holds one of an IP's WebSocket connection slots until dropped.
+pub struct WsConnectionGuard {
+    ip: IpAddr,
+    connections: ConnectionCounts,
+}
+
+impl Drop for WsConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(count) = connections.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// In-memory request throttling for REST routes and WebSocket connections.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    rest_buckets: Mutex<HashMap<BucketKey, TokenBucket>>,
+    ws_connections: ConnectionCounts,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            rest_buckets: Mutex::new(HashMap::new()),
+            ws_connections: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Spend one request from the IP bucket and, when known, the session
+    /// bucket. Nothing is spent unless every bucket has a token.
+    pub fn check_rest(&self, ip: IpAddr, session_id: Option<&str>) -> Result<(), RateLimited> {
+        let per_minute = self.config.rest_requests_per_minute;
+        if per_minute == 0 {
+            return Ok(());
+        }
+
+        let mut keys = vec![BucketKey::Ip(ip)];
+        if let Some(session_id) = session_id {
+            keys.push(BucketKey::Session(session_id.to_string()));
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.rest_buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                !bucket.is_full()
+            });
+        }
+
+        for key in &keys {
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| TokenBucket::new(self.config.rest_burst, per_minute as f64 / 60.0));
+            bucket.refill(now);
+            let wait = bucket.wait_time();
+            if !wait.is_zero() {
+                return Err(RateLimited {
+                    scope: key.label(),
+                    retry_after_secs: wait.as_secs_f64().ceil() as u64,
+                });
+            }
+        }
+
+        for key in &keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// WebSocket connections open right now, over all IPs.
+    pub fn open_ws_connections(&self) -> usize {
+        self.ws_connections.lock().unwrap().values().sum()
+    }
+
+    /// Claim a WebSocket connection slot for `ip`, or `None` if it already
+    /// has as many open connections as allowed.
+    pub fn acquire_ws(&self, ip: IpAddr) -> Option<WsConnectionGuard> {
+        let limit = self.config.ws_connections_per_ip;
+        let mut connections = self.ws_connections.lock().unwrap();
+        let count = connections.entry(ip).or_insert(0);
+        if limit > 0 && *count >= limit {
+            return None;
+        }
+
+        *count += 1;
+        Some(WsConnectionGuard {
+            ip,
+            connections: Arc::clone(&self.ws_connections),
+        })
+    }
+
+    /// A fresh per-connection message bucket, or `None` when unthrottled.
+    pub fn ws_message_bucket(&self) -> Option<TokenBucket> {
+        match self.config.ws_messages_per_second {
+            0 => None,
+            rate => Some(TokenBucket::new(self.config.ws_message_burst, rate as f64)),
+        }
+    }
+}
+
+/// Middleware applying the REST token buckets before the handler runs.
+pub async fn limit_rest(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = state.quota.client_ip(request.headers(), peer);
+    let session_id = request
+        .headers()
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok());
+
+    if let Err(e) = state.rate_limit.check_rest(ip, session_id) {
+        tracing::warn!(ip = %ip, path = %request.uri().path(), scope = e.scope, "Rate limited REST request");
+        return e.into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn drained(capacity: u32, refill_per_sec: f64) -> (TokenBucket, Instant) {
+        let mut bucket = TokenBucket::new(capacity, refill_per_sec);
+        let start = bucket.updated;
+        for _ in 0..capacity {
+            bucket.take(start).unwrap();
+        }
+        (bucket, start)
+    }
+
+    #[test]
+    fn bucket_allows_a_burst_then_waits() {
+        let (mut bucket, start) = drained(3, 2.0);
+        assert_eq!(bucket.take(start), Err(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn bucket_refills_continuously() {
+        let (mut bucket, start) = drained(3, 2.0);
+
+        assert_eq!(bucket.take(start + Duration::from_millis(200)), Err(Duration::from_millis(300)));
+        assert_eq!(bucket.take(start + Duration::from_millis(500)), Ok(()));
+        assert!(bucket.take(start + Duration::from_millis(500)).is_err());
+        assert_eq!(bucket.take(start + Duration::from_millis(1000)), Ok(()));
+    }
+
+    #[test]
+    fn bucket_never_holds_more_than_its_burst() {
+        let (mut bucket, start) = drained(3, 2.0);
+        let later = start + Duration::from_secs(3600);
+
+        for _ in 0..3 {
+            assert_eq!(bucket.take(later), Ok(()));
+        }
+        assert!(bucket.take(later).is_err());
+        assert!(!bucket.is_full());
+    }
+
+    #[test]
+    fn bucket_holds_at_least_one_token() {
+        let mut bucket = TokenBucket::new(0, 1.0);
+        let start = bucket.updated;
+        assert_eq!(bucket.take(start), Ok(()));
+        assert_eq!(bucket.take(start), Err(Duration::from_secs(1)));
+    }
+
+    fn limiter(rest_requests_per_minute: u32, rest_burst: u32) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            rest_requests_per_minute,
+            rest_burst,
+            ..RateLimitConfig::default()
+        })
+    }
+
+    #[test]
+    fn rest_requests_are_limited_per_ip_and_per_session() {
+        let limiter = limiter(60, 2);
+        let (first, second) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+
+        assert!(limiter.check_rest(first, Some("abc")).is_ok());
+        assert!(limiter.check_rest(first, Some("abc")).is_ok());
+        let limited = limiter.check_rest(first, Some("abc")).unwrap_err();
+        assert_eq!(limited.scope, "ip");
+        assert_eq!(limited.retry_after_secs, 1);
+
+        // The session is spent wherever it comes from
+        assert_eq!(limiter.check_rest(second, Some("abc")).unwrap_err().scope, "session");
+
+        // ...and that refusal cost the second IP nothing
+        assert!(limiter.check_rest(second, None).is_ok());
+        assert!(limiter.check_rest(second, None).is_ok());
+        assert_eq!(limiter.check_rest(second, None).unwrap_err().scope, "ip");
+    }
+
+    #[test]
+    fn a_zero_rate_disables_rest_limits() {
+        let limiter = limiter(0, 1);
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        for _ in 0..10 {
+            assert!(limiter.check_rest(ip, Some("abc")).is_ok());
+        }
+    }
+}
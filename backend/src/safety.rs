@@ -0,0 +1,416 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::db;
+use crate::state::AppState;
+
+/// Sensitive topics we screen for before a reading is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafetyCategory {
+    Crisis,
+    Medical,
+    Legal,
+    Financial,
+}
+
+impl SafetyCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SafetyCategory::Crisis => "crisis",
+            SafetyCategory::Medical => "medical",
+            SafetyCategory::Legal => "legal",
+            SafetyCategory::Financial => "financial",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "crisis" => Some(SafetyCategory::Crisis),
+            "medical" => Some(SafetyCategory::Medical),
+            "legal" => Some(SafetyCategory::Legal),
+            "financial" => Some(SafetyCategory::Financial),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecisionSource {
+    Rules,
+    Model,
+}
+
+impl DecisionSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DecisionSource::Rules => "rules",
+            DecisionSource::Model => "model",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SafetyAction {
+    /// Nothing sensitive detected; read as usual.
+    Allow,
+    /// Read as usual, but add these instructions to the system prompt.
+    Guardrail(String),
+    /// Skip the reading and answer with this supportive message instead.
+    Respond(String),
+}
+
+impl SafetyAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SafetyAction::Allow => "allow",
+            SafetyAction::Guardrail(_) => "guardrail",
+            SafetyAction::Respond(_) => "respond",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SafetyDecision {
+    pub category: Option<SafetyCategory>,
+    pub action: SafetyAction,
+    pub source: DecisionSource,
+    pub matched_terms: Vec<String>,
+}
+
+impl SafetyDecision {
+    pub fn guardrails(&self) -> Option<String> {
+        match &self.action {
+            SafetyAction::Guardrail(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn response(&self) -> Option<&str> {
+        match &self.action {
+            SafetyAction::Respond(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+// Checked in order: the first category with a match wins, so crisis terms
+// always take precedence over the softer categories.
+//
+// Terms match whole words; a trailing `*` lets the last word run on
+// ("diagnos*" covers "diagnosis"). Korean terms are all stems, since
+// particles attach to the word.
+const RULES: &[(SafetyCategory, &[&str])] = &[
+    (
+        SafetyCategory::Crisis,
+        &[
+            "suicide",
+            "suicidal",
+            "kill myself",
+            "end my life",
+            "want to die",
+            "self harm*",
+            "hurt myself",
+            "cut myself",
+            "no reason to live",
+            "자살*",
+            "죽고 싶*",
+            "자해*",
+            "목숨을 끊*",
+            "살기 싫*",
+        ],
+    ),
+    (
+        SafetyCategory::Medical,
+        &[
+            "diagnos*",
+            "cancer",
+            "tumor*",
+            "symptom*",
+            "disease*",
+            "pregnan*",
+            "medication*",
+            "surgery",
+            "surgeries",
+            "my doctor",
+            "진단*",
+            "증상*",
+            "질병*",
+            "수술*",
+            "임신*",
+            "복용*",
+        ],
+    ),
+    (
+        SafetyCategory::Legal,
+        &[
+            "lawsuit*",
+            "sue",
+            "suing",
+            "court",
+            "courts",
+            "lawyer*",
+            "arrest*",
+            "custody",
+            "divorce settlement*",
+            "criminal charge*",
+            "소송*",
+            "고소*",
+            "변호사*",
+            "재판*",
+            "체포*",
+            "양육권*",
+        ],
+    ),
+    (
+        SafetyCategory::Financial,
+        &[
+            "invest",
+            "invests",
+            "invested",
+            "investing",
+            "investment*",
+            "investor*",
+            "stock",
+            "stocks",
+            "stock market",
+            "crypto*",
+            "bitcoin*",
+            "bankrupt*",
+            "loan*",
+            "debt*",
+            "mortgage*",
+            "투자*",
+            "주식*",
+            "코인*",
+            "대출*",
+            "빚*",
+            "파산*",
+        ],
+    ),
+];
+
+/// Phrases that use a term in its harmless sense, blanked out before
+/// matching: "Cancer" is also a star sign. Only wordings that can't be
+/// about the illness belong here; "is this a cancer" must still match.
+const BENIGN_PHRASES: &[&str] = &[
+    "i m a cancer",
+    "im a cancer",
+    "am a cancer",
+    "he s a cancer",
+    "she s a cancer",
+    "cancer sun",
+    "cancer moon",
+    "cancer rising",
+    "cancer season",
+    "cancer man",
+    "cancer woman",
+];
+
+/// Lowercase words separated by single spaces, with a space at each end so
+/// every word is bounded on both sides.
+fn normalize(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    format!(" {} ", words.join(" "))
+}
+
+/// Whether `term` (see `RULES`) occurs in `text` from `normalize`.
+fn matches_term(text: &str, term: &str) -> bool {
+    match term.strip_suffix('*') {
+        Some(stem) => text.contains(&format!(" {}", stem)),
+        None => text.contains(&format!(" {} ", term)),
+    }
+}
+
+const CRISIS_RESPONSE: &str = "Seeker, the cards can wait. What you are carrying sounds heavy, and you deserve the support of a real person right now. If you are in immediate danger, please call your local emergency number. In Korea you can reach the suicide prevention line at 109 (24 hours), in the US you can call or text 988, and elsewhere you can find a free, confidential helpline at https://findahelpline.com. You do not have to face this alone.";
+
+const MEDICAL_GUARDRAIL: &str = "The seeker's question touches on health or medical matters. Do not diagnose, predict the course of an illness, or suggest starting or stopping any treatment. Keep the reading on emotional and spiritual themes, and gently encourage them to consult a qualified medical professional.";
+
+const LEGAL_GUARDRAIL: &str = "The seeker's question touches on legal matters. Do not predict the outcome of any case or give legal advice. Keep the reading on emotional themes and decision-making, and gently encourage them to consult a qualified lawyer.";
+
+const FINANCIAL_GUARDRAIL: &str = "The seeker's question touches on money or investments. Do not recommend buying, selling or borrowing, and never predict prices or returns. Keep the reading on their relationship with security and risk, and gently encourage them to consult a qualified financial advisor.";
+
+fn action_for(category: SafetyCategory) -> SafetyAction {
+    match category {
+        SafetyCategory::Crisis => SafetyAction::Respond(CRISIS_RESPONSE.to_string()),
+        SafetyCategory::Medical => SafetyAction::Guardrail(MEDICAL_GUARDRAIL.to_string()),
+        SafetyCategory::Legal => SafetyAction::Guardrail(LEGAL_GUARDRAIL.to_string()),
+        SafetyCategory::Financial => SafetyAction::Guardrail(FINANCIAL_GUARDRAIL.to_string()),
+    }
+}
+
+/// Classify a query with the local rule set only.
+pub fn screen_with_rules(query: &str) -> SafetyDecision {
+    let mut text = normalize(query);
+    for phrase in BENIGN_PHRASES {
+        text = text.replace(&format!(" {} ", phrase), " ");
+    }
+
+    for (category, terms) in RULES {
+        let matched: Vec<String> = terms
+            .iter()
+            .filter(|term| matches_term(&text, term))
+            .map(|term| term.trim_end_matches('*').to_string())
+            .collect();
+
+        if !matched.is_empty() {
+            return SafetyDecision {
+                category: Some(*category),
+                action: action_for(*category),
+                source: DecisionSource::Rules,
+                matched_terms: matched,
+            };
+        }
+    }
+
+    SafetyDecision {
+        category: None,
+        action: SafetyAction::Allow,
+        source: DecisionSource::Rules,
+        matched_terms: Vec::new(),
+    }
+}
+
+/// Screen a query before drawing: rules first, then the optional model
+/// check when the rules find nothing. The model check spends the token
+/// budget of `quota_key` like any other call, and is skipped once it is
+/// gone. The decision is logged and recorded.
+pub async fn screen_query(
+    state: &AppState,
+    session_id: &str,
+    quota_key: &str,
+    client_ip: IpAddr,
+    query: &str,
+) -> SafetyDecision {
+    let mut decision = screen_with_rules(query);
+
+    let model_check = decision.category.is_none()
+        && state.config.ai.safety_model_check
+        && state.quota.has_token_budget(quota_key, Some(client_ip));
+    if model_check {
+        match state.ai.classify_query(query).await {
+            Ok((category, usage)) => {
+                let tokens = (usage.prompt_tokens + usage.completion_tokens) as u64;
+                state.quota.record_tokens(quota_key, Some(client_ip), tokens);
+                if let Err(e) = db::save_llm_usage(&state.db, session_id, None, None, &usage).await {
+                    tracing::error!(session_id = %session_id, "Failed to record LLM usage: {}", e);
+                }
+                if let Some(category) = category {
+                    decision = SafetyDecision {
+                        category: Some(category),
+                        action: action_for(category),
+                        source: DecisionSource::Model,
+                        matched_terms: Vec::new(),
+                    };
+                }
+            }
+            Err(e) => {
+                tracing::warn!(session_id = %session_id, "Safety model check failed: {}", e);
+            }
+        }
+    }
+
+    let category = decision.category.map(|c| c.as_str()).unwrap_or("none");
+    tracing::info!(
+        session_id = %session_id,
+        category = category,
+        action = decision.action.as_str(),
+        source = decision.source.as_str(),
+        "Safety screening decision"
+    );
+
+    let matched_json = serde_json::to_value(&decision.matched_terms).unwrap_or_default();
+    if let Err(e) = db::save_safety_decision(
+        &state.db,
+        session_id,
+        category,
+        decision.action.as_str(),
+        decision.source.as_str(),
+        &matched_json,
+    )
+    .await
+    {
+        tracing::error!(session_id = %session_id, "Failed to record safety decision: {}", e);
+    }
+
+    decision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(query: &str) -> Option<SafetyCategory> {
+        screen_with_rules(query).category
+    }
+
+    #[test]
+    fn sensitive_queries_are_screened() {
+        let cases = [
+            ("I want to end my life", SafetyCategory::Crisis),
+            ("요즘 죽고 싶어요", SafetyCategory::Crisis),
+            ("Is thinking about self-harm normal?", SafetyCategory::Crisis),
+            ("Will my diagnosis be good news?", SafetyCategory::Medical),
+            ("Does my mother have cancer?", SafetyCategory::Medical),
+            ("수술이 잘 될까요?", SafetyCategory::Medical),
+            ("Will I win in court?", SafetyCategory::Legal),
+            ("Should I sue my landlord?", SafetyCategory::Legal),
+            ("Should I invest in crypto?", SafetyCategory::Financial),
+            ("Will my stocks go up?", SafetyCategory::Financial),
+            ("주식을 사야 할까요?", SafetyCategory::Financial),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(category(query), Some(expected), "{:?}", query);
+        }
+    }
+
+    #[test]
+    fn terms_inside_other_words_are_not_matched() {
+        let queries = [
+            "Should I investigate what my partner is hiding?",
+            "Is this courtship going anywhere?",
+            "Will the stockings I knitted sell at the fair?",
+            "Is my friend pursuing me or just being kind?",
+            "Should I take the sunny road trip with my brother?",
+        ];
+        for query in queries {
+            assert_eq!(category(query), None, "{:?}", query);
+        }
+    }
+
+    #[test]
+    fn the_star_sign_is_not_a_diagnosis() {
+        let queries = [
+            "I'm a Cancer, will I find love this year?",
+            "What does Cancer season hold for my career?",
+            "Is a Cancer man right for me?",
+        ];
+        for query in queries {
+            assert_eq!(category(query), None, "{:?}", query);
+        }
+    }
+
+    #[test]
+    fn the_illness_is_not_mistaken_for_the_star_sign() {
+        let queries = [
+            "Do I have a cancer?",
+            "do I have a cancer",
+            "Is this lump a cancer?",
+            "Could it be a cancer my doctor missed?",
+        ];
+        for query in queries {
+            assert_eq!(category(query), Some(SafetyCategory::Medical), "{:?}", query);
+        }
+    }
+
+    #[test]
+    fn matched_terms_drop_the_stem_marker() {
+        let decision = screen_with_rules("My lawyer says the lawsuit is weak");
+        assert_eq!(decision.category, Some(SafetyCategory::Legal));
+        assert_eq!(decision.matched_terms, vec!["lawsuit", "lawyer"]);
+    }
+}
@@ -0,0 +1,1077 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{mpsc, Notify};
+use uuid::Uuid;
+
+use crate::ai_service::InjectionReport;
+use crate::auth;
+use crate::error::{AppError, ErrorCode};
+use crate::layout::DeckLayout;
+use crate::models::{Authorship, DrawnCard};
+use crate::protocol::Capability;
+use crate::usage::LlmUsage;
+use crate::ws_handler::ServerEnvelope;
+
+pub const DEFAULT_SPREAD_SIZE: usize = 3;
+pub const MAX_SPREAD_SIZE: usize = 10;
+
+/// How long a dropped connection's session is kept for resuming, and how
+/// many connections may share one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    pub resume_ttl_secs: u64,
+    /// Above this many detached sessions the oldest are dropped early.
+    pub max_detached: usize,
+    /// Connections in one room, the seeker included.
+    pub max_participants: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            resume_ttl_secs: 300,
+            max_detached: 1000,
+            max_participants: 8,
+        }
+    }
+}
+
+/// Where a reading session is. Sent to the client after every transition.
+///
+/// ```text
+/// Idle → Shuffled → Selecting(n of spread size) → Interpreting → FollowUp → Closed
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum SessionPhase {
+    /// No question asked yet.
+    Idle,
+    /// The deck order is committed; nothing selected.
+    Shuffled,
+    Selecting { selected: usize, spread_size: usize },
+    Interpreting,
+    /// The reading is done; it can be interpreted again or a new one started.
+    FollowUp,
+    Closed,
+}
+
+impl SessionPhase {
+    fn name(self) -> &'static str {
+        match self {
+            SessionPhase::Idle => "idle",
+            SessionPhase::Shuffled => "shuffled",
+            SessionPhase::Selecting { .. } => "selecting",
+            SessionPhase::Interpreting => "interpreting",
+            SessionPhase::FollowUp => "follow_up",
+            SessionPhase::Closed => "closed",
+        }
+    }
+}
+
+/// Client requests that move the session between phases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    StartSession,
+    Shuffle,
+    SelectCard,
+    RequestInterpretation,
+    EndSession,
+    ResumeSession,
+    CancelInterpretation,
+    CreateInvite,
+    JoinRoom,
+    /// Send part of an interpretation a reader is writing, or finish it.
+    WriteInterpretation,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::StartSession => "start_session",
+            Action::Shuffle => "shuffle",
+            Action::SelectCard => "select_card",
+            Action::RequestInterpretation => "request_interpretation",
+            Action::EndSession => "end_session",
+            Action::ResumeSession => "resume_session",
+            Action::CancelInterpretation => "cancel_interpretation",
+            Action::CreateInvite => "create_invite",
+            Action::JoinRoom => "join_room",
+            Action::WriteInterpretation => "write_interpretation",
+        }
+    }
+}
+
+/// What a connection is to the session it is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Asked the question; owns the session and may do anything.
+    Seeker,
+    /// Runs the reading alongside the seeker.
+    Reader,
+    /// Watches only.
+    Observer,
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Role::Seeker => "seeker",
+            Role::Reader => "reader",
+            Role::Observer => "observer",
+        }
+    }
+
+    /// Whether this role may ask for `action`. The seeker's question and
+    /// cards stay the seeker's; a reader handles the deck and the reading,
+    /// and only a reader writes one.
+    pub fn may(self, action: Action) -> bool {
+        match self {
+            Role::Seeker => action != Action::WriteInterpretation,
+            Role::Reader => matches!(
+                action,
+                Action::Shuffle
+                    | Action::RequestInterpretation
+                    | Action::CancelInterpretation
+                    | Action::WriteInterpretation
+            ),
+            Role::Observer => false,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("{action} is not allowed while the session is {phase}")]
+    InvalidState {
+        action: &'static str,
+        phase: &'static str,
+    },
+    #[error("spread size must be between 1 and {}", MAX_SPREAD_SIZE)]
+    InvalidSpreadSize,
+    #[error("card index {0} is not in the deck")]
+    InvalidCard(usize),
+    #[error("card {0} is already selected")]
+    CardAlreadySelected(usize),
+    #[error("all {0} cards of the spread are already selected")]
+    SpreadComplete(usize),
+    #[error("select {remaining} more card(s) before asking for an interpretation")]
+    SpreadIncomplete { remaining: usize },
+    #[error("session can't be resumed; start a new one")]
+    ResumeFailed,
+    #[error("the {role} role can't {action}")]
+    NotPermitted {
+        action: &'static str,
+        role: &'static str,
+    },
+    #[error("the invite isn't valid for this session")]
+    InvalidInvite,
+    #[error("the room is full")]
+    RoomFull,
+    #[error("only readers and observers can be invited")]
+    InvalidInviteRole,
+    #[error("only an interpretation a reader asked for can be written by hand")]
+    NotReaderWritten,
+    #[error("this connection isn't in the session's room")]
+    NotInRoom,
+}
+
+impl From<SessionError> for AppError {
+    fn from(error: SessionError) -> Self {
+        let code = match error {
+            SessionError::InvalidState { .. }
+            | SessionError::NotReaderWritten
+            | SessionError::SpreadComplete(_)
+            | SessionError::SpreadIncomplete { .. } => ErrorCode::InvalidState,
+            SessionError::CardAlreadySelected(_) => ErrorCode::CardAlreadySelected,
+            SessionError::InvalidCard(_) => ErrorCode::InvalidCard,
+            SessionError::InvalidSpreadSize => ErrorCode::InvalidRequest,
+            SessionError::ResumeFailed => ErrorCode::ResumeFailed,
+            SessionError::NotPermitted { .. } | SessionError::InvalidInvite | SessionError::NotInRoom => {
+                ErrorCode::Forbidden
+            }
+            SessionError::RoomFull => ErrorCode::Conflict,
+            SessionError::InvalidInviteRole => ErrorCode::InvalidRequest,
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+/// The checked state machine behind one reading session. The deck order
+/// and orientations are fixed when the deck is shuffled, so selecting a
+/// position always reveals the card that was there.
+#[derive(Debug)]
+pub struct SessionMachine {
+    phase: SessionPhase,
+    spread_size: usize,
+    deck: Vec<DrawnCard>,
+    /// The physical card at each deck position, numbered where it lay in
+    /// the first deck. A card keeps its number through reshuffles, so the
+    /// client can follow it; see `layout::card_id`.
+    table: Vec<usize>,
+    /// Deck positions in the order they were picked.
+    selected: Vec<usize>,
+    /// Whether the current selection has been interpreted at least once.
+    interpreted: bool,
+}
+
+impl Default for SessionMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionMachine {
+    pub fn new() -> Self {
+        Self {
+            phase: SessionPhase::Idle,
+            spread_size: DEFAULT_SPREAD_SIZE,
+            deck: Vec::new(),
+            table: Vec::new(),
+            selected: Vec::new(),
+            interpreted: false,
+        }
+    }
+
+    pub fn phase(&self) -> SessionPhase {
+        self.phase
+    }
+
+    pub fn deck_size(&self) -> usize {
+        self.deck.len()
+    }
+
+    pub fn spread_size(&self) -> usize {
+        self.spread_size
+    }
+
+    pub fn table(&self) -> &[usize] {
+        &self.table
+    }
+
+    /// Deck positions in the order they were picked.
+    pub fn selected(&self) -> &[usize] {
+        &self.selected
+    }
+
+    /// Whether `action` is allowed now, without changing anything.
+    pub fn check(&self, action: Action) -> Result<(), SessionError> {
+        use SessionPhase::*;
+
+        let allowed = match action {
+            Action::StartSession => matches!(self.phase, Idle | Shuffled | Selecting { .. } | FollowUp),
+            Action::Shuffle => matches!(self.phase, Shuffled | Selecting { .. } | FollowUp),
+            Action::SelectCard => matches!(self.phase, Shuffled | Selecting { .. }),
+            Action::RequestInterpretation => matches!(self.phase, Shuffled | Selecting { .. } | FollowUp),
+            Action::EndSession => self.phase != Closed,
+            // Only a fresh connection can take over another session
+            Action::ResumeSession => self.phase == Idle,
+            Action::CancelInterpretation => self.phase == Interpreting,
+            Action::CreateInvite => self.phase != Closed,
+            // Like resuming, only from a fresh connection
+            Action::JoinRoom => self.phase == Idle,
+            Action::WriteInterpretation => self.phase == Interpreting,
+        };
+        if !allowed {
+            return Err(SessionError::InvalidState {
+                action: action.name(),
+                phase: self.phase.name(),
+            });
+        }
+
+        match action {
+            Action::SelectCard if self.selected.len() >= self.spread_size => {
+                Err(SessionError::SpreadComplete(self.spread_size))
+            }
+            Action::RequestInterpretation if self.phase != FollowUp && self.selected.len() < self.spread_size => {
+                Err(SessionError::SpreadIncomplete {
+                    remaining: self.spread_size - self.selected.len(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a reading of `spread_size` cards can be started now.
+    pub fn check_start(&self, spread_size: usize) -> Result<(), SessionError> {
+        self.check(Action::StartSession)?;
+        if (1..=MAX_SPREAD_SIZE).contains(&spread_size) {
+            Ok(())
+        } else {
+            Err(SessionError::InvalidSpreadSize)
+        }
+    }
+
+    /// Start a new reading of `spread_size` cards from a freshly shuffled deck.
+    pub fn start(&mut self, spread_size: usize, deck: Vec<DrawnCard>) -> Result<(), SessionError> {
+        self.check_start(spread_size)?;
+
+        self.spread_size = spread_size;
+        self.commit_deck(deck);
+        Ok(())
+    }
+
+    /// Reshuffle, discarding any selection.
+    pub fn shuffle(&mut self, deck: Vec<DrawnCard>) -> Result<(), SessionError> {
+        self.check(Action::Shuffle)?;
+        self.commit_deck(deck);
+        Ok(())
+    }
+
+    fn commit_deck(&mut self, deck: Vec<DrawnCard>) {
+        let lying: HashMap<&str, usize> = self
+            .deck
+            .iter()
+            .zip(&self.table)
+            .map(|(drawn, &card)| (drawn.card.id.as_str(), card))
+            .collect();
+        // Renumber from scratch when the deck isn't the same set of cards
+        self.table = deck
+            .iter()
+            .map(|drawn| lying.get(drawn.card.id.as_str()).copied())
+            .collect::<Option<Vec<_>>>()
+            .filter(|table| table.len() == lying.len())
+            .unwrap_or_else(|| (0..deck.len()).collect());
+        self.deck = deck;
+        self.selected.clear();
+        self.interpreted = false;
+        self.phase = SessionPhase::Shuffled;
+    }
+
+    /// Reveal the card at `index` in the committed deck.
+    pub fn select(&mut self, index: usize) -> Result<DrawnCard, SessionError> {
+        self.check(Action::SelectCard)?;
+        if index >= self.deck.len() {
+            return Err(SessionError::InvalidCard(index));
+        }
+        if self.selected.contains(&index) {
+            return Err(SessionError::CardAlreadySelected(index));
+        }
+
+        self.selected.push(index);
+        self.phase = SessionPhase::Selecting {
+            selected: self.selected.len(),
+            spread_size: self.spread_size,
+        };
+        Ok(self.selected_cards().pop().expect("a card was just selected"))
+    }
+
+    /// The selected cards, positioned in the order they were picked.
+    pub fn selected_cards(&self) -> Vec<DrawnCard> {
+        self.selected
+            .iter()
+            .enumerate()
+            .map(|(position, &index)| DrawnCard {
+                position_index: position,
+                ..self.deck[index].clone()
+            })
+            .collect()
+    }
+
+    pub fn begin_interpretation(&mut self) -> Result<Vec<DrawnCard>, SessionError> {
+        self.check(Action::RequestInterpretation)?;
+        self.phase = SessionPhase::Interpreting;
+        Ok(self.selected_cards())
+    }
+
+    pub fn finish_interpretation(&mut self) {
+        if self.phase == SessionPhase::Interpreting {
+            self.interpreted = true;
+            self.phase = SessionPhase::FollowUp;
+        }
+    }
+
+    /// Return to where the interpretation was requested from after it failed.
+    pub fn abort_interpretation(&mut self) {
+        if self.phase != SessionPhase::Interpreting {
+            return;
+        }
+        self.phase = if self.interpreted {
+            SessionPhase::FollowUp
+        } else {
+            SessionPhase::Selecting {
+                selected: self.selected.len(),
+                spread_size: self.spread_size,
+            }
+        };
+    }
+
+    /// Back to `Idle`, e.g. after a question was declined.
+    pub fn reset(&mut self) {
+        if self.phase != SessionPhase::Closed {
+            *self = Self::new();
+        }
+    }
+
+    pub fn close(&mut self) -> Result<(), SessionError> {
+        self.check(Action::EndSession)?;
+        self.phase = SessionPhase::Closed;
+        Ok(())
+    }
+}
+
+/// One event of the current interpretation, kept for replay on resume.
+#[derive(Debug, Clone)]
+pub enum TranscriptEvent {
+    Chunk(String),
+    Complete,
+    Cancelled,
+}
+
+/// The current interpretation's events, numbered across the whole session
+/// so a resuming client can say which it has already seen.
+#[derive(Debug, Default)]
+pub struct Transcript {
+    next_seq: u64,
+    events: Vec<(u64, TranscriptEvent)>,
+}
+
+impl Transcript {
+    /// Forget the previous interpretation; numbering carries on.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn push(&mut self, event: TranscriptEvent) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.events.push((seq, event));
+        seq
+    }
+
+    /// Events after `last_seq`, or all of them.
+    pub fn since(&self, last_seq: Option<u64>) -> impl Iterator<Item = &(u64, TranscriptEvent)> {
+        self.events
+            .iter()
+            .filter(move |(seq, _)| last_seq.is_none_or(|last| *seq > last))
+    }
+}
+
+/// Share of a part's word pairs that must come from the draft for the part
+/// to count as the reader's rewording of it.
+const DRAFT_BORROWED_SHARE: f64 = 0.3;
+
+/// An interpretation a reader is writing, with the model's draft to work from.
+#[derive(Debug, Default)]
+pub struct ReaderInterpretation {
+    /// The follow-up question being answered, if this isn't the reading's
+    /// first interpretation.
+    pub question: Option<String>,
+    /// The model's suggestion once it is ready. Only readers see it.
+    pub draft: Option<String>,
+    /// What generating the draft cost.
+    pub draft_usage: Option<LlmUsage>,
+    /// What was sent to the room so far, part by part.
+    pub parts: Vec<(String, Authorship)>,
+}
+
+impl ReaderInterpretation {
+    /// Record a part the reader sent, judging who wrote it from the draft
+    /// as it stands now.
+    pub fn push(&mut self, text: String) -> Authorship {
+        let authorship = judge_authorship(&text, self.draft.as_deref());
+        self.parts.push((text, authorship));
+        authorship
+    }
+
+    pub fn text(&self) -> String {
+        self.parts.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    /// Who wrote the interpretation as a whole.
+    pub fn authorship(&self) -> Authorship {
+        let mut parts = self.parts.iter().map(|&(_, authorship)| authorship);
+        match parts.next() {
+            Some(first) if parts.all(|authorship| authorship == first) => first,
+            Some(_) => Authorship::Both,
+            None => Authorship::Human,
+        }
+    }
+}
+
+/// A part copied from the draft word for word is the model's; one sharing
+/// enough word pairs with it is a rewording, both the reader's and the
+/// model's; anything else is the reader's own. Case, punctuation and
+/// spacing don't count.
+fn judge_authorship(text: &str, draft: Option<&str>) -> Authorship {
+    fn words(text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    }
+
+    let (text, draft) = (words(text), words(draft.unwrap_or_default()));
+    if text.is_empty() {
+        return Authorship::Human;
+    }
+    if draft.windows(text.len()).any(|window| window == text.as_slice()) {
+        return Authorship::Ai;
+    }
+
+    let pairs = text.windows(2).count();
+    let borrowed = text
+        .windows(2)
+        .filter(|pair| draft.windows(2).any(|drafted| drafted == *pair))
+        .count();
+    if pairs > 0 && borrowed as f64 / pairs as f64 >= DRAFT_BORROWED_SHARE {
+        Authorship::Both
+    } else {
+        Authorship::Human
+    }
+}
+
+/// One connection in a session's room.
+pub struct Participant {
+    pub connection_id: Uuid,
+    pub role: Role,
+    /// Where the connection's messages go. Interpretations run in their own
+    /// task and send here, so they follow the room's membership.
+    pub sender: mpsc::Sender<ServerEnvelope>,
+    /// What the connection negotiated with `hello`.
+    pub capabilities: Vec<Capability>,
+    /// Wakes the connection's receive loop when it should hang up.
+    pub hang_up: Arc<Notify>,
+}
+
+/// Everything the server knows about one reading session. It outlives its
+/// WebSocket connections so a reconnecting client can resume it, and is
+/// shared by every connection in its room.
+pub struct SessionState {
+    pub session_id: String,
+    /// Hash of the secret that lets a new connection take over the session.
+    resume_token_hash: String,
+    pub client_ip: IpAddr,
+    /// The account the seeker authenticated as, if any.
+    pub user_id: Option<i64>,
+    pub query: Option<String>,
+    pub guardrails: Option<String>,
+    pub injection: InjectionReport,
+    pub machine: SessionMachine,
+    /// How the face-down deck is spread, chosen at `start_session`.
+    pub layout: DeckLayout,
+    /// The reading produced for the current selection; further
+    /// interpretations of it count as follow-ups.
+    pub reading_id: Option<i64>,
+    pub transcript: Transcript,
+    /// The connections in the room; the first is the seeker.
+    pub participants: Vec<Participant>,
+    /// Hashes of the invites handed out, with the role each grants.
+    invites: Vec<(String, Role)>,
+    /// Stops the interpretation in progress, if any.
+    pub cancel_interpretation: Option<Arc<Notify>>,
+    /// Asked for by a REST draw; applies once its stream starts the
+    /// interpretation.
+    pub bypass_cache: bool,
+    /// Set while a reader writes the interpretation instead of the model.
+    pub reader_interpretation: Option<ReaderInterpretation>,
+    /// The session quota a REST draw counts against, when not this session.
+    pub quota_owner: Option<String>,
+}
+
+impl SessionState {
+    /// A session with the seeker's connection, or with nobody yet for a
+    /// reading drawn over REST.
+    pub fn new(client_ip: IpAddr, user_id: Option<i64>, seeker: Option<Participant>) -> Self {
+        Self {
+            session_id: Uuid::new_v4().to_string(),
+            resume_token_hash: String::new(),
+            client_ip,
+            user_id,
+            query: None,
+            guardrails: None,
+            injection: InjectionReport::default(),
+            machine: SessionMachine::new(),
+            layout: DeckLayout::default(),
+            reading_id: None,
+            transcript: Transcript::default(),
+            participants: seeker.into_iter().collect(),
+            invites: Vec::new(),
+            cancel_interpretation: None,
+            bypass_cache: false,
+            reader_interpretation: None,
+            quota_owner: None,
+        }
+    }
+
+    /// The key of the session-scope quotas this session counts against.
+    pub fn quota_key(&self) -> &str {
+        self.quota_owner.as_deref().unwrap_or(&self.session_id)
+    }
+
+    /// Issue a new resume token, invalidating the previous one.
+    pub fn rotate_resume_token(&mut self) -> String {
+        let token = auth::random_hex(16);
+        self.resume_token_hash = auth::hash_token(&token);
+        token
+    }
+
+    /// Issue an invite that lets a new connection join the room as `role`.
+    pub fn create_invite(&mut self, role: Role) -> Result<String, SessionError> {
+        if role == Role::Seeker {
+            return Err(SessionError::InvalidInviteRole);
+        }
+        let token = auth::random_hex(16);
+        self.invites.push((auth::hash_token(&token), role));
+        Ok(token)
+    }
+
+    pub fn participant(&self, connection_id: Uuid) -> Option<&Participant> {
+        self.participants.iter().find(|p| p.connection_id == connection_id)
+    }
+
+    pub fn participant_mut(&mut self, connection_id: Uuid) -> Option<&mut Participant> {
+        self.participants.iter_mut().find(|p| p.connection_id == connection_id)
+    }
+
+    /// Whether the connection may ask for `action` in this session. Only
+    /// connections in the room may ask for anything.
+    pub fn permit(&self, connection_id: Uuid, action: Action) -> Result<(), SessionError> {
+        let participant = self.participant(connection_id).ok_or(SessionError::NotInRoom)?;
+        if participant.role.may(action) {
+            Ok(())
+        } else {
+            Err(SessionError::NotPermitted {
+                action: action.name(),
+                role: participant.role.name(),
+            })
+        }
+    }
+
+    /// Take the connection out of the room.
+    pub fn remove_participant(&mut self, connection_id: Uuid) -> Option<Participant> {
+        let index = self.participants.iter().position(|p| p.connection_id == connection_id)?;
+        Some(self.participants.remove(index))
+    }
+
+    /// End the session for everyone still in the room.
+    fn close_room(&mut self) {
+        let _ = self.machine.close();
+        for participant in self.participants.drain(..) {
+            participant.hang_up.notify_one();
+        }
+    }
+
+    /// `user_id` is `None` when the token alone is the credential.
+    fn accepts(&self, resume_token: &str, user_id: Option<Option<i64>>) -> bool {
+        !self.resume_token_hash.is_empty()
+            && self.resume_token_hash == auth::hash_token(resume_token)
+            && user_id.is_none_or(|id| self.user_id == id)
+    }
+
+    /// Worth keeping after the connection drops.
+    fn is_resumable(&self) -> bool {
+        !matches!(self.machine.phase(), SessionPhase::Idle | SessionPhase::Closed)
+    }
+}
+
+pub type SharedSession = Arc<tokio::sync::Mutex<SessionState>>;
+
+struct RegistryEntry {
+    session: SharedSession,
+    /// When the last connection let go of the session.
+    detached_at: Option<Instant>,
+}
+
+/// Live and recently detached sessions by session id.
+pub struct SessionRegistry {
+    config: SessionConfig,
+    entries: Mutex<HashMap<String, RegistryEntry>>,
+}
+
+impl SessionRegistry {
+    pub fn new(config: SessionConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn register(&self, session_id: &str, session: SharedSession) {
+        self.entries.lock().unwrap().insert(
+            session_id.to_string(),
+            RegistryEntry {
+                session,
+                detached_at: None,
+            },
+        );
+    }
+
+    /// Keep a session no connection has taken yet, e.g. one drawn over REST
+    /// whose interpretation is streamed later. Unless something resumes it,
+    /// it expires like a detached one.
+    pub fn park(&self, session_id: &str, session: SharedSession) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(
+            session_id.to_string(),
+            RegistryEntry {
+                session,
+                detached_at: Some(Instant::now()),
+            },
+        );
+        self.prune(&mut entries);
+    }
+
+    /// Sessions whose interpretation is still being generated or streamed.
+    /// One busy right now counts too. A reader writing by hand doesn't.
+    pub fn interpreting(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| {
+                entry.session.try_lock().map_or(true, |state| {
+                    state.cancel_interpretation.is_some() && state.reader_interpretation.is_none()
+                })
+            })
+            .count()
+    }
+
+    /// Forget a session outright, e.g. one its connection abandoned for another.
+    pub fn remove(&self, session_id: &str) {
+        self.entries.lock().unwrap().remove(session_id);
+    }
+
+    /// Hand the session to a new connection if the token and account match.
+    /// Waits for the previous connection to finish what it is doing.
+    pub async fn resume(
+        &self,
+        session_id: &str,
+        resume_token: &str,
+        user_id: Option<i64>,
+        seeker: Participant,
+    ) -> Result<SharedSession, SessionError> {
+        self.take_over(session_id, resume_token, Some(user_id), seeker).await
+    }
+
+    /// Like `resume`, for a reading's event stream. The stream token was
+    /// handed to whoever drew the reading and is the only credential, so
+    /// the account's token never has to go into a URL.
+    pub async fn resume_stream(
+        &self,
+        session_id: &str,
+        stream_token: &str,
+        seeker: Participant,
+    ) -> Result<SharedSession, SessionError> {
+        self.take_over(session_id, stream_token, None, seeker).await
+    }
+
+    async fn take_over(
+        &self,
+        session_id: &str,
+        resume_token: &str,
+        user_id: Option<Option<i64>>,
+        seeker: Participant,
+    ) -> Result<SharedSession, SessionError> {
+        let session = {
+            let mut entries = self.entries.lock().unwrap();
+            self.prune(&mut entries);
+            entries
+                .get(session_id)
+                .map(|entry| entry.session.clone())
+                .ok_or(SessionError::ResumeFailed)?
+        };
+
+        let mut state = session.lock().await;
+        if !state.accepts(resume_token, user_id) || !state.is_resumable() {
+            return Err(SessionError::ResumeFailed);
+        }
+        // A seeker still connected elsewhere is replaced
+        if let Some(previous) = state.participants.iter().position(|p| p.role == Role::Seeker) {
+            state.participants.remove(previous).hang_up.notify_one();
+        }
+        state.participants.insert(0, seeker);
+        drop(state);
+
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(session_id) {
+            entry.detached_at = None;
+        }
+        Ok(session)
+    }
+
+    /// Add a connection to the room the invite belongs to.
+    pub async fn join(
+        &self,
+        session_id: &str,
+        invite_token: &str,
+        mut participant: Participant,
+    ) -> Result<SharedSession, SessionError> {
+        let session = {
+            let mut entries = self.entries.lock().unwrap();
+            self.prune(&mut entries);
+            entries
+                .get(session_id)
+                .map(|entry| entry.session.clone())
+                .ok_or(SessionError::InvalidInvite)?
+        };
+
+        let mut state = session.lock().await;
+        let invite = auth::hash_token(invite_token);
+        participant.role = state
+            .invites
+            .iter()
+            .find(|(hash, _)| *hash == invite)
+            .map(|&(_, role)| role)
+            .filter(|_| state.machine.phase() != SessionPhase::Closed)
+            .ok_or(SessionError::InvalidInvite)?;
+        if state.participants.len() >= self.config.max_participants {
+            return Err(SessionError::RoomFull);
+        }
+        state.participants.push(participant);
+        drop(state);
+        Ok(session)
+    }
+
+    /// The connection `connection_id` has gone; returns the role it had if
+    /// it was still in the room. When the seeker goes, the session is kept
+    /// for resuming if there is anything to resume, otherwise the room ends.
+    pub async fn detach(&self, session: &SharedSession, connection_id: Uuid) -> Option<Role> {
+        let mut state = session.lock().await;
+        let role = state.remove_participant(connection_id)?.role;
+        if role != Role::Seeker {
+            return Some(role);
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if state.is_resumable() && self.config.resume_ttl_secs > 0 {
+            if let Some(entry) = entries.get_mut(&state.session_id) {
+                entry.detached_at = Some(Instant::now());
+            }
+        } else {
+            entries.remove(&state.session_id);
+            state.close_room();
+        }
+        self.prune(&mut entries);
+        Some(role)
+    }
+
+    /// Drop expired sessions, then the oldest detached ones over the cap.
+    fn prune(&self, entries: &mut HashMap<String, RegistryEntry>) {
+        let ttl = Duration::from_secs(self.config.resume_ttl_secs);
+        entries.retain(|_, entry| {
+            let keep = entry.detached_at.is_none_or(|at| at.elapsed() < ttl);
+            if !keep {
+                end_room(&entry.session);
+            }
+            keep
+        });
+
+        let mut detached: Vec<(Instant, String)> = entries
+            .iter()
+            .filter_map(|(id, entry)| entry.detached_at.map(|at| (at, id.clone())))
+            .collect();
+        if detached.len() > self.config.max_detached {
+            detached.sort();
+            let excess = detached.len() - self.config.max_detached;
+            for (_, id) in detached.into_iter().take(excess) {
+                if let Some(entry) = entries.remove(&id) {
+                    end_room(&entry.session);
+                }
+            }
+        }
+    }
+}
+
+/// Send everyone left in a dropped session's room away. A session busy
+/// right now is left to its own connections to notice.
+fn end_room(session: &SharedSession) {
+    if let Ok(mut state) = session.try_lock() {
+        state.close_room();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Keywords, TarotCard};
+    use std::net::Ipv4Addr;
+
+    fn deck(count: usize) -> Vec<DrawnCard> {
+        (0..count)
+            .map(|n| DrawnCard {
+                card: TarotCard {
+                    id: format!("test_{}", n),
+                    name: format!("Test {}", n),
+                    arcana: "major".to_string(),
+                    suit: None,
+                    number: n as i32,
+                    archetype: String::new(),
+                    keywords: Keywords {
+                        upright: Vec::new(),
+                        reversed: Vec::new(),
+                    },
+                    situational_tags: Vec::new(),
+                },
+                is_reversed: n % 2 == 1,
+                position_index: n,
+            })
+            .collect()
+    }
+
+    /// A machine with `spread_size` cards already picked.
+    fn selected(spread_size: usize) -> SessionMachine {
+        let mut machine = SessionMachine::new();
+        machine.start(spread_size, deck(10)).unwrap();
+        for index in 0..spread_size {
+            machine.select(index).unwrap();
+        }
+        machine
+    }
+
+    fn code(error: SessionError) -> ErrorCode {
+        AppError::from(error).code
+    }
+
+    #[test]
+    fn a_reading_goes_through_every_phase() {
+        let mut machine = SessionMachine::new();
+        machine.start(2, deck(10)).unwrap();
+        assert_eq!(machine.phase(), SessionPhase::Shuffled);
+
+        assert_eq!(machine.select(4).unwrap().position_index, 0);
+        assert_eq!(machine.select(7).unwrap().card.id, "test_7");
+        assert_eq!(machine.phase(), SessionPhase::Selecting { selected: 2, spread_size: 2 });
+
+        let cards = machine.begin_interpretation().unwrap();
+        assert_eq!(cards.iter().map(|c| c.position_index).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(machine.phase(), SessionPhase::Interpreting);
+
+        machine.finish_interpretation();
+        assert_eq!(machine.phase(), SessionPhase::FollowUp);
+        machine.begin_interpretation().unwrap();
+        machine.close().unwrap();
+        assert_eq!(machine.phase(), SessionPhase::Closed);
+    }
+
+    #[test]
+    fn selecting_before_the_session_starts_is_refused() {
+        let mut machine = SessionMachine::new();
+
+        let error = machine.select(0).unwrap_err();
+        assert!(matches!(
+            error,
+            SessionError::InvalidState { action: "select_card", phase: "idle" }
+        ));
+        assert_eq!(code(error), ErrorCode::InvalidState);
+        assert!(machine.selected().is_empty());
+    }
+
+    #[test]
+    fn shuffling_while_interpreting_is_refused() {
+        let mut machine = selected(3);
+        machine.begin_interpretation().unwrap();
+
+        let error = machine.shuffle(deck(10)).unwrap_err();
+        assert!(matches!(
+            error,
+            SessionError::InvalidState { action: "shuffle", phase: "interpreting" }
+        ));
+        assert_eq!(code(error), ErrorCode::InvalidState);
+        assert_eq!(machine.phase(), SessionPhase::Interpreting);
+        assert_eq!(machine.selected(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn selecting_past_the_spread_size_is_refused() {
+        let mut machine = selected(2);
+
+        let error = machine.select(5).unwrap_err();
+        assert!(matches!(error, SessionError::SpreadComplete(2)));
+        assert_eq!(code(error), ErrorCode::InvalidState);
+        assert_eq!(machine.selected(), &[0, 1]);
+    }
+
+    #[test]
+    fn bad_selections_have_their_own_codes() {
+        let mut machine = SessionMachine::new();
+        machine.start(3, deck(10)).unwrap();
+        machine.select(1).unwrap();
+
+        assert_eq!(code(machine.select(1).unwrap_err()), ErrorCode::CardAlreadySelected);
+        assert_eq!(code(machine.select(10).unwrap_err()), ErrorCode::InvalidCard);
+
+        let error = machine.begin_interpretation().unwrap_err();
+        assert!(matches!(error, SessionError::SpreadIncomplete { remaining: 2 }));
+        assert_eq!(code(error), ErrorCode::InvalidState);
+
+        let error = SessionMachine::new().check_start(MAX_SPREAD_SIZE + 1).unwrap_err();
+        assert_eq!(code(error), ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn interpreting_after_close_is_refused() {
+        let mut machine = selected(3);
+        machine.close().unwrap();
+
+        let error = machine.begin_interpretation().unwrap_err();
+        assert!(matches!(
+            error,
+            SessionError::InvalidState { action: "request_interpretation", phase: "closed" }
+        ));
+        assert_eq!(code(error), ErrorCode::InvalidState);
+        assert_eq!(code(machine.close().unwrap_err()), ErrorCode::InvalidState);
+
+        // Nothing brings a closed session back
+        machine.reset();
+        assert_eq!(machine.phase(), SessionPhase::Closed);
+    }
+
+    #[test]
+    fn a_failed_interpretation_returns_to_where_it_was_asked_for() {
+        let mut machine = selected(3);
+        machine.begin_interpretation().unwrap();
+        machine.abort_interpretation();
+        assert_eq!(machine.phase(), SessionPhase::Selecting { selected: 3, spread_size: 3 });
+
+        machine.begin_interpretation().unwrap();
+        machine.finish_interpretation();
+        machine.begin_interpretation().unwrap();
+        machine.abort_interpretation();
+        assert_eq!(machine.phase(), SessionPhase::FollowUp);
+    }
+
+    fn participant(role: Role) -> Participant {
+        let (sender, _) = mpsc::channel(1);
+        Participant {
+            connection_id: Uuid::new_v4(),
+            role,
+            sender,
+            capabilities: Vec::new(),
+            hang_up: Arc::new(Notify::new()),
+        }
+    }
+
+    fn room(participants: Vec<Participant>) -> SessionState {
+        let mut session = SessionState::new(IpAddr::V4(Ipv4Addr::LOCALHOST), None, None);
+        session.participants = participants;
+        session
+    }
+
+    #[test]
+    fn roles_are_permitted_their_actions() {
+        let seeker = participant(Role::Seeker);
+        let observer = participant(Role::Observer);
+        let (seeker_id, observer_id) = (seeker.connection_id, observer.connection_id);
+        let session = room(vec![seeker, observer]);
+
+        assert!(session.permit(seeker_id, Action::SelectCard).is_ok());
+        assert!(matches!(
+            session.permit(observer_id, Action::SelectCard),
+            Err(SessionError::NotPermitted { .. })
+        ));
+    }
+
+    #[test]
+    fn connections_outside_the_room_are_refused() {
+        let session = room(vec![participant(Role::Seeker)]);
+
+        let error = session.permit(Uuid::new_v4(), Action::SelectCard).unwrap_err();
+        assert!(matches!(error, SessionError::NotInRoom));
+        assert_eq!(AppError::from(error).code, ErrorCode::Forbidden);
+    }
+}
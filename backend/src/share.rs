@@ -0,0 +1,389 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::fmt::Write;
+use thiserror::Error;
+
+use crate::auth;
+use crate::db;
+use crate::error::{AppError, ErrorCode};
+use crate::export::{self, ExportedCard};
+use crate::models::{Share, ShareRequest};
+use crate::state::AppState;
+
+/// Share tokens are 128 random bits, hex encoded.
+const TOKEN_BYTES: usize = 16;
+/// How much of the interpretation goes into link previews.
+const PREVIEW_CHARS: usize = 200;
+/// Shown in place of the question when the owner didn't opt in to sharing it.
+const REDACTED_QUERY: &str = "[private question]";
+
+#[derive(Debug, Error)]
+pub enum ShareError {
+    #[error("share not found")]
+    NotFound,
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<ShareError> for AppError {
+    fn from(error: ShareError) -> Self {
+        let code = match error {
+            ShareError::NotFound => ErrorCode::NotFound,
+            ShareError::Database(_) => return AppError::internal("Share request failed", error),
+        };
+        AppError::new(code, error.to_string())
+    }
+}
+
+impl IntoResponse for ShareError {
+    fn into_response(self) -> Response {
+        AppError::from(self).into_response()
+    }
+}
+
+/// A share as its owner sees it, with the link to hand out.
+#[derive(Debug, Serialize)]
+pub struct ShareLink {
+    #[serde(flatten)]
+    pub share: Share,
+    pub url: String,
+}
+
+/// What anyone holding the link can see. The journal and follow-up
+/// conversation are never included.
+#[derive(Debug, Serialize)]
+pub struct SharedReading {
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    pub cards: Vec<ExportedCard>,
+    pub interpretation: Option<String>,
+    pub url: String,
+}
+
+fn link(state: &AppState, share: Share) -> ShareLink {
+    let url = page_url(state, &share.token);
+    ShareLink { share, url }
+}
+
+fn page_url(state: &AppState, token: &str) -> String {
+    format!("{}/s/{}", state.config.server.public_url.trim_end_matches('/'), token)
+}
+
+/// Tokens we could have issued; anything else is rejected without a query.
+fn is_token(token: &str) -> bool {
+    token.len() == TOKEN_BYTES * 2 && token.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+pub async fn create(
+    state: &AppState,
+    user_id: i64,
+    reading_id: i64,
+    request: ShareRequest,
+) -> Result<ShareLink, ShareError> {
+    if !db::reading_belongs_to(&state.db, reading_id, user_id).await? {
+        return Err(ShareError::NotFound);
+    }
+
+    let token = auth::random_hex(TOKEN_BYTES);
+    let share = db::create_share(&state.db, &token, reading_id, user_id, request.include_query).await?;
+    Ok(link(state, share))
+}
+
+pub async fn list(state: &AppState, user_id: i64, reading_id: i64) -> Result<Vec<ShareLink>, ShareError> {
+    if !db::reading_belongs_to(&state.db, reading_id, user_id).await? {
+        return Err(ShareError::NotFound);
+    }
+
+    Ok(db::list_shares(&state.db, reading_id)
+        .await?
+        .into_iter()
+        .map(|share| link(state, share))
+        .collect())
+}
+
+pub async fn revoke(state: &AppState, user_id: i64, token: &str) -> Result<(), ShareError> {
+    if is_token(token) && db::revoke_share(&state.db, token, user_id).await? {
+        Ok(())
+    } else {
+        Err(ShareError::NotFound)
+    }
+}
+
+/// The public view of an active share. Counts as a view.
+pub async fn open(state: &AppState, token: &str) -> Result<SharedReading, ShareError> {
+    if !is_token(token) {
+        return Err(ShareError::NotFound);
+    }
+
+    let (reading, include_query) = db::open_share(&state.db, token)
+        .await?
+        .ok_or(ShareError::NotFound)?;
+
+    let cards = export::reading_cards(state, reading.id).await?;
+
+    // Interpretations may quote the question back, so hiding it means
+    // scrubbing it from the text as well.
+    let interpretation = if include_query {
+        reading.interpretation
+    } else {
+        reading
+            .interpretation
+            .map(|text| redact_query(&text, &reading.user_query))
+    };
+
+    Ok(SharedReading {
+        created_at: reading.created_at,
+        query: include_query.then_some(reading.user_query),
+        cards,
+        interpretation,
+        url: page_url(state, token),
+    })
+}
+
+/// Replace quotes of `query` in `text`, ignoring case, how the words are
+/// spaced and the question's closing punctuation. Best effort: a reworded
+/// question still gets through.
+fn redact_query(text: &str, query: &str) -> String {
+    let query = query.trim().trim_end_matches(|c: char| c.is_ascii_punctuation());
+    let words: Vec<&str> = query.split_whitespace().collect();
+    if words.is_empty() {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut previous = None;
+    while let Some(c) = rest.chars().next() {
+        let quoted = match previous {
+            Some(p) if is_word_char(p) => None,
+            _ => quote_length(rest, &words),
+        };
+        match quoted {
+            Some(len) => {
+                out.push_str(REDACTED_QUERY);
+                previous = rest[..len].chars().next_back();
+                rest = &rest[len..];
+            }
+            None => {
+                out.push(c);
+                previous = Some(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// Length in bytes of the quote of `words` that `text` starts with, if any.
+fn quote_length(text: &str, words: &[&str]) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    for (n, word) in words.iter().enumerate() {
+        if n > 0 {
+            let mut spaced = false;
+            while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {
+                spaced = true;
+            }
+            if !spaced {
+                return None;
+            }
+        }
+        for expected in word.chars() {
+            let (_, c) = chars.next()?;
+            if c != expected && !c.to_lowercase().eq(expected.to_lowercase()) {
+                return None;
+            }
+        }
+    }
+
+    match chars.peek() {
+        // The quote has to end where a word does
+        Some(&(_, c)) if is_word_char(c) => None,
+        Some(&(end, _)) => Some(end),
+        None => Some(text.len()),
+    }
+}
+
+/// Only Latin letters and digits mark word boundaries: Korean particles
+/// attach straight to the quoted words, and hiding a little too much is
+/// better than leaking the question.
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn preview(text: &str) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= PREVIEW_CHARS {
+        flat
+    } else {
+        let cut: String = flat.chars().take(PREVIEW_CHARS - 1).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
+const PAGE_STYLE: &str = "body{margin:0;background:#120b1f;color:#eee6f7;font:17px/1.6 Georgia,serif}\
+main{max-width:42rem;margin:0 auto;padding:2rem 1.25rem}\
+h1{font-weight:normal;letter-spacing:.05em;color:#e8c874}\
+.meta{color:#a99bbd;font-size:.9rem}\
+ol{padding-left:1.5rem}li{margin:.25rem 0}\
+.reversed{color:#a99bbd;font-style:italic}\
+blockquote{margin:1rem 0;padding:.5rem 1rem;border-left:3px solid #e8c874;color:#d6cce6}";
+
+/// Browser response headers for share pages: nothing external may load, and
+/// the token must not leak through the Referer header.
+fn page_response(status: StatusCode, html: String) -> Response {
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (header::CONTENT_SECURITY_POLICY, "default-src 'none'; style-src 'unsafe-inline'"),
+            (header::REFERRER_POLICY, "no-referrer"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        html,
+    )
+        .into_response()
+}
+
+/// A standalone HTML page for a share, with Open Graph tags for link
+/// previews. Search engines are asked not to index it.
+pub fn render_page(reading: &SharedReading) -> Response {
+    let names: Vec<&str> = reading.cards.iter().map(|c| c.name.as_str()).collect();
+    let title = if names.is_empty() {
+        "A tarot reading".to_string()
+    } else {
+        format!("Tarot reading: {}", names.join(", "))
+    };
+    let description = reading
+        .interpretation
+        .as_deref()
+        .map(preview)
+        .unwrap_or_else(|| format!("{} cards drawn with Immersive Tarot.", reading.cards.len()));
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <meta name=\"robots\" content=\"noindex\">\n<title>{title}</title>\n\
+         <meta name=\"description\" content=\"{description}\">\n\
+         <meta property=\"og:type\" content=\"article\">\n\
+         <meta property=\"og:site_name\" content=\"Immersive Tarot\">\n\
+         <meta property=\"og:title\" content=\"{title}\">\n\
+         <meta property=\"og:description\" content=\"{description}\">\n\
+         <meta property=\"og:url\" content=\"{url}\">\n\
+         <meta name=\"twitter:card\" content=\"summary\">\n\
+         <style>{style}</style>\n</head>\n<body>\n<main>\n<h1>A Tarot Reading</h1>\n\
+         <p class=\"meta\">{created_at} UTC</p>\n",
+        title = escape_html(&title),
+        description = escape_html(&description),
+        url = escape_html(&reading.url),
+        style = PAGE_STYLE,
+        created_at = escape_html(&reading.created_at),
+    );
+
+    if let Some(query) = &reading.query {
+        let _ = writeln!(out, "<blockquote>{}</blockquote>", escape_html(query));
+    }
+
+    out.push_str("<h2>Cards</h2>\n<ol>\n");
+    for card in &reading.cards {
+        let class = if card.reversed { " class=\"reversed\"" } else { "" };
+        let _ = writeln!(
+            out,
+            "<li>{} <span{}>({})</span></li>",
+            escape_html(&card.name),
+            class,
+            export::orientation(card.reversed)
+        );
+    }
+    out.push_str("</ol>\n");
+
+    if let Some(interpretation) = &reading.interpretation {
+        out.push_str("<h2>Interpretation</h2>\n");
+        for paragraph in interpretation.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+            let _ = writeln!(out, "<p>{}</p>", escape_html(paragraph).replace('\n', "<br>"));
+        }
+    }
+
+    out.push_str("</main>\n</body>\n</html>\n");
+    page_response(StatusCode::OK, out)
+}
+
+/// Shown for unknown and revoked links.
+pub fn render_not_found() -> Response {
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"robots\" content=\"noindex\">\n<title>Reading not available</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<main>\n<h1>Reading not available</h1>\n\
+         <p>This link has been revoked or never existed.</p>\n</main>\n</body>\n</html>\n",
+        PAGE_STYLE
+    );
+    page_response(StatusCode::NOT_FOUND, html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_are_hidden_whatever_their_case_and_spacing() {
+        let query = "Should I move to Lisbon?";
+        assert_eq!(
+            redact_query("You asked: Should I move to Lisbon? The Star says yes.", query),
+            "You asked: [private question]? The Star says yes."
+        );
+        assert_eq!(
+            redact_query("As for \"should i MOVE\n to   lisbon\", the cards are clear.", query),
+            "As for \"[private question]\", the cards are clear."
+        );
+        assert_eq!(
+            redact_query("should I move to Lisbon. Should I move to Lisbon", query),
+            "[private question]. [private question]"
+        );
+    }
+
+    #[test]
+    fn quotes_must_be_whole_words() {
+        assert_eq!(
+            redact_query("Your lovely question about love.", "Love"),
+            "Your lovely question about [private question]."
+        );
+        assert_eq!(redact_query("Beloved, the Lovers appear.", "love"), "Beloved, the Lovers appear.");
+    }
+
+    #[test]
+    fn non_latin_questions_are_hidden_too() {
+        assert_eq!(
+            redact_query("질문은 \"이직해도  될까요\"였습니다.", "이직해도 될까요?"),
+            "질문은 \"[private question]\"였습니다."
+        );
+        assert_eq!(
+            redact_query("이직해도 될까요라는 질문에 탑이 나왔습니다.", "이직해도 될까요?"),
+            "[private question]라는 질문에 탑이 나왔습니다."
+        );
+    }
+
+    #[test]
+    fn a_blank_question_hides_nothing() {
+        assert_eq!(redact_query("The Tower.", "  ?  "), "The Tower.");
+    }
+}
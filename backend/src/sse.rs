@@ -0,0 +1,237 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Notify};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::ai_service::InjectionReport;
+use crate::db;
+use crate::error::AppError;
+use crate::models::DrawnCard;
+use crate::protocol::Capability;
+use crate::session::{Action, Participant, Role, SessionError, SessionPhase, SessionState, SharedSession};
+use crate::state::AppState;
+use crate::ws_handler::{self, InterpretationJob, ServerEnvelope, ServerMessage};
+
+/// A reading drawn over REST whose interpretation is left for its stream.
+pub struct StreamedReading {
+    pub reading_id: i64,
+    pub cards: Vec<DrawnCard>,
+    pub stream_token: String,
+}
+
+/// What a REST draw with `stream` needs to know about the query.
+pub struct StreamedDraw<'a> {
+    pub session_id: &'a str,
+    /// Whose session quota the interpretation's tokens count against.
+    pub quota_key: &'a str,
+    pub client_ip: IpAddr,
+    pub user_id: Option<i64>,
+    pub query: &'a str,
+    pub count: usize,
+    pub bypass_cache: bool,
+    pub guardrails: Option<String>,
+    pub injection: InjectionReport,
+}
+
+/// Draw and save a reading through the same session machine a WebSocket
+/// reading goes through, then park the session until a stream picks it up.
+pub async fn start_streamed_reading(state: &AppState, draw: StreamedDraw<'_>) -> Result<StreamedReading, AppError> {
+    let mut session = SessionState::new(draw.client_ip, draw.user_id, None);
+    session.session_id = draw.session_id.to_string();
+    session.machine.start(draw.count, state.deck.shuffled(draw.query))?;
+    for index in 0..draw.count {
+        session.machine.select(index)?;
+    }
+    let cards = session.machine.selected_cards();
+
+    let injection_json = serde_json::to_value(&draw.injection).unwrap_or_default();
+    let reading_id = db::save_reading(&state.db, draw.session_id, draw.query, &cards, None, &injection_json)
+        .await
+        .map_err(|e| AppError::internal("Failed to save reading", e))?;
+
+    session.query = Some(draw.query.to_string());
+    session.guardrails = draw.guardrails;
+    session.injection = draw.injection;
+    session.bypass_cache = draw.bypass_cache;
+    session.quota_owner = Some(draw.quota_key.to_string());
+    session.reading_id = Some(reading_id);
+    let stream_token = session.rotate_resume_token();
+    state
+        .sessions
+        .park(draw.session_id, Arc::new(tokio::sync::Mutex::new(session)));
+
+    Ok(StreamedReading {
+        reading_id,
+        cards,
+        stream_token,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamParams {
+    /// From the draw response.
+    stream_token: String,
+}
+
+/// `GET /api/readings/{id}/stream`: the reading's interpretation as
+/// Server-Sent Events. The first stream starts the interpretation; a
+/// reconnect with `Last-Event-ID` gets what came after that event.
+pub async fn interpretation_stream(
+    State(state): State<Arc<AppState>>,
+    Path(reading_id): Path<i64>,
+    Query(params): Query<StreamParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let last_seq = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+
+    let session_id = db::get_reading_session(&state.db, reading_id)
+        .await
+        .map_err(|e| AppError::internal("Failed to look up reading", e))?
+        .ok_or(SessionError::ResumeFailed)?;
+
+    // The stream takes the session over like a resuming connection
+    let (tx, rx) = mpsc::channel::<ServerEnvelope>(32);
+    let connection_id = Uuid::new_v4();
+    let hang_up = Arc::new(Notify::new());
+    let seeker = Participant {
+        connection_id,
+        role: Role::Seeker,
+        sender: tx,
+        capabilities: vec![Capability::Streaming],
+        hang_up: hang_up.clone(),
+    };
+    let shared = state
+        .sessions
+        .resume_stream(&session_id, &params.stream_token, seeker)
+        .await?;
+    let departure = Departure {
+        state: state.clone(),
+        session: shared.clone(),
+        connection_id,
+    };
+
+    let mut session = shared.lock().await;
+    if session.reading_id != Some(reading_id) {
+        return Err(SessionError::ResumeFailed.into());
+    }
+
+    let replayed: Vec<Event> = session
+        .transcript
+        .since(last_seq)
+        .filter_map(|(seq, event)| sse_event(&ws_handler::transcript_message(*seq, event)))
+        .collect();
+
+    let fresh = matches!(session.machine.phase(), SessionPhase::Selecting { .. })
+        && session.machine.check(Action::RequestInterpretation).is_ok();
+    if fresh {
+        start_interpretation(&mut session, &shared, &state, connection_id, reading_id)?;
+    }
+    info!(session_id = %session_id, reading_id, last_seq = ?last_seq, fresh, "Interpretation stream opened");
+
+    // A finished reading is only replayed
+    let live = session.machine.phase() == SessionPhase::Interpreting;
+    drop(session);
+
+    let events = stream::unfold(Some((rx, hang_up, departure)), move |open| async move {
+        let (mut rx, hang_up, departure) = open.filter(|_| live)?;
+        loop {
+            let envelope = tokio::select! {
+                envelope = rx.recv() => envelope?,
+                // Taken over by another stream, or the session ended
+                _ = hang_up.notified() => return None,
+                // The client reconnects with `Last-Event-ID`
+                _ = departure.state.shutting_down() => return None,
+            };
+            let message = envelope.message;
+            let Some(event) = sse_event(&message) else {
+                continue;
+            };
+            let last = matches!(
+                message,
+                ServerMessage::InterpretationComplete { .. } | ServerMessage::InterpretationCancelled { .. }
+            );
+            let next = (!last).then_some((rx, hang_up, departure));
+            return Some((event, next));
+        }
+    });
+
+    let events = stream::iter(replayed).chain(events).map(Ok);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn start_interpretation(
+    session: &mut SessionState,
+    shared: &SharedSession,
+    state: &Arc<AppState>,
+    requester: Uuid,
+    reading_id: i64,
+) -> Result<(), SessionError> {
+    let cards = session.machine.begin_interpretation()?;
+    session.transcript.clear();
+    let cancel = Arc::new(Notify::new());
+    session.cancel_interpretation = Some(cancel.clone());
+
+    tokio::spawn(ws_handler::interpret(InterpretationJob {
+        session: shared.clone(),
+        app_state: state.clone(),
+        cards,
+        bypass_cache: session.bypass_cache,
+        draft: false,
+        streaming: true,
+        requester,
+        request_id: None,
+        cancel,
+        reading_id: Some(reading_id),
+        follow_up: None,
+    }));
+    Ok(())
+}
+
+/// Interpretation messages become events named after their type, with the
+/// transcript `seq` as the event id; everything else isn't streamed.
+fn sse_event(message: &ServerMessage) -> Option<Event> {
+    let (name, seq) = match message {
+        ServerMessage::InterpretationChunk { seq, .. } => ("interpretation_chunk", *seq),
+        ServerMessage::InterpretationComplete { seq } => ("interpretation_complete", *seq),
+        ServerMessage::InterpretationCancelled { seq } => ("interpretation_cancelled", *seq),
+        _ => return None,
+    };
+    match Event::default().event(name).id(seq.to_string()).json_data(message) {
+        Ok(event) => Some(event),
+        Err(e) => {
+            error!("Failed to serialize event: {}", e);
+            None
+        }
+    }
+}
+
+/// Lets go of the session when the stream ends or the client goes away, so
+/// the session can be resumed again until it expires.
+struct Departure {
+    state: Arc<AppState>,
+    session: SharedSession,
+    connection_id: Uuid,
+}
+
+impl Drop for Departure {
+    fn drop(&mut self) {
+        let state = self.state.clone();
+        let session = self.session.clone();
+        let connection_id = self.connection_id;
+        tokio::spawn(async move {
+            state.sessions.detach(&session, connection_id).await;
+        });
+    }
+}
@@ -1,7 +1,37 @@
 use sqlx::SqlitePool;
+use std::future;
+use tokio::sync::watch;
+use crate::ai_service::AiService;
+use crate::auth::AuthService;
+use crate::config::Config;
+use crate::export::Exporter;
+use crate::quota::QuotaManager;
+use crate::rate_limit::RateLimiter;
+use crate::session::SessionRegistry;
 use crate::tarot_engine::TarotDeck;
 
 pub struct AppState {
     pub db: SqlitePool,
     pub deck: TarotDeck,
+    pub ai: AiService,
+    pub quota: QuotaManager,
+    pub rate_limit: RateLimiter,
+    pub auth: AuthService,
+    pub export: Exporter,
+    pub sessions: SessionRegistry,
+    pub config: Config,
+    /// Turns true once the server starts shutting down, so long-lived
+    /// connections can close themselves.
+    pub shutdown: watch::Receiver<bool>,
+}
+
+impl AppState {
+    /// Resolves once the server starts shutting down.
+    pub async fn shutting_down(&self) {
+        let mut shutdown = self.shutdown.clone();
+        let down = shutdown.wait_for(|&down| down).await.is_ok();
+        if !down {
+            future::pending::<()>().await;
+        }
+    }
 }
@@ -1,33 +1,113 @@
-use crate::models::{TarotCard, DrawnCard};
-use rand::seq::SliceRandom;
+use crate::models::{DeckFile, TarotCard, DrawnCard};
 use rand::Rng;
+use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::BufReader;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DeckError {
+    #[error("failed to open deck file {}: {source}", path.display())]
+    Open {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse deck file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+/// Chance that a drawn card comes up reversed.
+pub const REVERSED_PROBABILITY: f64 = 0.3;
 
 #[derive(Clone)]
 pub struct TarotDeck {
+    version: String,
     cards: Vec<TarotCard>,
 }
 
 impl TarotDeck {
-    pub fn new() -> Self {
-        // Load from JSON file
-        // In a real app we might load this from DB or bake it in.
-        // For now, we assume the JSON is at "../frontend/assets/tarot_data.json" or similar
-        // Or we can embed it. Let's try to load from a known path.
-        // Load from local file in container or dev
-        let path = "tarot_data.json"; 
-        let file = File::open(path).expect("Failed to open tarot_data.json");
+    /// Load a deck from a JSON file with a `deck` header and a `cards` list.
+    pub fn load(path: &Path) -> Result<Self, DeckError> {
+        let file = File::open(path).map_err(|source| DeckError::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
         let reader = BufReader::new(file);
-        let cards: Vec<TarotCard> = serde_json::from_reader(reader).expect("Failed to parse tarot_data.json");
+        let deck: DeckFile = serde_json::from_reader(reader).map_err(|source| DeckError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        if deck.cards.len() != deck.deck.total_cards {
+            tracing::warn!(
+                expected = deck.deck.total_cards,
+                found = deck.cards.len(),
+                "Deck card count does not match its header"
+            );
+        }
+        tracing::info!(name = %deck.deck.name, version = %deck.deck.version, "Loaded tarot deck");
         
-        Self { cards }
+        Ok(Self {
+            version: deck.deck.version,
+            cards: deck.cards,
+        })
+    }
+
+    /// Version of the deck data, part of every interpretation cache key.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn cards(&self) -> &[TarotCard] {
+        &self.cards
+    }
+
+    pub fn card(&self, id: &str) -> Option<&TarotCard> {
+        self.cards.iter().find(|c| c.id == id)
+    }
+
+    pub fn card_names(&self) -> Vec<String> {
+        self.cards.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// The card of the day for `owner`, derived from a hash of the owner,
+    /// the day and the deck version so it doesn't depend on RNG internals.
+    pub fn daily_card(&self, owner: &str, day: &str) -> Option<DrawnCard> {
+        if self.cards.is_empty() {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+        for part in ["daily", owner, day, &self.version] {
+            hasher.update(part.as_bytes());
+            hasher.update([0u8]);
+        }
+        let digest = hasher.finalize();
+        let word = |i: usize| u64::from_be_bytes(digest[i..i + 8].try_into().expect("8 bytes"));
+
+        let card = &self.cards[(word(0) % self.cards.len() as u64) as usize];
+        let roll = word(8) as f64 / u64::MAX as f64;
+
+        Some(DrawnCard {
+            card: card.clone(),
+            is_reversed: roll < REVERSED_PROBABILITY,
+            position_index: 0,
+        })
+    }
+
+    /// The whole deck in a context-aware order, each card with its
+    /// orientation already decided.
+    pub fn shuffled(&self, query: &str) -> Vec<DrawnCard> {
+        self.draw_with_context(query, self.cards.len())
     }
 
     /// Context-aware biased shuffle
     pub fn draw_with_context(&self, query: &str, count: usize) -> Vec<DrawnCard> {
-        let mut rng = rand::thread_rng();
+        let mut rng = rand::rng();
         let query_lower = query.to_lowercase();
         
         // simple keyword extraction
@@ -65,7 +145,7 @@ impl TarotDeck {
                 .map(|&idx| weighted_cards[idx].1)
                 .sum();
             
-            let mut r = rng.gen_range(0.0..total_weight);
+            let mut r = rng.random_range(0.0..total_weight);
             let mut selected_idx_in_available = 0;
             
             for (i, &card_idx) in available_indices.iter().enumerate() {
@@ -82,7 +162,7 @@ impl TarotDeck {
             
             // Random orientation (50/50? Or biased?)
             // Let's go 70% Upright, 30% Reversed for less "doom"
-            let is_reversed = rng.gen_bool(0.3);
+            let is_reversed = rng.random_bool(REVERSED_PROBABILITY);
 
             chosen_cards.push(DrawnCard {
                 card: card.clone(),
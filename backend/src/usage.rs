@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// USD price per million tokens for one model.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+/// Model name to price, used to estimate what each call cost.
+#[derive(Debug, Clone)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        let mut prices = HashMap::new();
+        prices.insert(
+            "deepseek-chat".to_string(),
+            ModelPrice {
+                prompt_per_million: 0.27,
+                completion_per_million: 1.10,
+            },
+        );
+        prices.insert(
+            "deepseek-reasoner".to_string(),
+            ModelPrice {
+                prompt_per_million: 0.55,
+                completion_per_million: 2.19,
+            },
+        );
+        Self { prices }
+    }
+}
+
+impl PriceTable {
+    /// The default prices with per-model overrides applied on top.
+    pub fn with_overrides(overrides: &HashMap<String, ModelPrice>) -> Self {
+        let mut table = Self::default();
+        table
+            .prices
+            .extend(overrides.iter().map(|(model, price)| (model.clone(), *price)));
+        table
+    }
+
+    pub fn estimate_cost(&self, model: &str, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+        match self.prices.get(model) {
+            Some(price) => {
+                (prompt_tokens as f64 * price.prompt_per_million
+                    + completion_tokens as f64 * price.completion_per_million)
+                    / 1_000_000.0
+            }
+            None => {
+                tracing::warn!(model = %model, "No price configured for model, cost recorded as 0");
+                0.0
+            }
+        }
+    }
+}
+
+/// What one LLM call consumed.
+#[derive(Debug, Clone, Serialize)]
+pub struct LlmUsage {
+    pub purpose: &'static str,
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub latency_ms: u64,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGrouping {
+    Day,
+    Model,
+    Session,
+}
+
+impl UsageGrouping {
+    /// SQL expression for the grouping column of `llm_usage`.
+    pub fn column(&self) -> &'static str {
+        match self {
+            UsageGrouping::Day => "date(created_at)",
+            UsageGrouping::Model => "model",
+            UsageGrouping::Session => "session_id",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub group_by: UsageGrouping,
+    /// Only count calls on or after this date (YYYY-MM-DD).
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct UsageSummary {
+    pub key: String,
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+    pub avg_latency_ms: f64,
+}
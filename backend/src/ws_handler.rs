@@ -1,207 +1,1004 @@
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Query, State,
     },
-    response::IntoResponse,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
 };
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::time::{self, Duration, Instant, Interval, MissedTickBehavior};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::ai_service;
+use crate::ai_service::{self, FollowUp, Interpretation, InterpretationRequest, InterpretationSource};
+use crate::auth;
 use crate::db;
+use crate::error::{AppError, ErrorCode};
+use crate::layout::{self, DeckLayout, Position, ShuffleStep};
+use crate::models::DrawnCard;
+use crate::protocol::{Capability, Protocol, ProtocolError};
+use crate::quota::QuotaExceeded;
+use crate::rate_limit::WsConnectionGuard;
+use crate::safety::{self, SafetyCategory};
+use crate::session::{
+    Action, Participant, ReaderInterpretation, Role, SessionError, SessionPhase, SessionState, SharedSession,
+    TranscriptEvent, DEFAULT_SPREAD_SIZE,
+};
 use crate::state::AppState;
 
+/// A client message and the id the client gave it. Every reply to the
+/// message carries the same id.
+#[derive(Debug, Deserialize)]
+pub struct ClientEnvelope {
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(flatten)]
+    pub message: ClientMessage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerEnvelope {
+    /// The request this answers; absent for unsolicited messages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(flatten)]
+    pub message: ServerMessage,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
-    StartSession { query: String },
+    /// Negotiate the protocol version and capabilities. Optional, and only
+    /// once per connection; see `protocol.rs`.
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        capabilities: Vec<String>,
+    },
+    StartSession {
+        query: String,
+        /// Number of cards to select; 3 when absent.
+        #[serde(default)]
+        spread_size: Option<usize>,
+        /// How to spread the deck; a fan when absent.
+        #[serde(default)]
+        layout: Option<DeckLayout>,
+    },
     SelectCard { card_index: usize },
-    RequestInterpretation,
+    RequestInterpretation {
+        #[serde(default)]
+        bypass_cache: bool,
+        /// What the seeker wants to know next; only read in `follow_up`.
+        #[serde(default)]
+        question: Option<String>,
+    },
     Shuffle,
+    /// Stop the interpretation in progress.
+    CancelInterpretation,
+    /// Send the next part of the interpretation a reader is writing.
+    ReaderChunk { text: String },
+    /// Finish the interpretation a reader is writing.
+    ReaderComplete,
+    EndSession,
+    /// Hand out an invite to this session's room. Seeker only.
+    CreateInvite { role: Role },
+    /// Join another session's room with an invite.
+    JoinRoom { session_id: String, invite_token: String },
+    /// Take over a session whose connection dropped.
+    ResumeSession {
+        session_id: String,
+        resume_token: String,
+        /// The last interpretation event the client received.
+        #[serde(default)]
+        last_seq: Option<u64>,
+    },
     Ping,
 }
 
+impl ClientMessage {
+    /// What the message asks the session to do, for checking the sender's
+    /// role. `hello` and `ping` concern only the connection.
+    fn action(&self) -> Option<Action> {
+        Some(match self {
+            ClientMessage::Hello { .. } | ClientMessage::Ping => return None,
+            ClientMessage::StartSession { .. } => Action::StartSession,
+            ClientMessage::SelectCard { .. } => Action::SelectCard,
+            ClientMessage::RequestInterpretation { .. } => Action::RequestInterpretation,
+            ClientMessage::Shuffle => Action::Shuffle,
+            ClientMessage::CancelInterpretation => Action::CancelInterpretation,
+            ClientMessage::ReaderChunk { .. } | ClientMessage::ReaderComplete => Action::WriteInterpretation,
+            ClientMessage::EndSession => Action::EndSession,
+            ClientMessage::CreateInvite { .. } => Action::CreateInvite,
+            ClientMessage::JoinRoom { .. } => Action::JoinRoom,
+            ClientMessage::ResumeSession { .. } => Action::ResumeSession,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ServerMessage {
-    SessionStarted { session_id: String },
-    DeckState { card_positions: Vec<CardPosition> },
-    CardSelected { card_id: String, is_reversed: bool },
-    InterpretationChunk { text: String },
-    InterpretationComplete,
+    Hello {
+        protocol_version: u32,
+        capabilities: Vec<Capability>,
+        server_version: &'static str,
+    },
+    /// `claim_token` comes with the first start of an anonymous session;
+    /// a login presents it to move the session into the account.
+    SessionStarted {
+        session_id: String,
+        resume_token: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        claim_token: Option<String>,
+    },
+    /// Followed by a replay of the session's deck, selection, state and
+    /// any interpretation events after the client's `last_seq`.
+    SessionResumed { session_id: String, resume_token: String },
+    /// An invite anyone can use to join the room as `role`.
+    RoomInvite {
+        session_id: String,
+        role: Role,
+        invite_token: String,
+    },
+    /// Followed by the same replay as `session_resumed`, from the start.
+    RoomJoined {
+        session_id: String,
+        participant_id: String,
+        role: Role,
+        participants: Vec<ParticipantInfo>,
+    },
+    ParticipantJoined { participant_id: String, role: Role },
+    ParticipantLeft { participant_id: String, role: Role },
+    SessionState(SessionPhase),
+    /// The whole table, in deck order: `card_positions[i]` is what
+    /// `select_card` calls `card_index` i.
+    DeckState {
+        layout: DeckLayout,
+        card_positions: Vec<layout::CardPosition>,
+    },
+    /// `slot` is where the revealed card lies in the spread.
+    CardSelected {
+        card_id: String,
+        is_reversed: bool,
+        slot: Position,
+    },
+    /// The model's suggestion for an interpretation a reader asked for.
+    /// Sent to readers only.
+    InterpretationDraft { text: String },
+    InterpretationChunk { seq: u64, text: String },
+    InterpretationComplete { seq: u64 },
+    /// The interpretation was stopped; chunks so far are all there will be.
+    InterpretationCancelled { seq: u64 },
+    /// The finished interpretation with its cards, for clients that
+    /// negotiated `structured_output`.
+    InterpretationResult {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reading_id: Option<i64>,
+        source: InterpretationSource,
+        cards: Vec<ResultCard>,
+        text: String,
+    },
     ShuffleAnimation { sequence: Vec<ShuffleStep> },
-    Error { message: String },
+    SafetyResponse { category: SafetyCategory, message: String },
+    Error {
+        code: ErrorCode,
+        message: String,
+        /// Whether sending the same message again later may succeed.
+        retryable: bool,
+        /// Unix seconds at which an exceeded quota resets
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reset_at: Option<u64>,
+    },
     Pong,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CardPosition {
-    pub card_id: String,
-    pub x: f32,
-    pub y: f32,
-    pub rotation: f32,
-    pub is_face_up: bool,
-    pub z_index: usize,
+/// What the client is told about a failed message. Errors that aren't
+/// one of ours are logged and reported as internal.
+fn app_error(error: Box<dyn std::error::Error + Send + Sync>) -> AppError {
+    let error = match error.downcast::<AppError>() {
+        Ok(e) => return *e,
+        Err(e) => e,
+    };
+    let error = match error.downcast::<SessionError>() {
+        Ok(e) => return (*e).into(),
+        Err(e) => e,
+    };
+    let error = match error.downcast::<ProtocolError>() {
+        Ok(e) => return (*e).into(),
+        Err(e) => e,
+    };
+    let error = match error.downcast::<QuotaExceeded>() {
+        Ok(e) => return (*e).into(),
+        Err(e) => e,
+    };
+    match error.downcast::<serde_json::Error>() {
+        Ok(e) if e.is_syntax() || e.is_eof() => AppError::new(ErrorCode::BadMessage, "message is not valid JSON"),
+        Ok(e) => AppError::new(ErrorCode::BadMessage, format!("invalid message: {}", e)),
+        Err(e) => AppError::internal("WebSocket message failed", e),
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ShuffleStep {
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultCard {
+    /// Position in the spread, in pick order.
+    pub position: usize,
     pub card_id: String,
-    pub from: Position,
-    pub to: Position,
-    pub duration_ms: u32,
+    pub name: String,
+    pub is_reversed: bool,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct ParticipantInfo {
+    pub participant_id: String,
+    pub role: Role,
+}
+
+impl From<&Participant> for ParticipantInfo {
+    fn from(participant: &Participant) -> Self {
+        Self {
+            participant_id: participant.connection_id.to_string(),
+            role: participant.role,
+        }
+    }
+}
+
+/// Close code for a connection whose session was resumed elsewhere; the
+/// client shouldn't reconnect.
+const CLOSE_SESSION_TAKEN: u16 = 4000;
+/// Close code for a connection left idle too long; the client should only
+/// reconnect once its user is back.
+const CLOSE_IDLE_TIMEOUT: u16 = 4001;
+/// Close code for a connection that stopped answering pings; the client
+/// should reconnect and resume.
+const CLOSE_HEARTBEAT_TIMEOUT: u16 = 4002;
+
+/// Server pings, and how long a connection may go without answering or
+/// without doing anything. 0 turns a check off.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Position {
-    pub x: f32,
-    pub y: f32,
-    pub rotation: f32,
+#[serde(default, deny_unknown_fields)]
+pub struct WebSocketConfig {
+    /// How often each connection is pinged.
+    pub heartbeat_interval_secs: u64,
+    /// A pinged connection that sends nothing back, pongs included, for this
+    /// long is taken for dead.
+    pub heartbeat_timeout_secs: u64,
+    /// A connection with no messages either way, keepalives aside, for this
+    /// long is closed.
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval_secs: 20,
+            heartbeat_timeout_secs: 60,
+            idle_timeout_secs: 1800,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    fn heartbeat_interval(&self) -> Option<Duration> {
+        (self.heartbeat_interval_secs > 0).then(|| Duration::from_secs(self.heartbeat_interval_secs))
+    }
+
+    /// Only connections that are pinged are expected to say anything.
+    fn heartbeat_timeout(&self) -> Option<Duration> {
+        (self.heartbeat_interval_secs > 0 && self.heartbeat_timeout_secs > 0)
+            .then(|| Duration::from_secs(self.heartbeat_timeout_secs))
+    }
+
+    fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout_secs > 0).then(|| Duration::from_secs(self.idle_timeout_secs))
+    }
 }
 
-struct SessionState {
-    session_id: String,
-    query: Option<String>,
-    selected_cards: Vec<usize>,
+/// When a connection last exchanged a message that wasn't a keepalive.
+/// Shared with its send task, since what the room sends counts too.
+struct Activity {
+    opened: Instant,
+    /// Milliseconds after `opened`.
+    last: AtomicU64,
 }
 
-impl SessionState {
+impl Activity {
     fn new() -> Self {
         Self {
-            session_id: Uuid::new_v4().to_string(),
-            query: None,
-            selected_cards: Vec::new(),
+            opened: Instant::now(),
+            last: AtomicU64::new(0),
+        }
+    }
+
+    fn touch(&self) {
+        let elapsed = self.opened.elapsed().as_millis() as u64;
+        self.last.store(elapsed, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.opened + Duration::from_millis(self.last.load(Ordering::Relaxed))
+    }
+}
+
+/// Wait until `at`, or forever if there's nothing to wait for.
+async fn sleep_until(at: Option<Instant>) {
+    match at {
+        Some(at) => time::sleep_until(at).await,
+        None => future::pending().await,
+    }
+}
+
+/// The next heartbeat, or never if heartbeats are off.
+async fn next_tick(heartbeat: &mut Option<Interval>) {
+    match heartbeat {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => future::pending().await,
+    }
+}
+
+/// Longest part of a reader's interpretation accepted in one message.
+const MAX_READER_CHUNK_CHARS: usize = 4000;
+
+/// Asked on the seeker's behalf when a follow-up comes without a question.
+const DEFAULT_FOLLOW_UP_QUESTION: &str = "Tell me more about what these cards are saying to me.";
+
+/// One WebSocket connection, as the rooms it joins see it.
+struct Connection {
+    id: Uuid,
+    tx: mpsc::Sender<ServerEnvelope>,
+    hang_up: Arc<Notify>,
+    activity: Arc<Activity>,
+}
+
+impl Connection {
+    fn participant(&self, role: Role, protocol: &Protocol) -> Participant {
+        Participant {
+            connection_id: self.id,
+            role,
+            sender: self.tx.clone(),
+            capabilities: protocol.capabilities().to_vec(),
+            hang_up: self.hang_up.clone(),
         }
     }
 }
 
+/// Sends the replies to one client message, tagged with its request id.
+struct Outbox<'a> {
+    connection: &'a Connection,
+    request_id: Option<String>,
+}
+
+impl<'a> Outbox<'a> {
+    fn new(connection: &'a Connection, request_id: Option<String>) -> Self {
+        Self { connection, request_id }
+    }
+
+    /// Queue a reply without waiting: callers usually hold the room's lock,
+    /// so a connection whose queue is full is hung up like any participant
+    /// that fell too far behind rather than stalling the room.
+    fn send(&self, message: ServerMessage) -> Result<(), mpsc::error::SendError<ServerEnvelope>> {
+        let envelope = ServerEnvelope {
+            request_id: self.request_id.clone(),
+            message,
+        };
+        match self.connection.tx.try_send(envelope) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.connection.hang_up.notify_one();
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Closed(envelope)) => Err(mpsc::error::SendError(envelope)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConnectParams {
+    /// Bearer token; browsers can't set headers on a WebSocket handshake.
+    token: Option<String>,
+}
+
 pub async fn ws_upgrade(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Query(params): Query<ConnectParams>,
+    headers: HeaderMap,
+) -> Response {
+    let client_ip = state.quota.client_ip(&headers, peer);
+
+    // Stop other sites from opening sessions with a visitor's browser.
+    // Non-browser clients send no Origin and are let through.
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        if !state.config.cors.allows_origin(origin) {
+            warn!(ip = %client_ip, origin = ?origin, "Rejected WebSocket upgrade from disallowed origin");
+            return AppError::new(ErrorCode::Forbidden, "origin not allowed").into_response();
+        }
+    }
+
+    let token = params
+        .token
+        .as_deref()
+        .or_else(|| auth::bearer_token(&headers));
+    let user_id = match token {
+        Some(token) => match state.auth.authenticate(token).await {
+            Ok(user) => Some(user.id),
+            Err(e) => return e.into_response(),
+        },
+        None => None,
+    };
+
+    let limits = state.rate_limit.config();
+    let ws = ws
+        .max_frame_size(limits.ws_max_frame_bytes)
+        .max_message_size(limits.ws_max_message_bytes);
+
+    match state.rate_limit.acquire_ws(client_ip) {
+        Some(guard) => ws.on_upgrade(move |socket| {
+            handle_socket(socket, state, client_ip, user_id, guard)
+        }),
+        None => {
+            warn!(ip = %client_ip, "Too many WebSocket connections from this IP");
+            // Browsers can't read the status of a refused upgrade, so accept
+            // it and close straight away with a code the client can act on
+            ws.on_upgrade(|mut socket| async move {
+                let _ = socket
+                    .send(Message::Close(Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "too many connections".into(),
+                    })))
+                    .await;
+            })
+        }
+    }
 }
 
-async fn handle_socket(socket: WebSocket, app_state: Arc<AppState>) {
+async fn handle_socket(
+    socket: WebSocket,
+    app_state: Arc<AppState>,
+    client_ip: IpAddr,
+    user_id: Option<i64>,
+    _connection: WsConnectionGuard,
+) {
     let (mut sender, mut receiver) = socket.split();
-    let (tx, mut rx) = mpsc::channel::<ServerMessage>(32);
-    let mut session = SessionState::new();
+    let (tx, mut rx) = mpsc::channel::<ServerEnvelope>(32);
+    let (close_tx, mut close_rx) = oneshot::channel::<CloseFrame>();
+    let connection = Connection {
+        id: Uuid::new_v4(),
+        tx,
+        hang_up: Arc::new(Notify::new()),
+        activity: Arc::new(Activity::new()),
+    };
+    let mut protocol = Protocol::default();
+    let initial = SessionState::new(client_ip, user_id, Some(connection.participant(Role::Seeker, &protocol)));
+    let mut session_id = initial.session_id.clone();
+    let mut shared: SharedSession = Arc::new(tokio::sync::Mutex::new(initial));
+    app_state.sessions.register(&session_id, shared.clone());
+    let mut message_bucket = app_state.rate_limit.ws_message_bucket();
 
-    info!(session_id = %session.session_id, "WebSocket connection established");
+    info!(session_id = %session_id, user_id = ?user_id, "WebSocket connection established");
+    let _ = Outbox::new(&connection, None)
+        .send(ServerMessage::SessionState(SessionPhase::Idle));
 
+    let timeouts = app_state.config.websocket.clone();
+    let mut heartbeat = timeouts.heartbeat_interval().map(|period| {
+        let mut interval = time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+    let activity = connection.activity.clone();
     let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            match serde_json::to_string(&msg) {
-                Ok(text) => {
-                    if sender.send(Message::Text(text.into())).await.is_err() {
-                        break;
+        loop {
+            // Drain queued messages before a close frame
+            tokio::select! {
+                biased;
+                msg = rx.recv() => {
+                    let Some(msg) = msg else { break };
+                    if !matches!(msg.message, ServerMessage::Pong) {
+                        activity.touch();
                     }
+                    match serde_json::to_string(&msg) {
+                        Ok(text) => {
+                            if sender.send(Message::Text(text.into())).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to serialize message: {}", e);
+                        }
+                    }
+                }
+                frame = &mut close_rx => {
+                    if let Ok(frame) = frame {
+                        let _ = sender.send(Message::Close(Some(frame))).await;
+                    }
+                    break;
                 }
-                Err(e) => {
-                    error!("Failed to serialize message: {}", e);
+                _ = next_tick(&mut heartbeat) => {
+                    if sender.send(Message::Ping(Default::default())).await.is_err() {
+                        break;
+                    }
                 }
             }
         }
     });
 
-    while let Some(result) = receiver.next().await {
+    let mut last_frame = Instant::now();
+    let mut close_frame = None;
+    loop {
+        let dead_at = timeouts.heartbeat_timeout().map(|timeout| last_frame + timeout);
+        let idle_at = timeouts
+            .idle_timeout()
+            .map(|timeout| connection.activity.last() + timeout);
+        let result = tokio::select! {
+            result = receiver.next() => match result {
+                Some(result) => result,
+                None => break,
+            },
+            // Sent away by the room: ended, taken over or too far behind
+            _ = connection.hang_up.notified() => {
+                close_frame = Some(hang_up_frame(&*shared.lock().await, connection.id));
+                break;
+            }
+            _ = sleep_until(dead_at) => {
+                warn!(session_id = %session_id, ip = %client_ip, "WebSocket heartbeat timed out");
+                close_frame = Some(CloseFrame {
+                    code: CLOSE_HEARTBEAT_TIMEOUT,
+                    reason: "heartbeat timeout".into(),
+                });
+                break;
+            }
+            _ = sleep_until(idle_at) => {
+                // The room may have sent something while this slept
+                if connection.activity.last() + timeouts.idle_timeout().unwrap_or_default() > Instant::now() {
+                    continue;
+                }
+                info!(session_id = %session_id, "WebSocket connection idle; closing");
+                close_frame = Some(CloseFrame {
+                    code: CLOSE_IDLE_TIMEOUT,
+                    reason: "idle timeout".into(),
+                });
+                break;
+            }
+            _ = app_state.shutting_down() => {
+                close_frame = Some(CloseFrame {
+                    code: close_code::RESTART,
+                    reason: "server restarting".into(),
+                });
+                break;
+            }
+        };
+        last_frame = Instant::now();
         match result {
             Ok(msg) => {
-                if let Err(e) = process_message(msg, &mut session, &app_state, &tx).await {
-                    warn!(session_id = %session.session_id, "Error processing message: {}", e);
-                    let _ = tx
-                        .send(ServerMessage::Error {
-                            message: e.to_string(),
-                        })
-                        .await;
+                let throttled = message_bucket
+                    .as_mut()
+                    .is_some_and(|bucket| bucket.try_take().is_err());
+                if throttled {
+                    warn!(session_id = %session_id, ip = %client_ip, "WebSocket message rate exceeded");
+                    close_frame = Some(CloseFrame {
+                        code: close_code::POLICY,
+                        reason: "message rate exceeded".into(),
+                    });
+                    break;
+                }
+
+                // Pings are answered by the WebSocket layer itself; a pong
+                // only proves the connection is alive
+                if matches!(msg, Message::Ping(_) | Message::Pong(_)) {
+                    continue;
+                }
+
+                // Held for the whole message, so a resume elsewhere waits for
+                // an interpretation in progress to finish
+                let current = shared.clone();
+                let mut session = current.lock().await;
+                if session.participant(connection.id).is_none() {
+                    close_frame = Some(hang_up_frame(&session, connection.id));
+                    break;
+                }
+
+                if let Some(resumed) =
+                    process_message(msg, &mut session, &current, &mut protocol, &app_state, &connection).await
+                {
+                    drop(session);
+                    app_state.sessions.remove(&session_id);
+                    session_id = resumed.lock().await.session_id.clone();
+                    shared = resumed;
+                    continue;
+                }
+
+                if session.machine.phase() == SessionPhase::Closed {
+                    close_frame = Some(CloseFrame {
+                        code: close_code::NORMAL,
+                        reason: "session ended".into(),
+                    });
+                    break;
                 }
             }
             Err(e) => {
-                error!(session_id = %session.session_id, "WebSocket error: {}", e);
+                let message = e.to_string();
+                if is_oversized(e) {
+                    warn!(session_id = %session_id, ip = %client_ip, "WebSocket message too large");
+                    close_frame = Some(CloseFrame {
+                        code: close_code::SIZE,
+                        reason: "message too large".into(),
+                    });
+                } else {
+                    error!(session_id = %session_id, "WebSocket error: {}", message);
+                }
                 break;
             }
         }
     }
 
-    info!(session_id = %session.session_id, "WebSocket connection closed");
-    send_task.abort();
+    info!(session_id = %session_id, "WebSocket connection closed");
+    if let Some(role) = app_state.sessions.detach(&shared, connection.id).await {
+        let session = shared.lock().await;
+        let left = ServerMessage::ParticipantLeft {
+            participant_id: connection.id.to_string(),
+            role,
+        };
+        for participant in &session.participants {
+            deliver(participant, None, left.clone());
+        }
+    }
+    match close_frame {
+        Some(frame) => {
+            // Let the send task deliver the close frame before tearing down
+            let _ = close_tx.send(frame);
+            let _ = tokio::time::timeout(tokio::time::Duration::from_secs(1), send_task).await;
+        }
+        None => send_task.abort(),
+    }
+}
+
+/// Why a connection that is no longer in its session's room is closed.
+fn hang_up_frame(session: &SessionState, connection_id: Uuid) -> CloseFrame {
+    if session.machine.phase() == SessionPhase::Closed {
+        CloseFrame {
+            code: close_code::NORMAL,
+            reason: "session ended".into(),
+        }
+    } else if session.participant(connection_id).is_none() {
+        info!(session_id = %session.session_id, "Session resumed on another connection");
+        CloseFrame {
+            code: CLOSE_SESSION_TAKEN,
+            reason: "session resumed on another connection".into(),
+        }
+    } else {
+        warn!(session_id = %session.session_id, "Room participant fell too far behind");
+        CloseFrame {
+            code: close_code::POLICY,
+            reason: "too far behind".into(),
+        }
+    }
 }
 
+/// Queue a message for another participant without waiting: one that can't
+/// keep up is sent away rather than holding up the whole room.
+fn deliver(participant: &Participant, request_id: Option<String>, message: ServerMessage) {
+    let envelope = ServerEnvelope { request_id, message };
+    if let Err(mpsc::error::TrySendError::Full(_)) = participant.sender.try_send(envelope) {
+        participant.hang_up.notify_one();
+    }
+}
+
+/// Send to everyone in the room; only the connection that asked gets its
+/// request id back.
+fn announce(
+    session: &SessionState,
+    tx: &Outbox<'_>,
+    message: ServerMessage,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    for participant in &session.participants {
+        if participant.connection_id != tx.connection.id {
+            deliver(participant, None, message.clone());
+        }
+    }
+    tx.send(message)?;
+    Ok(())
+}
+
+/// Whether a receive error came from a frame or message over the size limits.
+fn is_oversized(error: axum::Error) -> bool {
+    matches!(
+        error.into_inner().downcast::<tungstenite::Error>().map(|e| *e),
+        Ok(tungstenite::Error::Capacity(_))
+    )
+}
+
+/// A parsed client message, or why it couldn't be parsed. The request id is
+/// recovered even from messages that fail to parse, so the error can echo it.
+fn parse_message(bytes: &[u8]) -> (Option<String>, Result<ClientMessage, serde_json::Error>) {
+    #[derive(Deserialize)]
+    struct RequestId {
+        request_id: Option<String>,
+    }
+
+    match serde_json::from_slice::<ClientEnvelope>(bytes) {
+        Ok(envelope) => (envelope.request_id, Ok(envelope.message)),
+        Err(e) => {
+            let request_id = serde_json::from_slice::<RequestId>(bytes)
+                .ok()
+                .and_then(|r| r.request_id);
+            (request_id, Err(e))
+        }
+    }
+}
+
+/// Handles one frame and reports any error to the client. Returns the
+/// session the connection now drives if the message resumed one.
 async fn process_message(
     msg: Message,
     session: &mut SessionState,
+    shared: &SharedSession,
+    protocol: &mut Protocol,
     app_state: &Arc<AppState>,
-    tx: &mpsc::Sender<ServerMessage>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    match msg {
-        Message::Text(text) => {
-            let client_msg: ClientMessage = serde_json::from_str(&text)?;
-            handle_client_message(client_msg, session, app_state, tx).await?;
-        }
-        Message::Binary(data) => {
-            let client_msg: ClientMessage = serde_json::from_slice(&data)?;
-            handle_client_message(client_msg, session, app_state, tx).await?;
-        }
-        Message::Ping(_) => {
-            tx.send(ServerMessage::Pong).await?;
-        }
-        Message::Pong(_) => {}
+    connection: &Connection,
+) -> Option<SharedSession> {
+    let (request_id, parsed) = match msg {
+        Message::Text(text) => parse_message(text.as_bytes()),
+        Message::Binary(data) => parse_message(&data),
+        Message::Ping(_) | Message::Pong(_) => return None,
         Message::Close(_) => {
             info!(session_id = %session.session_id, "Client initiated close");
+            return None;
+        }
+    };
+
+    // A JSON ping keeps the connection alive, not the user's session
+    if !matches!(parsed, Ok(ClientMessage::Ping)) {
+        connection.activity.touch();
+    }
+
+    let out = Outbox::new(connection, request_id);
+    let result = match parsed {
+        Ok(client_msg) => handle_client_message(client_msg, session, shared, protocol, app_state, &out).await,
+        Err(e) => Err(e.into()),
+    };
+
+    match result {
+        Ok(resumed) => resumed,
+        Err(e) => {
+            warn!(session_id = %session.session_id, "Error processing message: {}", e);
+            let error = app_error(e);
+            let _ = out
+                .send(ServerMessage::Error {
+                    code: error.code,
+                    message: error.message,
+                    retryable: error.retryable,
+                    reset_at: error.reset_at,
+                });
+            None
         }
     }
-    Ok(())
 }
 
+/// Handles one message; a successful resume returns the session the
+/// connection now drives.
 async fn handle_client_message(
     msg: ClientMessage,
     session: &mut SessionState,
+    shared: &SharedSession,
+    protocol: &mut Protocol,
     app_state: &Arc<AppState>,
-    tx: &mpsc::Sender<ServerMessage>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    tx: &Outbox<'_>,
+) -> Result<Option<SharedSession>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(action) = msg.action() {
+        session.permit(tx.connection.id, action)?;
+    }
+
     match msg {
-        ClientMessage::StartSession { query } => {
-            handle_start_session(query, session, app_state, tx).await?;
+        ClientMessage::Hello {
+            protocol_version,
+            capabilities,
+        } => {
+            protocol.negotiate(protocol_version, &capabilities)?;
+            if let Some(participant) = session.participant_mut(tx.connection.id) {
+                participant.capabilities = protocol.capabilities().to_vec();
+            }
+            info!(
+                session_id = %session.session_id,
+                version = protocol.version,
+                capabilities = ?protocol.capabilities(),
+                "Protocol negotiated"
+            );
+            tx.send(ServerMessage::Hello {
+                protocol_version: protocol.version,
+                capabilities: protocol.capabilities().to_vec(),
+                server_version: env!("CARGO_PKG_VERSION"),
+            })?;
+        }
+        ClientMessage::StartSession {
+            query,
+            spread_size,
+            layout,
+        } => {
+            if spread_size.is_some_and(|size| size != DEFAULT_SPREAD_SIZE) {
+                protocol.require(Capability::Spreads, "spread_size")?;
+            }
+            let spread_size = spread_size.unwrap_or(DEFAULT_SPREAD_SIZE);
+            let layout = layout.unwrap_or_default();
+            handle_start_session(query, spread_size, layout, session, app_state, tx).await?;
         }
         ClientMessage::SelectCard { card_index } => {
-            handle_select_card(card_index, session, app_state, tx).await?;
+            handle_select_card(card_index, session, tx).await?;
+        }
+        ClientMessage::RequestInterpretation { bypass_cache, question } => {
+            handle_request_interpretation(bypass_cache, question, session, shared, protocol, app_state, tx).await?;
+        }
+        ClientMessage::CancelInterpretation => {
+            session.machine.check(Action::CancelInterpretation)?;
+            info!(session_id = %session.session_id, "Interpretation cancelled by client");
+            // The interpretation task reports the cancellation and new state,
+            // unless a reader is writing it
+            if let Some(cancel) = session.cancel_interpretation.take() {
+                cancel.notify_one();
+            }
+            if session.reader_interpretation.is_some() {
+                finish_reader_interpretation(true, session, app_state, tx).await?;
+            }
+        }
+        ClientMessage::ReaderChunk { text } => {
+            handle_reader_chunk(text, session, tx).await?;
         }
-        ClientMessage::RequestInterpretation => {
-            handle_request_interpretation(session, app_state, tx).await?;
+        ClientMessage::ReaderComplete => {
+            session.machine.check(Action::WriteInterpretation)?;
+            match &session.reader_interpretation {
+                None => return Err(SessionError::NotReaderWritten.into()),
+                Some(reading) if reading.parts.is_empty() => {
+                    return Err(AppError::new(ErrorCode::InvalidRequest, "nothing has been written yet").into());
+                }
+                Some(_) => finish_reader_interpretation(false, session, app_state, tx).await?,
+            }
         }
         ClientMessage::Shuffle => {
-            handle_shuffle(session, tx).await?;
+            handle_shuffle(session, app_state, tx).await?;
+        }
+        ClientMessage::EndSession => {
+            info!(session_id = %session.session_id, "Session ended by client");
+            session.machine.close()?;
+            if let Some(cancel) = session.cancel_interpretation.take() {
+                cancel.notify_one();
+            }
+            finish_reader_interpretation(true, session, app_state, tx).await?;
+            send_state(session, tx)?;
+            for participant in &session.participants {
+                if participant.connection_id != tx.connection.id {
+                    participant.hang_up.notify_one();
+                }
+            }
+        }
+        ClientMessage::CreateInvite { role } => {
+            session.machine.check(Action::CreateInvite)?;
+            let invite_token = session.create_invite(role)?;
+            info!(session_id = %session.session_id, role = ?role, "Room invite created");
+            tx.send(ServerMessage::RoomInvite {
+                session_id: session.session_id.clone(),
+                role,
+                invite_token,
+            })?;
+        }
+        ClientMessage::JoinRoom {
+            session_id,
+            invite_token,
+        } => {
+            let joined = handle_join_room(&session_id, &invite_token, session, protocol, app_state, tx).await?;
+            return Ok(Some(joined));
+        }
+        ClientMessage::ResumeSession {
+            session_id,
+            resume_token,
+            last_seq,
+        } => {
+            let resumed =
+                handle_resume_session(&session_id, &resume_token, last_seq, session, protocol, app_state, tx)
+                    .await?;
+            return Ok(Some(resumed));
         }
         ClientMessage::Ping => {
-            tx.send(ServerMessage::Pong).await?;
+            tx.send(ServerMessage::Pong)?;
         }
     }
-    Ok(())
+    Ok(None)
+}
+
+fn send_state(
+    session: &SessionState,
+    tx: &Outbox<'_>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    announce(session, tx, ServerMessage::SessionState(session.machine.phase()))
+}
+
+/// Where every card of the session's deck lies right now.
+fn deck_state(session: &SessionState) -> ServerMessage {
+    let machine = &session.machine;
+    ServerMessage::DeckState {
+        layout: session.layout,
+        card_positions: layout::table(session.layout, machine.table(), machine.selected(), machine.spread_size()),
+    }
+}
+
+fn slot_position(session: &SessionState, slot: usize) -> Position {
+    let machine = &session.machine;
+    layout::slot_position(session.layout, machine.deck_size(), slot, machine.spread_size())
 }
 
 async fn handle_start_session(
     query: String,
+    spread_size: usize,
+    layout: DeckLayout,
     session: &mut SessionState,
     app_state: &Arc<AppState>,
-    tx: &mpsc::Sender<ServerMessage>,
+    tx: &Outbox<'_>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     info!(session_id = %session.session_id, query = %query, "Starting new session");
+    session.machine.check_start(spread_size)?;
+
+    session.query = None;
+    session.guardrails = None;
+    session.reading_id = None;
+    session.transcript.clear();
+
+    let decision = safety::screen_query(
+        app_state,
+        &session.session_id,
+        session.quota_key(),
+        session.client_ip,
+        &query,
+    )
+    .await;
+    if let (Some(category), Some(message)) = (decision.category, decision.response()) {
+        session.machine.reset();
+        tx.send(ServerMessage::SafetyResponse {
+            category,
+            message: message.to_string(),
+        })?;
+        send_state(session, tx)?;
+        return Ok(());
+    }
+
+    session.machine.start(spread_size, app_state.deck.shuffled(&query))?;
+    session.layout = layout;
+
+    session.injection = ai_service::detect_injection(&query);
+    if session.injection.is_suspicious() {
+        warn!(session_id = %session.session_id, signals = ?session.injection.signals, "Possible prompt injection in query");
+    }
 
     session.query = Some(query);
-    session.selected_cards.clear();
+    session.guardrails = decision.guardrails();
+
+    let claim_token = match session.user_id {
+        Some(user_id) => {
+            db::attach_session_user(&app_state.db, &session.session_id, user_id).await?;
+            None
+        }
+        None => {
+            let token = auth::random_hex(32);
+            db::create_anonymous_session(&app_state.db, &session.session_id, &auth::hash_token(&token))
+                .await?
+                .then_some(token)
+        }
+    };
 
     tx.send(ServerMessage::SessionStarted {
         session_id: session.session_id.clone(),
-    })
-    .await?;
+        resume_token: session.rotate_resume_token(),
+        claim_token,
+    })?;
 
-    let card_positions = generate_deck_positions(app_state);
-    tx.send(ServerMessage::DeckState { card_positions }).await?;
+    announce(session, tx, deck_state(session))?;
+    send_state(session, tx)?;
 
     Ok(())
 }
@@ -209,135 +1006,657 @@ async fn handle_start_session(
 async fn handle_select_card(
     card_index: usize,
     session: &mut SessionState,
-    app_state: &Arc<AppState>,
-    tx: &mpsc::Sender<ServerMessage>,
+    tx: &Outbox<'_>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     info!(session_id = %session.session_id, card_index = card_index, "Card selected");
 
-    const DECK_SIZE: usize = 78;
-    if card_index >= DECK_SIZE {
-        return Err(format!("Invalid card index: {}", card_index).into());
-    }
-
-    if session.selected_cards.contains(&card_index) {
-        return Err("Card already selected".into());
-    }
+    let drawn = session.machine.select(card_index)?;
 
-    session.selected_cards.push(card_index);
-
-    let query = session.query.as_deref().unwrap_or("");
-    let drawn_cards = app_state.deck.draw_with_context(query, 1);
-
-    if let Some(drawn) = drawn_cards.first() {
-        tx.send(ServerMessage::CardSelected {
-            card_id: drawn.card.id.clone(),
-            is_reversed: drawn.is_reversed,
-        })
-        .await?;
-    }
+    let selected = ServerMessage::CardSelected {
+        card_id: drawn.card.id,
+        is_reversed: drawn.is_reversed,
+        slot: slot_position(session, drawn.position_index),
+    };
+    announce(session, tx, selected)?;
+    send_state(session, tx)?;
 
     Ok(())
 }
 
 async fn handle_request_interpretation(
+    bypass_cache: bool,
+    question: Option<String>,
     session: &mut SessionState,
+    shared: &SharedSession,
+    protocol: &Protocol,
     app_state: &Arc<AppState>,
-    tx: &mpsc::Sender<ServerMessage>,
+    tx: &Outbox<'_>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     info!(session_id = %session.session_id, "Interpretation requested");
 
-    let query = session
-        .query
-        .as_deref()
-        .ok_or("No session started - please start a session first")?;
+    session.machine.check(Action::RequestInterpretation)?;
 
-    if session.selected_cards.is_empty() {
-        return Err("No cards selected".into());
+    let follow_up = match session.reading_id {
+        Some(reading_id) => {
+            let used = db::count_follow_ups(&app_state.db, reading_id).await?;
+            app_state.quota.check_follow_up(used)?;
+            match follow_up_question(question, session, app_state, tx).await? {
+                Some(follow_up) => Some(follow_up),
+                None => return Ok(()),
+            }
+        }
+        None => {
+            app_state
+                .quota
+                .try_start_reading(session.quota_key(), Some(session.client_ip))?;
+            None
+        }
+    };
+
+    let cards = session.machine.begin_interpretation()?;
+    session.transcript.clear();
+    let cancel = Arc::new(Notify::new());
+    session.cancel_interpretation = Some(cancel.clone());
+    // A reader asking means the reader writes it, from the model's draft
+    let draft = session
+        .participant(tx.connection.id)
+        .is_some_and(|participant| participant.role == Role::Reader);
+    session.reader_interpretation = draft.then(|| ReaderInterpretation {
+        question: follow_up.as_ref().map(|follow_up| follow_up.question.clone()),
+        ..ReaderInterpretation::default()
+    });
+    send_state(session, tx)?;
+
+    // Runs on its own so the connection keeps answering pings and can cancel
+    tokio::spawn(interpret(InterpretationJob {
+        session: shared.clone(),
+        app_state: app_state.clone(),
+        cards,
+        bypass_cache,
+        draft,
+        streaming: protocol.has(Capability::Streaming),
+        requester: tx.connection.id,
+        request_id: tx.request_id.clone(),
+        cancel,
+        reading_id: session.reading_id,
+        follow_up,
+    }));
+
+    Ok(())
+}
+
+/// The seeker's follow-up question, screened like the one that started the
+/// reading. `None` when the screen answered it instead of the cards.
+async fn follow_up_question(
+    question: Option<String>,
+    session: &SessionState,
+    app_state: &Arc<AppState>,
+    tx: &Outbox<'_>,
+) -> Result<Option<FollowUpQuestion>, Box<dyn std::error::Error + Send + Sync>> {
+    let question = question.map(|q| q.trim().to_string()).filter(|q| !q.is_empty());
+    let Some(question) = question else {
+        return Ok(Some(FollowUpQuestion {
+            question: DEFAULT_FOLLOW_UP_QUESTION.to_string(),
+            guardrails: session.guardrails.clone(),
+        }));
+    };
+
+    let decision = safety::screen_query(
+        app_state,
+        &session.session_id,
+        session.quota_key(),
+        session.client_ip,
+        &question,
+    )
+    .await;
+    if let (Some(category), Some(message)) = (decision.category, decision.response()) {
+        tx.send(ServerMessage::SafetyResponse {
+            category,
+            message: message.to_string(),
+        })?;
+        return Ok(None);
     }
 
-    let cards = app_state
-        .deck
-        .draw_with_context(query, session.selected_cards.len());
+    Ok(Some(FollowUpQuestion {
+        question,
+        guardrails: decision.guardrails().or_else(|| session.guardrails.clone()),
+    }))
+}
+
+/// A further question about a reading that was already interpreted.
+pub struct FollowUpQuestion {
+    pub question: String,
+    /// From screening the question, else those of the reading.
+    pub guardrails: Option<String>,
+}
 
-    let interpretation = ai_service::generate_interpretation(query, &cards).await;
+/// One interpretation, generated and streamed outside the receive loop.
+pub struct InterpretationJob {
+    pub session: SharedSession,
+    pub app_state: Arc<AppState>,
+    pub cards: Vec<DrawnCard>,
+    pub bypass_cache: bool,
+    /// Only a draft for the room's readers is wanted.
+    pub draft: bool,
+    /// Chunking follows the connection that asked.
+    pub streaming: bool,
+    pub requester: Uuid,
+    pub request_id: Option<String>,
+    pub cancel: Arc<Notify>,
+    /// The reading, when it was saved before being interpreted or this
+    /// is a follow-up to it.
+    pub reading_id: Option<i64>,
+    /// Answered from the reading's last interpretation and added to its
+    /// messages, instead of interpreting the reading.
+    pub follow_up: Option<FollowUpQuestion>,
+}
 
-    let chunks: Vec<&str> = interpretation
-        .split(". ")
-        .filter(|s| !s.is_empty())
-        .collect();
+impl InterpretationJob {
+    /// Send to whoever is in the room now. A connection that dropped misses
+    /// nothing: resuming replays the transcript.
+    fn send(&self, session: &SessionState, message: ServerMessage) {
+        self.send_if(session, message, |_| true);
+    }
 
-    for (i, chunk) in chunks.iter().enumerate() {
-        let text = if i < chunks.len() - 1 {
-            format!("{}. ", chunk)
+    fn send_if(&self, session: &SessionState, message: ServerMessage, wanted: impl Fn(&Participant) -> bool) {
+        for participant in session.participants.iter().filter(|p| wanted(p)) {
+            let request_id = if participant.connection_id == self.requester {
+                self.request_id.clone()
+            } else {
+                None
+            };
+            deliver(participant, request_id, message.clone());
+        }
+    }
+}
+
+pub async fn interpret(job: InterpretationJob) {
+    let (session_id, quota_key, client_ip, query, guardrails, injection) = {
+        let session = job.session.lock().await;
+        (
+            session.session_id.clone(),
+            session.quota_key().to_string(),
+            session.client_ip,
+            session.query.clone().unwrap_or_default(),
+            session.guardrails.clone(),
+            session.injection.clone(),
+        )
+    };
+    let app_state = &job.app_state;
+
+    let previous = match (&job.follow_up, job.reading_id) {
+        (Some(_), Some(reading_id)) => last_answer(app_state, reading_id).await,
+        _ => String::new(),
+    };
+    let guardrails = match &job.follow_up {
+        Some(follow_up) => follow_up.guardrails.clone(),
+        None => guardrails,
+    };
+    let request = InterpretationRequest {
+        query: &query,
+        cards: &job.cards,
+        guardrails: guardrails.as_deref(),
+        follow_up: job.follow_up.as_ref().map(|follow_up| FollowUp {
+            question: &follow_up.question,
+            previous: &previous,
+        }),
+        bypass_cache: job.bypass_cache,
+        offline_only: !app_state.quota.has_token_budget(&quota_key, Some(client_ip)),
+    };
+    // Dropping the generation future aborts the provider request
+    let generated = tokio::select! {
+        interpretation = app_state.ai.generate_interpretation(&request) => Some(interpretation),
+        _ = job.cancel.notified() => None,
+    };
+    let mut cancelled = generated.is_none();
+
+    if let Some(usage) = generated.as_ref().and_then(|i| i.usage.as_ref()) {
+        let tokens = (usage.prompt_tokens + usage.completion_tokens) as u64;
+        app_state.quota.record_tokens(&quota_key, Some(client_ip), tokens);
+    }
+    if job.draft {
+        if let Some(interpretation) = generated {
+            offer_draft(&job, interpretation).await;
+        }
+        return;
+    }
+
+    let mut delivered = String::new();
+    if let Some(interpretation) = &generated {
+        info!(session_id = %session_id, source = ?interpretation.source, "Interpretation ready");
+
+        // Without streaming the whole text goes out as a single chunk
+        let chunks = if job.streaming {
+            split_sentences(&interpretation.text)
         } else {
-            chunk.to_string()
+            vec![interpretation.text.clone()]
         };
+        let last = chunks.len().saturating_sub(1);
+
+        for (i, text) in chunks.into_iter().enumerate() {
+            {
+                let mut session = job.session.lock().await;
+                let seq = session.transcript.push(TranscriptEvent::Chunk(text.clone()));
+                delivered.push_str(&text);
+                job.send(&session, ServerMessage::InterpretationChunk { seq, text });
+            }
+
+            if job.streaming && i < last {
+                tokio::select! {
+                    _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {}
+                    _ = job.cancel.notified() => {
+                        cancelled = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // A cancelled reading keeps what was delivered, flagged as cancelled
+    let text = match &generated {
+        Some(interpretation) if !cancelled => interpretation.text.clone(),
+        _ => delivered,
+    };
+    let usage = generated.as_ref().and_then(|i| i.usage.as_ref());
+    let saved = match (job.reading_id, &job.follow_up) {
+        (Some(reading_id), Some(follow_up)) => {
+            db::save_follow_up(&app_state.db, &session_id, reading_id, &follow_up.question, &text, usage)
+                .await
+                .map(|()| reading_id)
+        }
+        (Some(reading_id), None) => {
+            db::save_interpretation(&app_state.db, &session_id, reading_id, &query, &text, usage)
+                .await
+                .map(|()| reading_id)
+        }
+        (None, _) => {
+            let injection_json = serde_json::to_value(&injection).unwrap_or_default();
+            db::save_interpreted_reading(&app_state.db, &session_id, &query, &job.cards, &text, &injection_json, usage)
+                .await
+        }
+    };
+    match &saved {
+        // A stopped follow-up leaves the reading itself as it was
+        Ok(reading_id) if cancelled && job.follow_up.is_none() => {
+            if let Err(e) = db::mark_interpretation_cancelled(&app_state.db, *reading_id).await {
+                error!(session_id = %session_id, "Failed to mark reading as cancelled: {}", e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => error!(session_id = %session_id, "Failed to save reading: {}", e),
+    }
 
-        tx.send(ServerMessage::InterpretationChunk { text }).await?;
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let mut session = job.session.lock().await;
+    session.cancel_interpretation = None;
+    if let (None, Ok(reading_id)) = (session.reading_id, &saved) {
+        session.reading_id = Some(*reading_id);
     }
 
-    tx.send(ServerMessage::InterpretationComplete).await?;
+    if cancelled {
+        info!(session_id = %session_id, "Interpretation stopped");
+        let seq = session.transcript.push(TranscriptEvent::Cancelled);
+        job.send(&session, ServerMessage::InterpretationCancelled { seq });
+        session.machine.abort_interpretation();
+    } else {
+        if let Some(interpretation) = generated {
+            let cards = job
+                .cards
+                .iter()
+                .map(|drawn| ResultCard {
+                    position: drawn.position_index,
+                    card_id: drawn.card.id.clone(),
+                    name: drawn.card.name.clone(),
+                    is_reversed: drawn.is_reversed,
+                })
+                .collect();
+            let result = ServerMessage::InterpretationResult {
+                reading_id: saved.ok(),
+                source: interpretation.source,
+                cards,
+                text: interpretation.text,
+            };
+            job.send_if(&session, result, |p| p.capabilities.contains(&Capability::StructuredOutput));
+        }
+        let seq = session.transcript.push(TranscriptEvent::Complete);
+        job.send(&session, ServerMessage::InterpretationComplete { seq });
+        session.machine.finish_interpretation();
+    }
 
-    let cards_json = serde_json::to_value(&cards).unwrap_or_default();
-    let _ = db::save_reading(
-        &app_state.db,
-        &session.session_id,
-        query,
-        &cards_json,
-        &interpretation,
-    )
-    .await;
+    // Unless the session was ended meanwhile
+    if session.machine.phase() != SessionPhase::Closed {
+        let phase = session.machine.phase();
+        job.send(&session, ServerMessage::SessionState(phase));
+    }
+}
+
+/// The reading's latest interpretation, which a follow-up is asked about.
+async fn last_answer(app_state: &AppState, reading_id: i64) -> String {
+    match db::get_messages_for_reading(&app_state.db, reading_id).await {
+        Ok(messages) => messages
+            .into_iter()
+            .rev()
+            .find(|message| message.role == "assistant")
+            .map(|message| message.content)
+            .unwrap_or_default(),
+        Err(e) => {
+            error!(reading_id, "Failed to load the reading's messages: {}", e);
+            String::new()
+        }
+    }
+}
+
+/// Hand the model's interpretation to the room's readers to write from,
+/// unless the reading it was for is already over.
+async fn offer_draft(job: &InterpretationJob, interpretation: Interpretation) {
+    let mut session = job.session.lock().await;
+    let current = session
+        .cancel_interpretation
+        .as_ref()
+        .is_some_and(|cancel| Arc::ptr_eq(cancel, &job.cancel));
+    let Some(reading) = session.reader_interpretation.as_mut().filter(|_| current) else {
+        return;
+    };
+
+    reading.draft = Some(interpretation.text.clone());
+    reading.draft_usage = interpretation.usage;
+    info!(session_id = %session.session_id, source = ?interpretation.source, "Interpretation draft ready");
+    let draft = ServerMessage::InterpretationDraft {
+        text: interpretation.text,
+    };
+    job.send_if(&session, draft, |p| p.role == Role::Reader);
+}
+
+async fn handle_reader_chunk(
+    text: String,
+    session: &mut SessionState,
+    tx: &Outbox<'_>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    session.machine.check(Action::WriteInterpretation)?;
+    if text.trim().is_empty() || text.chars().count() > MAX_READER_CHUNK_CHARS {
+        return Err(AppError::new(
+            ErrorCode::InvalidRequest,
+            format!("text must be between 1 and {} characters", MAX_READER_CHUNK_CHARS),
+        )
+        .into());
+    }
+    let reading = session
+        .reader_interpretation
+        .as_mut()
+        .ok_or(SessionError::NotReaderWritten)?;
+
+    let authorship = reading.push(text.clone());
+    info!(session_id = %session.session_id, authorship = authorship.as_str(), "Reader sent interpretation");
+    let seq = session.transcript.push(TranscriptEvent::Chunk(text.clone()));
+    announce(session, tx, ServerMessage::InterpretationChunk { seq, text })
+}
 
+/// Save what the reader wrote and end the interpretation for the room,
+/// completed or cancelled. Does nothing if no reader is writing one.
+async fn finish_reader_interpretation(
+    cancelled: bool,
+    session: &mut SessionState,
+    app_state: &Arc<AppState>,
+    tx: &Outbox<'_>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(reading) = session.reader_interpretation.take() else {
+        return Ok(());
+    };
+    // Stops a draft that is still being generated
+    if let Some(cancel) = session.cancel_interpretation.take() {
+        cancel.notify_one();
+    }
+
+    let query = session.query.clone().unwrap_or_default();
+    let injection_json = serde_json::to_value(&session.injection).unwrap_or_default();
+    let saved = match session.reading_id {
+        // A follow-up only adds to the reading's messages; its parts and
+        // authorship stay those of the first interpretation
+        Some(reading_id) => db::save_follow_up(
+            &app_state.db,
+            &session.session_id,
+            reading_id,
+            reading.question.as_deref().unwrap_or(&query),
+            &reading.text(),
+            reading.draft_usage.as_ref(),
+        )
+        .await
+        .map(|()| None),
+        None => db::save_interpreted_reading(
+            &app_state.db,
+            &session.session_id,
+            &query,
+            &session.machine.selected_cards(),
+            &reading.text(),
+            &injection_json,
+            reading.draft_usage.as_ref(),
+        )
+        .await
+        .map(Some),
+    };
+    match saved {
+        Ok(None) => {}
+        Ok(Some(reading_id)) => {
+            session.reading_id.get_or_insert(reading_id);
+            let mut recorded = db::save_interpretation_parts(
+                &app_state.db,
+                reading_id,
+                reading.authorship(),
+                reading.draft.as_deref(),
+                &reading.parts,
+            )
+            .await;
+            if cancelled && recorded.is_ok() {
+                recorded = db::mark_interpretation_cancelled(&app_state.db, reading_id).await;
+            }
+            if let Err(e) = recorded {
+                error!(session_id = %session.session_id, "Failed to record reader interpretation: {}", e);
+            }
+        }
+        Err(e) => error!(session_id = %session.session_id, "Failed to save reading: {}", e),
+    }
+
+    if cancelled {
+        info!(session_id = %session.session_id, "Reader interpretation stopped");
+        let seq = session.transcript.push(TranscriptEvent::Cancelled);
+        announce(session, tx, ServerMessage::InterpretationCancelled { seq })?;
+        session.machine.abort_interpretation();
+    } else {
+        info!(session_id = %session.session_id, authorship = reading.authorship().as_str(), "Reader interpretation complete");
+        let seq = session.transcript.push(TranscriptEvent::Complete);
+        announce(session, tx, ServerMessage::InterpretationComplete { seq })?;
+        session.machine.finish_interpretation();
+    }
+
+    if session.machine.phase() != SessionPhase::Closed {
+        send_state(session, tx)?;
+    }
     Ok(())
 }
 
 async fn handle_shuffle(
     session: &mut SessionState,
-    tx: &mpsc::Sender<ServerMessage>,
+    app_state: &Arc<AppState>,
+    tx: &Outbox<'_>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     info!(session_id = %session.session_id, "Shuffle requested");
 
-    session.selected_cards.clear();
-    let sequence = generate_shuffle_animation();
-    tx.send(ServerMessage::ShuffleAnimation { sequence }).await?;
+    let query = session.query.as_deref().unwrap_or_default();
+    let before = session.machine.table().to_vec();
+    session.machine.shuffle(app_state.deck.shuffled(query))?;
+    session.reading_id = None;
+    session.transcript.clear();
+
+    let sequence = layout::choreograph(&before, session.machine.table());
+    announce(session, tx, ServerMessage::ShuffleAnimation { sequence })?;
+    announce(session, tx, deck_state(session))?;
+    send_state(session, tx)?;
 
     Ok(())
 }
 
-fn generate_deck_positions(_app_state: &Arc<AppState>) -> Vec<CardPosition> {
-    (0..78)
-        .map(|i| CardPosition {
-            card_id: format!("card_{}", i),
-            x: i as f32 * 0.5,
-            y: -(i as f32 * 0.2),
-            rotation: 0.0,
-            is_face_up: false,
-            z_index: i,
-        })
-        .collect()
+async fn handle_resume_session(
+    session_id: &str,
+    resume_token: &str,
+    last_seq: Option<u64>,
+    session: &mut SessionState,
+    protocol: &Protocol,
+    app_state: &Arc<AppState>,
+    tx: &Outbox<'_>,
+) -> Result<SharedSession, Box<dyn std::error::Error + Send + Sync>> {
+    info!(session_id = %session_id, "Resume requested");
+
+    session.machine.check(Action::ResumeSession)?;
+    // The connection's own session is locked by the caller
+    if session_id == session.session_id {
+        return Err(SessionError::ResumeFailed.into());
+    }
+
+    let seeker = tx.connection.participant(Role::Seeker, protocol);
+    let resumed = app_state
+        .sessions
+        .resume(session_id, resume_token, session.user_id, seeker)
+        .await?;
+
+    {
+        let mut state = resumed.lock().await;
+        state.client_ip = session.client_ip;
+
+        tx.send(ServerMessage::SessionResumed {
+            session_id: state.session_id.clone(),
+            resume_token: state.rotate_resume_token(),
+        })?;
+        replay(&state, last_seq, tx)?;
+        announce_joined(&state, tx, Role::Seeker);
+        info!(session_id = %state.session_id, "Session resumed");
+    }
+
+    Ok(resumed)
+}
+
+async fn handle_join_room(
+    session_id: &str,
+    invite_token: &str,
+    session: &mut SessionState,
+    protocol: &Protocol,
+    app_state: &Arc<AppState>,
+    tx: &Outbox<'_>,
+) -> Result<SharedSession, Box<dyn std::error::Error + Send + Sync>> {
+    info!(session_id = %session_id, "Room join requested");
+
+    session.machine.check(Action::JoinRoom)?;
+    // The connection's own session is locked by the caller
+    if session_id == session.session_id {
+        return Err(SessionError::InvalidInvite.into());
+    }
+
+    // The invite decides the role
+    let participant = tx.connection.participant(Role::Observer, protocol);
+    let joined = app_state.sessions.join(session_id, invite_token, participant).await?;
+
+    {
+        let state = joined.lock().await;
+        let role = state
+            .participant(tx.connection.id)
+            .map(|participant| participant.role)
+            .ok_or(SessionError::InvalidInvite)?;
+
+        tx.send(ServerMessage::RoomJoined {
+            session_id: state.session_id.clone(),
+            participant_id: tx.connection.id.to_string(),
+            role,
+            participants: state.participants.iter().map(ParticipantInfo::from).collect(),
+        })?;
+        replay(&state, None, tx)?;
+        // A reader joining part way through gets the draft too
+        let draft = state.reader_interpretation.as_ref().and_then(|r| r.draft.clone());
+        if let (Role::Reader, Some(text)) = (role, draft) {
+            tx.send(ServerMessage::InterpretationDraft { text })?;
+        }
+        announce_joined(&state, tx, role);
+        info!(session_id = %state.session_id, role = ?role, "Joined room");
+    }
+
+    Ok(joined)
+}
+
+/// Bring a connection that just came in up to date: the deck, the selection,
+/// the state and any interpretation events after `last_seq`.
+fn replay(
+    state: &SessionState,
+    last_seq: Option<u64>,
+    tx: &Outbox<'_>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if state.machine.deck_size() > 0 {
+        tx.send(deck_state(state))?;
+    }
+    for drawn in state.machine.selected_cards() {
+        tx.send(ServerMessage::CardSelected {
+            card_id: drawn.card.id,
+            is_reversed: drawn.is_reversed,
+            slot: slot_position(state, drawn.position_index),
+        })?;
+    }
+    tx.send(ServerMessage::SessionState(state.machine.phase()))?;
+
+    // Consecutive chunks go out as one, so a long interpretation fits in the
+    // connection's queue; the merged chunk carries the last one's seq
+    let mut pending: Option<(u64, String)> = None;
+    for (seq, event) in state.transcript.since(last_seq) {
+        if let TranscriptEvent::Chunk(text) = event {
+            let merged = pending.get_or_insert_with(|| (*seq, String::new()));
+            merged.0 = *seq;
+            merged.1.push_str(text);
+            continue;
+        }
+        if let Some((seq, text)) = pending.take() {
+            tx.send(ServerMessage::InterpretationChunk { seq, text })?;
+        }
+        tx.send(transcript_message(*seq, event))?;
+    }
+    if let Some((seq, text)) = pending {
+        tx.send(ServerMessage::InterpretationChunk { seq, text })?;
+    }
+    Ok(())
+}
+
+/// How a transcript event was first sent.
+pub fn transcript_message(seq: u64, event: &TranscriptEvent) -> ServerMessage {
+    match event {
+        TranscriptEvent::Chunk(text) => ServerMessage::InterpretationChunk {
+            seq,
+            text: text.clone(),
+        },
+        TranscriptEvent::Complete => ServerMessage::InterpretationComplete { seq },
+        TranscriptEvent::Cancelled => ServerMessage::InterpretationCancelled { seq },
+    }
+}
+
+/// Tell the rest of the room that this connection came in.
+fn announce_joined(state: &SessionState, tx: &Outbox<'_>, role: Role) {
+    let joined = ServerMessage::ParticipantJoined {
+        participant_id: tx.connection.id.to_string(),
+        role,
+    };
+    for participant in &state.participants {
+        if participant.connection_id != tx.connection.id {
+            deliver(participant, None, joined.clone());
+        }
+    }
 }
 
-fn generate_shuffle_animation() -> Vec<ShuffleStep> {
-    use rand::Rng;
-    let mut rng = rand::rng();
-
-    (0..78)
-        .map(|i| {
-            let side = if i % 2 == 0 { -1.0 } else { 1.0 };
-            ShuffleStep {
-                card_id: format!("card_{}", i),
-                from: Position {
-                    x: 0.0,
-                    y: 0.0,
-                    rotation: 0.0,
-                },
-                to: Position {
-                    x: side * rng.random_range(50.0..150.0),
-                    y: rng.random_range(-30.0..30.0),
-                    rotation: rng.random_range(-15.0..15.0),
-                },
-                duration_ms: rng.random_range(200..400),
+/// Split text into sentence-sized chunks for streaming.
+fn split_sentences(text: &str) -> Vec<String> {
+    let sentences: Vec<&str> = text.split(". ").filter(|s| !s.is_empty()).collect();
+    let last = sentences.len().saturating_sub(1);
+    sentences
+        .iter()
+        .enumerate()
+        .map(|(i, sentence)| {
+            if i < last {
+                format!("{}. ", sentence)
+            } else {
+                sentence.to_string()
             }
         })
         .collect()
@@ -0,0 +1,32 @@
+import type { ServerMessage } from './websocket';
+
+/** What `/api/draw` returns for `stream: true`. */
+export interface StreamedDraw {
+    session_id: string;
+    reading_id: number;
+    stream_token: string;
+}
+
+const EVENTS = ['interpretation_chunk', 'interpretation_complete', 'interpretation_cancelled'] as const;
+
+/**
+ * Stream a drawn reading's interpretation over Server-Sent Events, for
+ * networks that block WebSockets. Events carry the same messages as the
+ * WebSocket; the browser reconnects with Last-Event-ID on its own.
+ */
+export function streamInterpretation(
+    draw: StreamedDraw,
+    onMessage: (message: ServerMessage) => void
+): EventSource {
+    const params = new URLSearchParams({ stream_token: draw.stream_token });
+    const source = new EventSource(`/api/readings/${draw.reading_id}/stream?${params}`);
+
+    for (const name of EVENTS) {
+        source.addEventListener(name, event => {
+            onMessage(JSON.parse((event as MessageEvent).data) as ServerMessage);
+            // The server closes after these; don't let the browser reconnect
+            if (name !== 'interpretation_chunk') source.close();
+        });
+    }
+    return source;
+}
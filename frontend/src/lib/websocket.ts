@@ -1,19 +1,104 @@
+/** Protocol version this client speaks; see the backend's protocol.rs. */
+export const PROTOCOL_VERSION = 2;
+
+export type Capability = 'streaming' | 'structured_output' | 'spreads';
+
+const CAPABILITIES: Capability[] = ['streaming', 'structured_output', 'spreads'];
+
+/** Any message may carry a request id; replies to it echo the same id. */
+export type Envelope<T> = T & { request_id?: string };
+
 export type ClientMessage =
-    | { type: 'start_session'; query: string }
+    | { type: 'hello'; protocol_version: number; capabilities: Capability[] }
+    | { type: 'start_session'; query: string; spread_size?: number; layout?: DeckLayout }
     | { type: 'select_card'; card_index: number }
-    | { type: 'request_interpretation' }
+    | { type: 'request_interpretation'; bypass_cache?: boolean; question?: string }
     | { type: 'shuffle' }
+    | { type: 'cancel_interpretation' }
+    | { type: 'reader_chunk'; text: string }
+    | { type: 'reader_complete' }
+    | { type: 'end_session' }
+    | { type: 'create_invite'; role: Exclude<Role, 'seeker'> }
+    | { type: 'join_room'; session_id: string; invite_token: string }
+    | { type: 'resume_session'; session_id: string; resume_token: string; last_seq?: number }
     | { type: 'ping' };
 
-export type ServerMessage =
-    | { type: 'session_started'; session_id: string }
-    | { type: 'deck_state'; card_positions: CardPosition[] }
-    | { type: 'card_selected'; card_id: string; is_reversed: boolean }
-    | { type: 'interpretation_chunk'; text: string }
-    | { type: 'interpretation_complete' }
+export type ServerMessage = Envelope<
+    | { type: 'hello'; protocol_version: number; capabilities: Capability[]; server_version: string }
+    | { type: 'session_started'; session_id: string; resume_token: string; claim_token?: string }
+    | { type: 'session_resumed'; session_id: string; resume_token: string }
+    | { type: 'room_invite'; session_id: string; role: Role; invite_token: string }
+    | {
+          type: 'room_joined';
+          session_id: string;
+          participant_id: string;
+          role: Role;
+          participants: Participant[];
+      }
+    | ({ type: 'participant_joined' } & Participant)
+    | ({ type: 'participant_left' } & Participant)
+    | ({ type: 'session_state' } & SessionPhase)
+    | { type: 'deck_state'; layout: DeckLayout; card_positions: CardPosition[] }
+    | { type: 'card_selected'; card_id: string; is_reversed: boolean; slot: Position }
+    | { type: 'interpretation_draft'; text: string }
+    | { type: 'interpretation_chunk'; seq: number; text: string }
+    | { type: 'interpretation_complete'; seq: number }
+    | { type: 'interpretation_cancelled'; seq: number }
+    | {
+          type: 'interpretation_result';
+          reading_id?: number;
+          source: 'model' | 'cache' | 'fallback';
+          cards: ResultCard[];
+          text: string;
+      }
     | { type: 'shuffle_animation'; sequence: ShuffleStep[] }
-    | { type: 'error'; message: string }
-    | { type: 'pong' };
+    | { type: 'safety_response'; category: SafetyCategory; message: string }
+    | { type: 'error'; code: ErrorCode; message: string; retryable: boolean; reset_at?: number }
+    | { type: 'pong' }
+>;
+
+/** What a connection is to a shared reading room. */
+export type Role = 'seeker' | 'reader' | 'observer';
+
+export interface Participant {
+    participant_id: string;
+    role: Role;
+}
+
+export interface ResultCard {
+    position: number;
+    card_id: string;
+    name: string;
+    is_reversed: boolean;
+}
+
+export type SafetyCategory = 'crisis' | 'medical' | 'legal' | 'financial';
+
+export type SessionPhase =
+    | { state: 'idle' }
+    | { state: 'shuffled' }
+    | { state: 'selecting'; selected: number; spread_size: number }
+    | { state: 'interpreting' }
+    | { state: 'follow_up' }
+    | { state: 'closed' };
+
+export type ErrorCode =
+    | 'INVALID_STATE'
+    | 'CARD_ALREADY_SELECTED'
+    | 'INVALID_CARD'
+    | 'INVALID_REQUEST'
+    | 'BAD_MESSAGE'
+    | 'UNAUTHORIZED'
+    | 'FORBIDDEN'
+    | 'NOT_FOUND'
+    | 'CONFLICT'
+    | 'RATE_LIMITED'
+    | 'QUOTA_EXCEEDED'
+    | 'AI_UNAVAILABLE'
+    | 'UPSTREAM_ERROR'
+    | 'RESUME_FAILED'
+    | 'UNSUPPORTED_VERSION'
+    | 'INTERNAL_ERROR';
 
 export interface CardPosition {
     card_id: string;
@@ -24,10 +109,15 @@ export interface CardPosition {
     z_index: number;
 }
 
+export type DeckLayout = 'fan' | 'circle' | 'stack';
+
 export interface ShuffleStep {
     card_id: string;
+    phase: 'overhand' | 'riffle' | 'cut';
     from: Position;
     to: Position;
+    z_index: number;
+    delay_ms: number;
     duration_ms: number;
 }
 
@@ -49,6 +139,17 @@ export interface WebSocketClientOptions {
     onMessage?: (message: ServerMessage) => void;
 }
 
+/** Close code for a connection whose session was resumed elsewhere. */
+const CLOSE_SESSION_TAKEN = 4000;
+/** Close code for a connection left idle; reconnect once the user acts. */
+const CLOSE_IDLE_TIMEOUT = 4001;
+
+interface ResumeInfo {
+    sessionId: string;
+    resumeToken: string;
+    lastSeq?: number;
+}
+
 const DEFAULT_OPTIONS = {
     url: getWebSocketUrl(),
     reconnect: true,
@@ -80,7 +181,13 @@ export class WebSocketClient {
     private reconnectTimeout: ReturnType<typeof setTimeout> | null = null;
     private pingInterval: ReturnType<typeof setInterval> | null = null;
     private isIntentionallyClosed = false;
+    /** Closed by the server for idling; the next send reconnects. */
+    private idleClosed = false;
     private messageQueue: ClientMessage[] = [];
+    private resume: ResumeInfo | null = null;
+    private nextRequestId = 0;
+    /** What the server agreed to in its `hello` reply. */
+    capabilities: Capability[] = [];
 
     constructor(options: WebSocketClientOptions = {}) {
         this.options = { ...DEFAULT_OPTIONS, ...options };
@@ -103,6 +210,7 @@ export class WebSocketClient {
 
     disconnect(): void {
         this.isIntentionallyClosed = true;
+        this.idleClosed = false;
         this.cleanup();
         
         if (this.ws) {
@@ -129,6 +237,8 @@ export class WebSocketClient {
             console.log('[WS] Connected');
             this.retryCount = 0;
             this.startPingInterval();
+            this.sendNow({ type: 'hello', protocol_version: PROTOCOL_VERSION, capabilities: CAPABILITIES });
+            this.resumeSession();
             this.flushMessageQueue();
             this.options.onOpen?.();
         };
@@ -137,7 +247,20 @@ export class WebSocketClient {
             console.log(`[WS] Disconnected (code: ${event.code}, reason: ${event.reason})`);
             this.cleanup();
             this.options.onClose?.();
-            
+
+            if (event.code === CLOSE_SESSION_TAKEN) {
+                // Another tab or device took over the session
+                this.resume = null;
+                return;
+            }
+
+            if (event.code === CLOSE_IDLE_TIMEOUT) {
+                this.idleClosed = true;
+                return;
+            }
+
+            // Anything else, a restart (1012) or missed heartbeats (4002)
+            // included, reconnects and resumes
             if (!this.isIntentionallyClosed && this.options.reconnect) {
                 this.scheduleReconnect();
             }
@@ -160,9 +283,57 @@ export class WebSocketClient {
 
     private handleMessage(message: ServerMessage): void {
         if (message.type === 'pong') return;
+        if (message.type === 'hello') {
+            this.capabilities = message.capabilities;
+        }
+        this.trackResume(message);
         this.options.onMessage?.(message);
     }
 
+    /** Remembers what a reconnect needs to pick the session back up. */
+    private trackResume(message: ServerMessage): void {
+        switch (message.type) {
+            case 'session_started':
+            case 'session_resumed':
+                this.resume = {
+                    sessionId: message.session_id,
+                    resumeToken: message.resume_token,
+                    lastSeq: this.resume?.sessionId === message.session_id ? this.resume.lastSeq : undefined,
+                };
+                break;
+            case 'interpretation_chunk':
+            case 'interpretation_complete':
+            case 'interpretation_cancelled':
+                if (this.resume) this.resume.lastSeq = message.seq;
+                break;
+            case 'session_state':
+                if (message.state === 'closed') this.resume = null;
+                break;
+            case 'error':
+                if (message.code === 'RESUME_FAILED') this.resume = null;
+                break;
+        }
+    }
+
+    private resumeSession(): void {
+        if (!this.resume) return;
+        this.sendNow({
+            type: 'resume_session',
+            session_id: this.resume.sessionId,
+            resume_token: this.resume.resumeToken,
+            last_seq: this.resume.lastSeq,
+        });
+    }
+
+    /** Sends on the open socket, bypassing the queue; returns the request id. */
+    private sendNow(message: ClientMessage): string | null {
+        if (!this.ws) return null;
+        const requestId = `r${++this.nextRequestId}`;
+        const envelope: Envelope<ClientMessage> = { ...message, request_id: requestId };
+        this.ws.send(JSON.stringify(envelope));
+        return requestId;
+    }
+
     private scheduleReconnect(): void {
         if (this.retryCount >= this.options.maxRetries) {
             console.error(`[WS] Max retries (${this.options.maxRetries}) reached. Giving up.`);
@@ -226,11 +397,15 @@ export class WebSocketClient {
         if (!this.ws || this.ws.readyState !== WebSocket.OPEN) {
             console.warn('[WS] Not connected. Queueing message:', message.type);
             this.messageQueue.push(message);
+            if (this.idleClosed) {
+                this.idleClosed = false;
+                this.connect();
+            }
             return false;
         }
 
         try {
-            this.ws.send(JSON.stringify(message));
+            this.sendNow(message);
             return true;
         } catch (error) {
             console.error('[WS] Failed to send message:', error);
@@ -238,22 +413,48 @@ export class WebSocketClient {
         }
     }
 
-    startSession(query: string): boolean {
-        return this.send({ type: 'start_session', query });
+    startSession(query: string, spreadSize?: number, layout?: DeckLayout): boolean {
+        return this.send({ type: 'start_session', query, spread_size: spreadSize, layout });
     }
 
     selectCard(cardIndex: number): boolean {
         return this.send({ type: 'select_card', card_index: cardIndex });
     }
 
-    requestInterpretation(): boolean {
-        return this.send({ type: 'request_interpretation' });
+    /** In `follow_up`, `question` is the seeker's follow-up question. */
+    requestInterpretation(question?: string): boolean {
+        return this.send({ type: 'request_interpretation', question });
     }
 
     shuffle(): boolean {
         return this.send({ type: 'shuffle' });
     }
 
+    cancelInterpretation(): boolean {
+        return this.send({ type: 'cancel_interpretation' });
+    }
+
+    /** Send the next part of an interpretation this reader is writing. */
+    sendReaderChunk(text: string): boolean {
+        return this.send({ type: 'reader_chunk', text });
+    }
+
+    completeReaderInterpretation(): boolean {
+        return this.send({ type: 'reader_complete' });
+    }
+
+    createInvite(role: Exclude<Role, 'seeker'>): boolean {
+        return this.send({ type: 'create_invite', role });
+    }
+
+    joinRoom(sessionId: string, inviteToken: string): boolean {
+        return this.send({ type: 'join_room', session_id: sessionId, invite_token: inviteToken });
+    }
+
+    endSession(): boolean {
+        return this.send({ type: 'end_session' });
+    }
+
     ping(): boolean {
         return this.send({ type: 'ping' });
     }
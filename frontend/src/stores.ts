@@ -1,5 +1,5 @@
 import { writable, derived } from 'svelte/store';
-import type { CardPosition, ServerMessage } from './lib/websocket';
+import type { CardPosition, Participant, Role, ServerMessage, SessionPhase } from './lib/websocket';
 
 export interface Card {
     id: string;
@@ -20,8 +20,14 @@ export const wsConnected = writable(false);
 export const sessionId = writable<string | null>(null);
 export const interpretation = writable('');
 export const isInterpreting = writable(false);
+/** The model's draft of an interpretation this reader is writing. */
+export const interpretationDraft = writable<string | null>(null);
 export const wsError = writable<string | null>(null);
 export const cardPositions = writable<CardPosition[]>([]);
+export const sessionPhase = writable<SessionPhase>({ state: 'idle' });
+export const role = writable<Role>('seeker');
+/** Everyone else in the reading room. */
+export const participants = writable<Participant[]>([]);
 
 export function handleServerMessage(message: ServerMessage): void {
     switch (message.type) {
@@ -30,7 +36,39 @@ export function handleServerMessage(message: ServerMessage): void {
             interpretation.set('');
             isInterpreting.set(false);
             break;
+
+        case 'room_joined':
+            // The server replays the deck, selection and interpretation
+            sessionId.set(message.session_id);
+            role.set(message.role);
+            participants.set(message.participants.filter(p => p.participant_id !== message.participant_id));
+            drawnCards.set([]);
+            interpretation.set('');
+            wsError.set(null);
+            break;
+
+        case 'participant_joined':
+            participants.update(list => [
+                ...list.filter(p => p.participant_id !== message.participant_id),
+                { participant_id: message.participant_id, role: message.role }
+            ]);
+            break;
+
+        case 'participant_left':
+            participants.update(list => list.filter(p => p.participant_id !== message.participant_id));
+            break;
+
+        case 'session_resumed':
+            // The server replays the selection; interpretation text so far is kept
+            sessionId.set(message.session_id);
+            drawnCards.set([]);
+            wsError.set(null);
+            break;
             
+        case 'session_state':
+            sessionPhase.set(message);
+            break;
+
         case 'deck_state':
             cardPositions.set(message.card_positions);
             break;
@@ -50,13 +88,19 @@ export function handleServerMessage(message: ServerMessage): void {
             });
             break;
             
+        case 'interpretation_draft':
+            interpretationDraft.set(message.text);
+            break;
+
         case 'interpretation_chunk':
             isInterpreting.set(true);
             interpretation.update(text => text + message.text);
             break;
             
         case 'interpretation_complete':
+        case 'interpretation_cancelled':
             isInterpreting.set(false);
+            interpretationDraft.set(null);
             isDrawing.set(false);
             break;
             
@@ -65,6 +109,12 @@ export function handleServerMessage(message: ServerMessage): void {
             interpretation.set('');
             break;
             
+        case 'safety_response':
+            interpretation.set(message.message);
+            isInterpreting.set(false);
+            isDrawing.set(false);
+            break;
+            
         case 'error':
             wsError.set(message.message);
             isDrawing.set(false);
@@ -84,8 +134,10 @@ export function resetSession(): void {
     drawnCards.set([]);
     interpretation.set('');
     isInterpreting.set(false);
+    interpretationDraft.set(null);
     wsError.set(null);
     cardPositions.set([]);
+    sessionPhase.set({ state: 'idle' });
 }
 
 export const hasActiveSession = derived(sessionId, $sessionId => $sessionId !== null);